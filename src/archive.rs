@@ -0,0 +1,578 @@
+//! Archive member support: treating tar and zip files as directories.
+//!
+//! Members of an archive are addressed by a virtual path made of the archive
+//! path followed by the member path, e.g. `release.zip/config/app.json`.
+
+use flate2::read::GzDecoder;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Most bytes reserved up front for a member. Sizes come from the archive's
+/// own headers, so a crafted archive could otherwise ask for any amount.
+const MAX_PREALLOC: u64 = 1 << 20;
+
+/// Size of a tar header block.
+const TAR_BLOCK: u64 = 512;
+
+/// Most tar.gz decoders a reader keeps open for reuse.
+const MAX_GZ_CURSORS: usize = 8;
+
+/// Archive formats that can be expanded into members.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    /// Uncompressed tar archive (`.tar`)
+    Tar,
+    /// Gzip-compressed tar archive (`.tar.gz`, `.tgz`)
+    TarGz,
+    /// Zip archive (`.zip`)
+    Zip,
+}
+
+impl ArchiveKind {
+    /// Detect the archive kind from a file name's extension.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let name = path.as_ref().file_name()?.to_str()?.to_ascii_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// A regular file stored inside an archive.
+#[derive(Debug, Clone)]
+pub struct ArchiveMember {
+    /// Path of the archive on disk
+    pub archive: PathBuf,
+    /// Format of the archive
+    pub kind: ArchiveKind,
+    /// Path of the member inside the archive, `/`-separated
+    pub path: String,
+    /// Uncompressed size in bytes
+    pub size: u64,
+    /// Unix permission bits, if recorded by the archive
+    pub mode: Option<u32>,
+    /// Modification time in seconds since the Unix epoch, if recorded
+    pub modified: Option<u64>,
+    /// Index of the member's entry in the archive, to read it back by
+    pub index: usize,
+    /// Offset of a tar member's data in the uncompressed tar stream
+    pub offset: u64,
+}
+
+impl ArchiveMember {
+    /// Virtual path of this member: the archive path joined with the member path.
+    pub fn virtual_path(&self) -> PathBuf {
+        self.archive.join(&self.path)
+    }
+}
+
+/// List all regular file members of an archive.
+///
+/// This is blocking I/O; async callers should run it via `spawn_blocking`.
+pub fn list_members(archive: &Path, kind: ArchiveKind) -> io::Result<Vec<ArchiveMember>> {
+    match kind {
+        ArchiveKind::Tar => list_tar(archive, kind, BufReader::new(File::open(archive)?)),
+        ArchiveKind::TarGz => list_tar(
+            archive,
+            kind,
+            GzDecoder::new(BufReader::new(File::open(archive)?)),
+        ),
+        ArchiveKind::Zip => list_zip(archive),
+    }
+}
+
+/// Read the full contents of an archive member.
+///
+/// This is blocking I/O; async callers should run it via `spawn_blocking`.
+/// Reading many members of one tar.gz is cheaper through an `ArchiveReader`.
+pub fn read_member(member: &ArchiveMember) -> io::Result<Vec<u8>> {
+    ArchiveReader::new().read(member)
+}
+
+/// Reads archive members, keeping tar.gz decoders open between reads.
+///
+/// A gzip stream can't seek, but members are usually read in archive order,
+/// so the next one tends to lie ahead of where the last read stopped and is
+/// reached by decoding forward from there rather than from the start.
+/// Clones share the kept decoders, which are closed when the last clone is
+/// dropped.
+#[derive(Clone, Default)]
+pub struct ArchiveReader {
+    gz_cursors: Arc<Mutex<Vec<GzCursor>>>,
+}
+
+impl ArchiveReader {
+    /// Create a reader with no decoders kept yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the full contents of an archive member.
+    ///
+    /// This is blocking I/O; async callers should run it via `spawn_blocking`.
+    pub fn read(&self, member: &ArchiveMember) -> io::Result<Vec<u8>> {
+        match member.kind {
+            ArchiveKind::Tar => {
+                if let Some(data) = read_tar_at_offset(member)? {
+                    return Ok(data);
+                }
+                read_tar(member, BufReader::new(File::open(&member.archive)?))
+            }
+            ArchiveKind::TarGz => {
+                if let Some(data) = self.read_tar_gz_at_offset(member)? {
+                    return Ok(data);
+                }
+                read_tar(
+                    member,
+                    GzDecoder::new(BufReader::new(File::open(&member.archive)?)),
+                )
+            }
+            ArchiveKind::Zip => {
+                let mut zip =
+                    zip::ZipArchive::new(File::open(&member.archive)?).map_err(io::Error::other)?;
+                // By index: the stored name may differ from the normalized path
+                let file = zip.by_index(member.index).map_err(io::Error::other)?;
+                if !file.is_file() || normalize_member_path(file.name()) != member.path {
+                    return Err(not_found(member));
+                }
+                read_sized(file, member.size)
+            }
+        }
+    }
+
+    /// Read a tar.gz member by decoding forward to its header, reusing a kept
+    /// decoder that hasn't passed it yet, or None if the header found there
+    /// doesn't match the member.
+    fn read_tar_gz_at_offset(&self, member: &ArchiveMember) -> io::Result<Option<Vec<u8>>> {
+        let Some(start) = member.offset.checked_sub(TAR_BLOCK) else {
+            return Ok(None);
+        };
+        let metadata = std::fs::metadata(&member.archive)?;
+        let version = (metadata.len(), metadata.modified().ok());
+
+        let kept = {
+            let mut cursors = self.gz_cursors.lock().unwrap_or_else(|e| e.into_inner());
+            cursors
+                .iter()
+                .position(|c| {
+                    c.archive == member.archive && c.version == version && c.position <= start
+                })
+                .map(|i| cursors.remove(i))
+        };
+        let mut cursor = match kept {
+            Some(cursor) => cursor,
+            None => GzCursor {
+                archive: member.archive.clone(),
+                version,
+                decoder: GzDecoder::new(BufReader::new(File::open(&member.archive)?)),
+                position: 0,
+            },
+        };
+
+        let gap = start - cursor.position;
+        if io::copy(&mut (&mut cursor.decoder).take(gap), &mut io::sink())? < gap {
+            return Ok(None);
+        }
+        let Some(data) = read_entry(&mut cursor.decoder, member)? else {
+            return Ok(None);
+        };
+        cursor.position = member.offset + member.size;
+
+        let mut cursors = self.gz_cursors.lock().unwrap_or_else(|e| e.into_inner());
+        if cursors.len() >= MAX_GZ_CURSORS {
+            cursors.remove(0);
+        }
+        cursors.push(cursor);
+        Ok(Some(data))
+    }
+}
+
+/// Read a member's data, growing the buffer as it arrives past a capped
+/// pre-allocation for its claimed size.
+fn read_sized(mut reader: impl Read, size: u64) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::with_capacity(size.min(MAX_PREALLOC) as usize);
+    reader.read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Normalize a member path: `/` separators, no leading `./` or `/`.
+fn normalize_member_path(path: &str) -> String {
+    path.replace('\\', "/")
+        .trim_start_matches("./")
+        .trim_start_matches('/')
+        .to_string()
+}
+
+fn list_tar(
+    archive: &Path,
+    kind: ArchiveKind,
+    reader: impl Read,
+) -> io::Result<Vec<ArchiveMember>> {
+    let mut tar = tar::Archive::new(reader);
+    let mut members = Vec::new();
+    for (index, entry) in tar.entries()?.enumerate() {
+        let entry = entry?;
+        let header = entry.header();
+        if !header.entry_type().is_file() {
+            continue;
+        }
+        members.push(ArchiveMember {
+            archive: archive.to_path_buf(),
+            kind,
+            path: normalize_member_path(&entry.path()?.to_string_lossy()),
+            size: header.size()?,
+            mode: header.mode().ok(),
+            modified: header.mtime().ok(),
+            index,
+            offset: entry.raw_file_position(),
+        });
+    }
+    Ok(members)
+}
+
+/// Read a tar member by seeking straight to its header, or None if the
+/// header found there doesn't match the member.
+fn read_tar_at_offset(member: &ArchiveMember) -> io::Result<Option<Vec<u8>>> {
+    let Some(start) = member.offset.checked_sub(TAR_BLOCK) else {
+        return Ok(None);
+    };
+    let mut file = BufReader::new(File::open(&member.archive)?);
+    file.seek(SeekFrom::Start(start))?;
+    read_entry(&mut file, member)
+}
+
+/// A tar.gz decoder and how far into the tar stream it has read.
+struct GzCursor {
+    archive: PathBuf,
+    version: (u64, Option<SystemTime>),
+    decoder: GzDecoder<BufReader<File>>,
+    position: u64,
+}
+
+/// Read a header block and the data following it, or None if the header
+/// isn't a valid file header naming the member, of the member's size.
+fn read_entry(reader: &mut impl Read, member: &ArchiveMember) -> io::Result<Option<Vec<u8>>> {
+    let mut block = [0u8; TAR_BLOCK as usize];
+    match reader.read_exact(&mut block) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let header = tar::Header::from_byte_slice(&block);
+    // The checksum sums the block with its own field read as spaces
+    let sum: u64 = block
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| {
+            if (148..156).contains(&i) {
+                32
+            } else {
+                u64::from(b)
+            }
+        })
+        .sum();
+    let valid = header.cksum().ok() == Some(sum as u32)
+        && header.entry_type().is_file()
+        && header.size().ok() == Some(member.size)
+        && names_member(header, member);
+    if !valid {
+        return Ok(None);
+    }
+    let data = read_sized(reader.take(member.size), member.size)?;
+    Ok((data.len() as u64 == member.size).then_some(data))
+}
+
+/// Whether a tar header's path is the member's path.
+///
+/// A long path is stored in an extension entry ahead of the header, which
+/// keeps only its first bytes, so a full name field need only be a prefix.
+fn names_member(header: &tar::Header, member: &ArchiveMember) -> bool {
+    let Ok(path) = header.path() else {
+        return false;
+    };
+    let path = normalize_member_path(&path.to_string_lossy());
+    let truncated = !header.as_old().name.contains(&0);
+    path == member.path || (truncated && member.path.starts_with(&path))
+}
+
+fn read_tar(member: &ArchiveMember, reader: impl Read) -> io::Result<Vec<u8>> {
+    let mut tar = tar::Archive::new(reader);
+    for entry in tar.entries()? {
+        let entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        if normalize_member_path(&entry.path()?.to_string_lossy()) == member.path {
+            return read_sized(entry, member.size);
+        }
+    }
+    Err(not_found(member))
+}
+
+fn not_found(member: &ArchiveMember) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!(
+            "member {} not found in {}",
+            member.path,
+            member.archive.display()
+        ),
+    )
+}
+
+fn list_zip(archive: &Path) -> io::Result<Vec<ArchiveMember>> {
+    let mut zip = zip::ZipArchive::new(File::open(archive)?).map_err(io::Error::other)?;
+    let mut members = Vec::with_capacity(zip.len());
+    for i in 0..zip.len() {
+        let file = zip.by_index(i).map_err(io::Error::other)?;
+        if !file.is_file() {
+            continue;
+        }
+        members.push(ArchiveMember {
+            archive: archive.to_path_buf(),
+            kind: ArchiveKind::Zip,
+            path: normalize_member_path(file.name()),
+            size: file.size(),
+            mode: file.unix_mode(),
+            modified: file.last_modified().map(zip_time),
+            index: i,
+            offset: 0,
+        });
+    }
+    Ok(members)
+}
+
+/// Seconds since the Unix epoch of a zip timestamp. Zip records local
+/// time without a zone, so it is taken as UTC.
+fn zip_time(time: zip::DateTime) -> u64 {
+    // Days from the civil date, after Howard Hinnant's `days_from_civil`
+    let (month, day) = (u64::from(time.month()), u64::from(time.day()));
+    let year = u64::from(time.year()) - u64::from(month <= 2);
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    days * 86_400
+        + u64::from(time.hour()) * 3_600
+        + u64::from(time.minute()) * 60
+        + u64::from(time.second())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    fn write_tar(path: &Path, files: &[(&str, &[u8])]) {
+        let mut builder = tar::Builder::new(File::create(path).unwrap());
+        for (name, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(1_700_000_000);
+            header.set_cksum();
+            builder.append_data(&mut header, name, *data).unwrap();
+        }
+        builder.finish().unwrap();
+    }
+
+    fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        for (name, data) in files {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_archive_kind_from_path() {
+        assert_eq!(ArchiveKind::from_path("a.tar"), Some(ArchiveKind::Tar));
+        assert_eq!(ArchiveKind::from_path("a.tar.gz"), Some(ArchiveKind::TarGz));
+        assert_eq!(ArchiveKind::from_path("a.TGZ"), Some(ArchiveKind::TarGz));
+        assert_eq!(ArchiveKind::from_path("dir/a.zip"), Some(ArchiveKind::Zip));
+        assert_eq!(ArchiveKind::from_path("a.txt"), None);
+    }
+
+    #[test]
+    fn test_list_and_read_tar() {
+        let dir = tempdir().unwrap();
+        let archive = dir.path().join("bundle.tar");
+        write_tar(&archive, &[("./a.json", b"{}"), ("sub/b.txt", b"hello")]);
+
+        let members = list_members(&archive, ArchiveKind::Tar).unwrap();
+        assert_eq!(members.len(), 2);
+        assert_eq!(members[0].path, "a.json");
+        assert_eq!(members[1].path, "sub/b.txt");
+        assert_eq!(members[1].size, 5);
+        assert_eq!(members[1].mode, Some(0o644));
+        assert_eq!(members[1].modified, Some(1_700_000_000));
+        assert_eq!(members[1].virtual_path(), archive.join("sub/b.txt"));
+
+        assert_eq!(read_member(&members[1]).unwrap(), b"hello");
+    }
+
+    fn write_tar_gz(path: &Path, files: &[(&str, &[u8])]) {
+        let tar_path = path.with_extension("plain");
+        write_tar(&tar_path, files);
+        let mut encoder = flate2::write::GzEncoder::new(
+            File::create(path).unwrap(),
+            flate2::Compression::default(),
+        );
+        encoder
+            .write_all(&std::fs::read(&tar_path).unwrap())
+            .unwrap();
+        encoder.finish().unwrap();
+    }
+
+    #[test]
+    fn test_list_and_read_tar_gz() {
+        let dir = tempdir().unwrap();
+        let archive = dir.path().join("bundle.tar.gz");
+        write_tar_gz(&archive, &[("x.txt", b"gz content")]);
+
+        let members = list_members(&archive, ArchiveKind::TarGz).unwrap();
+        assert_eq!(members.len(), 1);
+        assert_eq!(read_member(&members[0]).unwrap(), b"gz content");
+    }
+
+    #[test]
+    fn test_read_tar_members_by_offset() {
+        let dir = tempdir().unwrap();
+        let long = format!("{}/deep.txt", "nested".repeat(20));
+        let files: Vec<(String, Vec<u8>)> = (0..20)
+            .map(|i| (format!("m{}.txt", i), vec![b'a' + i as u8; 600 + i]))
+            .chain([(long, b"long name".to_vec())])
+            .collect();
+        let files: Vec<(&str, &[u8])> = files
+            .iter()
+            .map(|(name, data)| (name.as_str(), data.as_slice()))
+            .collect();
+
+        for (name, kind) in [
+            ("b.tar", ArchiveKind::Tar),
+            ("b.tar.gz", ArchiveKind::TarGz),
+        ] {
+            let archive = dir.path().join(name);
+            match kind {
+                ArchiveKind::Tar => write_tar(&archive, &files),
+                _ => write_tar_gz(&archive, &files),
+            }
+            let members = list_members(&archive, kind).unwrap();
+            assert_eq!(members.len(), files.len());
+
+            // In archive order, then backwards, then skipping about
+            let order = (0..files.len())
+                .chain((0..files.len()).rev())
+                .chain([3, 17, 5, 20]);
+            let reader = ArchiveReader::new();
+            for i in order {
+                let direct = match kind {
+                    ArchiveKind::Tar => read_tar_at_offset(&members[i]),
+                    _ => reader.read_tar_gz_at_offset(&members[i]),
+                };
+                assert_eq!(direct.unwrap().as_deref(), Some(files[i].1), "{}", i);
+                assert_eq!(read_member(&members[i]).unwrap(), files[i].1);
+            }
+
+            // A stale offset falls back to scanning for the member
+            let mut stale = members[7].clone();
+            stale.offset += 3;
+            assert!(read_tar_at_offset(&stale).unwrap().is_none());
+            assert_eq!(read_member(&stale).unwrap(), files[7].1);
+
+            // So does a header at the offset naming another member
+            let mut renamed = members[7].clone();
+            renamed.path = "m8.txt".to_string();
+            let direct = match kind {
+                ArchiveKind::Tar => read_tar_at_offset(&renamed),
+                _ => reader.read_tar_gz_at_offset(&renamed),
+            };
+            assert!(direct.unwrap().is_none());
+            assert_eq!(reader.read(&renamed).unwrap(), files[8].1);
+        }
+    }
+
+    #[test]
+    fn test_list_and_read_zip() {
+        let dir = tempdir().unwrap();
+        let archive = dir.path().join("release.zip");
+        write_zip(&archive, &[("config/app.json", b"{\"a\":1}")]);
+
+        let members = list_members(&archive, ArchiveKind::Zip).unwrap();
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].path, "config/app.json");
+        assert_eq!(read_member(&members[0]).unwrap(), b"{\"a\":1}");
+    }
+
+    #[test]
+    fn test_zip_member_modified() {
+        let dir = tempdir().unwrap();
+        let archive = dir.path().join("release.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&archive).unwrap());
+        let modified = zip::DateTime::from_date_and_time(2023, 11, 14, 22, 13, 20).unwrap();
+        let options = zip::write::SimpleFileOptions::default().last_modified_time(modified);
+        zip.start_file("a.txt", options).unwrap();
+        zip.write_all(b"a").unwrap();
+        zip.finish().unwrap();
+
+        let members = list_members(&archive, ArchiveKind::Zip).unwrap();
+        assert_eq!(members[0].modified, Some(1_700_000_000));
+    }
+
+    #[test]
+    fn test_read_zip_member_stored_unnormalized() {
+        let dir = tempdir().unwrap();
+        let archive = dir.path().join("release.zip");
+        write_zip(
+            &archive,
+            &[("./conf\\app.json", b"{}"), ("/abs.txt", b"abs")],
+        );
+
+        let members = list_members(&archive, ArchiveKind::Zip).unwrap();
+        assert_eq!(members[0].path, "conf/app.json");
+        assert_eq!(members[1].path, "abs.txt");
+        assert_eq!(read_member(&members[0]).unwrap(), b"{}");
+        assert_eq!(read_member(&members[1]).unwrap(), b"abs");
+    }
+
+    #[test]
+    fn test_read_sized_caps_preallocation() {
+        // A header claiming a terabyte must not reserve it up front
+        let data = read_sized(&b"tiny"[..], 1 << 40).unwrap();
+        assert_eq!(data, b"tiny");
+        assert!(data.capacity() <= MAX_PREALLOC as usize);
+    }
+
+    #[test]
+    fn test_read_missing_tar_member() {
+        let dir = tempdir().unwrap();
+        let archive = dir.path().join("bundle.tar");
+        write_tar(&archive, &[("a.txt", b"a")]);
+
+        let member = ArchiveMember {
+            archive: archive.clone(),
+            kind: ArchiveKind::Tar,
+            path: "missing.txt".to_string(),
+            size: 0,
+            mode: None,
+            modified: None,
+            index: 0,
+            offset: 0,
+        };
+        let err = read_member(&member).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
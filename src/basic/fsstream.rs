@@ -1,12 +1,15 @@
 //! FsstreamProcessor implementation using fsstream crate for async glob expansion.
 
-use crate::fop::{Fop, Pattern, ProcessorError};
-use crate::processor::AsyncProcessor;
+use crate::archive::{self, ArchiveKind};
+use crate::fop::{ErrorKind, Fop, Pattern, ProcessorError};
+use crate::processor::{AsyncProcessor, StreamingProcessor};
+use crate::progress::Progress;
 use fsstream::dir_scanner::DirScanner;
-use globset::Glob;
+use futures::stream::{self, BoxStream, StreamExt};
+use globset::{Glob, GlobBuilder};
 use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::Semaphore;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 use tokio_util::sync::CancellationToken;
 
 /// Default concurrency limit for simultaneous directory scans.
@@ -21,6 +24,9 @@ const GLOB_METACHARACTERS: &[char] = &['*', '?', '[', '{'];
 /// Includes a per-processor semaphore to limit concurrent directory scans,
 /// preventing file descriptor exhaustion when processing many patterns.
 ///
+/// As a `StreamingProcessor`, matches are yielded while the walk is still
+/// running; `process_one` collects them all.
+///
 /// # Example
 ///
 /// ```ignore
@@ -31,10 +37,24 @@ const GLOB_METACHARACTERS: &[char] = &['*', '?', '[', '{'];
 /// let fop = Fop::new("src/**/*.rs");
 /// let results = processor.process_one(fop).await;
 /// ```
+///
+/// With `with_archives(true)`, tar and zip files named in a pattern act like
+/// directories: `release.zip/**/*.json` yields one Fop per matching member,
+/// with a virtual `filename` and the `member` field set.
+///
+/// With `with_cancellation`, cancelling the token stops running scans and
+/// refuses new ones; a scan it cuts short comes back with a `Cancelled`
+/// error after whatever matched before the scan stopped.
 pub struct FsstreamProcessor {
     scanner: DirScanner,
     /// Semaphore to limit concurrent directory scans
     concurrency: Arc<Semaphore>,
+    /// Whether to expand archive members
+    archives: bool,
+    /// Parent of every scan's cancellation token
+    cancel: CancellationToken,
+    /// Optional sink for pattern and match progress
+    progress: Option<Progress>,
 }
 
 impl FsstreamProcessor {
@@ -43,6 +63,9 @@ impl FsstreamProcessor {
         Self {
             scanner: DirScanner::new(),
             concurrency: Arc::new(Semaphore::new(DEFAULT_CONCURRENCY)),
+            archives: false,
+            cancel: CancellationToken::new(),
+            progress: None,
         }
     }
 
@@ -67,6 +90,44 @@ impl FsstreamProcessor {
         self
     }
 
+    /// Set whether tar and zip archives in a pattern are expanded into members.
+    ///
+    /// Default is false, so archives are only matched as plain files.
+    pub fn with_archives(mut self, enabled: bool) -> Self {
+        self.archives = enabled;
+        self
+    }
+
+    /// Stop scans when `token` is cancelled, e.g. on Ctrl-C.
+    ///
+    /// Each scan runs under a child of `token`, so one pipeline-wide token
+    /// can be shared with other processors.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// Report patterns started and finished, and files matched.
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    fn cancelled() -> ProcessorError {
+        ProcessorError::new("FsstreamProcessor", "Scan was cancelled")
+            .with_kind(ErrorKind::Cancelled)
+    }
+
+    /// Acquire a scan permit, giving up if cancelled first.
+    async fn acquire(&self) -> Result<OwnedSemaphorePermit, ProcessorError> {
+        tokio::select! {
+            biased;
+            _ = self.cancel.cancelled() => Err(Self::cancelled()),
+            permit = self.concurrency.clone().acquire_owned() => permit
+                .map_err(|_| ProcessorError::new("FsstreamProcessor", "Semaphore closed")),
+        }
+    }
+
     /// Check if pattern contains glob metacharacters.
     fn has_wildcards(pattern: &str) -> bool {
         pattern.contains(GLOB_METACHARACTERS)
@@ -168,35 +229,140 @@ impl FsstreamProcessor {
             }
         }
     }
-}
 
-impl Default for FsstreamProcessor {
-    fn default() -> Self {
-        Self::new()
-    }
-}
+    /// Parse a pattern into (archive, kind, member_glob) if it reaches into an archive.
+    ///
+    /// This is the member-aware counterpart of `parse_pattern`: path components
+    /// are scanned up to the first wildcard, and the first one naming an archive
+    /// splits the pattern. The archive path itself must be literal.
+    ///
+    /// # Examples
+    ///
+    /// | Input | Archive | Member Glob |
+    /// |-------|---------|-------------|
+    /// | `release.zip/**/*.json` | `release.zip` | `**/*.json` |
+    /// | `out/bundle.tar.gz/bin/*` | `out/bundle.tar.gz` | `bin/*` |
+    /// | `release.zip/config/app.json` | `release.zip` | `config/app.json` |
+    /// | `*.zip/**` | - | - |
+    /// | `release.zip` | - | - |
+    fn parse_archive_pattern(pattern: &str) -> Option<(PathBuf, ArchiveKind, String)> {
+        let components: Vec<&str> = pattern.split(['/', '\\']).collect();
 
-impl Clone for FsstreamProcessor {
-    fn clone(&self) -> Self {
-        Self {
-            scanner: self.scanner.clone(),
-            concurrency: self.concurrency.clone(),
+        for (idx, component) in components.iter().enumerate() {
+            if Self::has_wildcards(component) {
+                return None;
+            }
+            if let Some(kind) = ArchiveKind::from_path(component) {
+                let member_glob = components[idx + 1..].join("/");
+                if member_glob.is_empty() {
+                    return None;
+                }
+                let archive = PathBuf::from(components[..=idx].join("/"));
+                return Some((archive, kind, member_glob));
+            }
         }
+
+        None
     }
-}
 
-impl AsyncProcessor for FsstreamProcessor {
-    fn name(&self) -> &'static str {
-        "FsstreamProcessor"
+    /// Expand a member glob against the members of an archive.
+    async fn expand_archive(
+        &self,
+        fop: Fop,
+        archive: PathBuf,
+        kind: ArchiveKind,
+        member_glob: &str,
+    ) -> Vec<Fop> {
+        let name = "FsstreamProcessor";
+
+        let matcher = match GlobBuilder::new(member_glob)
+            .literal_separator(true)
+            .build()
+        {
+            Ok(glob) => glob.compile_matcher(),
+            Err(e) => {
+                let mut error_fop = fop;
+                error_fop.err = Some(ProcessorError::new(
+                    name,
+                    format!("Invalid glob pattern: {}", e),
+                ));
+                return vec![error_fop];
+            }
+        };
+
+        let _permit = match self.acquire().await {
+            Ok(permit) => permit,
+            Err(err) => {
+                let mut error_fop = fop;
+                error_fop.err = Some(err);
+                return vec![error_fop];
+            }
+        };
+
+        let listing = {
+            let archive = archive.clone();
+            tokio::task::spawn_blocking(move || archive::list_members(&archive, kind)).await
+        };
+
+        let members = match listing {
+            Ok(Ok(members)) => members,
+            Ok(Err(e)) => {
+                let mut error_fop = fop;
+                error_fop.err = Some(ProcessorError::new(
+                    name,
+                    format!("Failed to list archive {}: {}", archive.display(), e),
+                ));
+                return vec![error_fop];
+            }
+            Err(e) => {
+                let mut error_fop = fop;
+                error_fop.err = Some(ProcessorError::new(name, format!("Join error: {}", e)));
+                return vec![error_fop];
+            }
+        };
+
+        let pattern_arc = Arc::new(Pattern::new(&*fop.file_or_pattern));
+        members
+            .into_iter()
+            .filter(|member| matcher.is_match(&member.path))
+            .map(|member| {
+                let mut new_fop = fop.clone();
+                new_fop.filename = Some(member.virtual_path());
+                new_fop.member = Some(Arc::new(member));
+                new_fop.pattern = Some(pattern_arc.clone());
+                new_fop
+            })
+            .collect()
     }
 
-    async fn process_one(&self, fop: Fop) -> Vec<Fop> {
+    /// Expand a pattern into a stream of matches, yielded while the scan runs.
+    async fn expand(self, fop: Fop) -> BoxStream<'static, Fop> {
         let name = "FsstreamProcessor";
         let file_or_pattern = fop.file_or_pattern.clone();
+        let fail = |mut fop: Fop, err: ProcessorError| {
+            fop.err = Some(err);
+            stream::iter(vec![fop]).boxed()
+        };
 
         // Skip if filename already set (don't glob concrete files)
         if fop.filename.is_some() {
-            return vec![fop];
+            return stream::iter(vec![fop]).boxed();
+        }
+
+        // Archive members: the pattern names an existing archive file
+        if self.archives {
+            if let Some((archive, kind, member_glob)) =
+                Self::parse_archive_pattern(&file_or_pattern)
+            {
+                let is_file = tokio::fs::metadata(&archive)
+                    .await
+                    .map(|m| m.is_file())
+                    .unwrap_or(false);
+                if is_file {
+                    let members = self.expand_archive(fop, archive, kind, &member_glob).await;
+                    return stream::iter(members).boxed();
+                }
+            }
         }
 
         // Fast path: literal file (no wildcards)
@@ -205,18 +371,16 @@ impl AsyncProcessor for FsstreamProcessor {
             return if tokio::fs::try_exists(&path).await.unwrap_or(false) {
                 let mut result = fop;
                 result.filename = Some(path);
-                vec![result]
+                stream::iter(vec![result]).boxed()
             } else {
                 // Match TinyGlobby behavior: no matches for non-existent literal paths
-                vec![]
+                stream::empty().boxed()
             };
         }
 
         // Validate pattern syntax using globset
         if let Err(err) = Self::validate_pattern(&file_or_pattern) {
-            let mut error_fop = fop;
-            error_fop.err = Some(err);
-            return vec![error_fop];
+            return fail(fop, err);
         }
 
         // Parse the glob pattern using component-based analysis
@@ -228,19 +392,13 @@ impl AsyncProcessor for FsstreamProcessor {
                 name,
                 format!("Base directory does not exist: {}", base_dir.display()),
             );
-            let mut error_fop = fop;
-            error_fop.err = Some(err);
-            return vec![error_fop];
+            return fail(fop, err);
         }
 
-        // Acquire semaphore permit to limit concurrency
-        let _permit = match self.concurrency.acquire().await {
+        // Acquire semaphore permit to limit concurrency, held until the scan ends
+        let permit = match self.acquire().await {
             Ok(permit) => permit,
-            Err(_) => {
-                let mut error_fop = fop;
-                error_fop.err = Some(ProcessorError::new(name, "Semaphore closed"));
-                return vec![error_fop];
-            }
+            Err(err) => return fail(fop, err),
         };
 
         // Create simple strategy with glob pattern
@@ -251,58 +409,241 @@ impl AsyncProcessor for FsstreamProcessor {
                     name,
                     format!("Failed to build pattern matcher: {}", e),
                 );
-                let mut error_fop = fop;
+                return fail(fop, err);
+            }
+        };
+
+        // Scan under a child token, so the pipeline can stop it, and so
+        // dropping the stream stops it too
+        let cancel = self.cancel.child_token();
+        let mut scan_handle = strategy.scan_streaming(&base_dir, cancel.clone()).await;
+
+        // Add pattern to all matches using Arc for cheap cloning
+        let pattern_arc = Arc::new(Pattern::new(&*file_or_pattern));
+        let matches = {
+            let fop = fop.clone();
+            stream::poll_fn(move |cx| scan_handle.receiver.poll_recv(cx)).map(move |path| {
+                let mut new_fop = fop.clone();
+                new_fop.filename = Some(path);
+                new_fop.pattern = Some(pattern_arc.clone());
+                new_fop
+            })
+        };
+
+        // Note whether cancellation cut the scan short, rather than arriving
+        // after it finished
+        let mut join_handle = scan_handle.join_handle;
+        let watched = cancel.clone();
+        let scan = tokio::spawn(async move {
+            tokio::select! {
+                biased;
+                result = &mut join_handle => (result, false),
+                _ = watched.cancelled() => (join_handle.await, true),
+            }
+        });
+
+        // Once matches run out, wait for the scan task and report its errors
+        let stop = cancel.clone().drop_guard();
+        let outcome = async move {
+            let _held = (permit, stop);
+            let mut errors = Vec::new();
+            let (result, cut_short) = match scan.await {
+                Ok(outcome) => outcome,
+                Err(e) => (Err(e), false),
+            };
+            let err = match result {
+                Ok(Ok(())) => None,
+                Ok(Err(e)) => Some(ProcessorError::new(name, format!("Scan error: {}", e))),
+                Err(e) => Some(ProcessorError::new(name, format!("Join error: {}", e))),
+            };
+            if let Some(err) = err {
+                let mut error_fop = fop.clone();
                 error_fop.err = Some(err);
-                return vec![error_fop];
+                errors.push(error_fop);
+            }
+            if cut_short {
+                let mut error_fop = fop;
+                error_fop.err = Some(Self::cancelled());
+                errors.push(error_fop);
             }
+            stream::iter(errors)
         };
 
-        // Use cancellation token for scan
-        let cancel = CancellationToken::new();
+        matches.chain(stream::once(outcome).flatten()).boxed()
+    }
+}
 
-        // Collect results from streaming scan
-        let mut scan_handle = strategy.scan_streaming(&base_dir, cancel).await;
-        let mut results = Vec::new();
+impl Default for FsstreamProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        while let Some(path) = scan_handle.receiver.recv().await {
-            let mut new_fop = fop.clone();
-            new_fop.filename = Some(path);
-            results.push(new_fop);
+impl Clone for FsstreamProcessor {
+    fn clone(&self) -> Self {
+        Self {
+            scanner: self.scanner.clone(),
+            concurrency: self.concurrency.clone(),
+            archives: self.archives,
+            cancel: self.cancel.clone(),
+            progress: self.progress.clone(),
         }
+    }
+}
 
-        // Wait for scan task to complete and propagate errors
-        match scan_handle.join_handle.await {
-            Ok(Ok(())) => {}
-            Ok(Err(e)) => {
-                let mut error_fop = fop.clone();
-                error_fop.err = Some(ProcessorError::new(name, format!("Scan error: {}", e)));
-                if results.is_empty() {
-                    return vec![error_fop];
+impl AsyncProcessor for FsstreamProcessor {
+    fn name(&self) -> &'static str {
+        "FsstreamProcessor"
+    }
+
+    async fn process_one(&self, fop: Fop) -> Vec<Fop> {
+        self.process_stream(fop).collect().await
+    }
+}
+
+impl StreamingProcessor for FsstreamProcessor {
+    fn process_stream(&self, fop: Fop) -> BoxStream<'static, Fop> {
+        // Concrete files pass through, so only patterns are tracked
+        let scope = match &self.progress {
+            Some(progress) if fop.filename.is_none() => {
+                Some(progress.pattern(fop.file_or_pattern.clone()))
+            }
+            _ => None,
+        };
+        let this = self.clone();
+        let expanded = stream::once(async move { this.expand(fop).await }).flatten();
+        match scope {
+            Some(mut scope) => expanded
+                .inspect(move |fop| {
+                    if fop.err.is_none() {
+                        scope.matched();
+                    }
+                })
+                .boxed(),
+            None => expanded.boxed(),
+        }
+    }
+}
+
+/// Translate a glob into an anchored regex, optionally capturing each wildcard.
+pub(crate) fn glob_to_regex(glob: &str, capture: bool) -> String {
+    let open = if capture { "(" } else { "(?:" };
+    let mut regex = String::new();
+    let mut chars = glob.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str(open);
+                    regex.push_str("(?:[^/]*/)*)");
+                } else {
+                    regex.push_str(open);
+                    regex.push_str(".*)");
                 }
-                results.push(error_fop);
             }
-            Err(e) => {
-                let mut error_fop = fop.clone();
-                error_fop.err = Some(ProcessorError::new(name, format!("Join error: {}", e)));
-                if results.is_empty() {
-                    return vec![error_fop];
+            '*' => {
+                regex.push_str(open);
+                regex.push_str("[^/]*)");
+            }
+            '?' => {
+                regex.push_str(open);
+                regex.push_str("[^/])");
+            }
+            '[' => {
+                let mut class = String::from("[");
+                if chars.peek() == Some(&'!') {
+                    chars.next();
+                    class.push('^');
                 }
-                results.push(error_fop);
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                    if c == '\\' || c == '[' {
+                        class.push('\\');
+                    }
+                    class.push(c);
+                }
+                class.push(']');
+                regex.push_str(open);
+                regex.push_str(&class);
+                regex.push(')');
             }
-        }
-
-        // Add pattern to all successful results using Arc for cheap cloning
-        if !results.is_empty() {
-            let pattern_arc = Arc::new(Pattern::new(&*file_or_pattern));
-            for fop in &mut results {
-                if fop.err.is_none() {
-                    fop.pattern = Some(pattern_arc.clone());
+            '{' => {
+                let mut depth = 1;
+                let mut body = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => {
+                            // Keep escapes for the alternative's own translation
+                            body.push(c);
+                            if let Some(escaped) = chars.next() {
+                                body.push(escaped);
+                            }
+                            continue;
+                        }
+                        '{' => depth += 1,
+                        '}' => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        _ => {}
+                    }
+                    body.push(c);
                 }
+                let alternatives: Vec<String> = split_alternatives(&body)
+                    .into_iter()
+                    .map(|alt| {
+                        let inner = glob_to_regex(alt, false);
+                        inner[1..inner.len() - 1].to_string()
+                    })
+                    .collect();
+                regex.push_str(open);
+                regex.push_str(&alternatives.join("|"));
+                regex.push(')');
+            }
+            // An escaped glob metacharacter is literal; any other backslash
+            // is a Windows separator
+            '\\' if chars.peek().is_some_and(|c| GLOB_SPECIAL.contains(*c)) => {
+                let escaped = chars.next().unwrap_or_default();
+                regex.push_str(&regex::escape(&escaped.to_string()));
             }
+            '/' | '\\' => regex.push('/'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
         }
+    }
+
+    format!("^{}$", regex)
+}
 
-        results
+/// Characters a backslash escapes in a glob.
+const GLOB_SPECIAL: &str = "*?[]{},!\\";
+
+/// Split a brace body at its top-level commas, leaving nested braces and
+/// escaped commas whole.
+fn split_alternatives(body: &str) -> Vec<&str> {
+    let mut alternatives = Vec::new();
+    let (mut depth, mut start, mut escaped) = (0usize, 0, false);
+    for (i, c) in body.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                alternatives.push(&body[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
     }
+    alternatives.push(&body[start..]);
+    alternatives
 }
 
 #[cfg(test)]
@@ -370,6 +711,32 @@ mod tests {
         assert_eq!(pattern, "[abc].rs");
     }
 
+    #[test]
+    fn test_parse_archive_pattern() {
+        let (archive, kind, glob) =
+            FsstreamProcessor::parse_archive_pattern("release.zip/**/*.json").unwrap();
+        assert_eq!(archive, PathBuf::from("release.zip"));
+        assert_eq!(kind, ArchiveKind::Zip);
+        assert_eq!(glob, "**/*.json");
+
+        let (archive, kind, glob) =
+            FsstreamProcessor::parse_archive_pattern("/out/bundle.tar.gz/bin/*").unwrap();
+        assert_eq!(archive, PathBuf::from("/out/bundle.tar.gz"));
+        assert_eq!(kind, ArchiveKind::TarGz);
+        assert_eq!(glob, "bin/*");
+
+        // Literal member path
+        let (archive, _, glob) =
+            FsstreamProcessor::parse_archive_pattern("release.zip/config/app.json").unwrap();
+        assert_eq!(archive, PathBuf::from("release.zip"));
+        assert_eq!(glob, "config/app.json");
+
+        // Wildcard before the archive, archive alone, or no archive at all
+        assert!(FsstreamProcessor::parse_archive_pattern("*.zip/**").is_none());
+        assert!(FsstreamProcessor::parse_archive_pattern("release.zip").is_none());
+        assert!(FsstreamProcessor::parse_archive_pattern("src/**/*.rs").is_none());
+    }
+
     #[test]
     fn test_validate_pattern() {
         // Valid patterns
@@ -514,6 +881,181 @@ mod tests {
         assert_eq!(sem.available_permits(), 2);
     }
 
+    #[tokio::test]
+    async fn test_async_cancellation() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "a").unwrap();
+        let pattern = dir.path().join("*.txt").to_str().unwrap().to_string();
+        let token = CancellationToken::new();
+        let processor = FsstreamProcessor::new()
+            .with_concurrency(1)
+            .with_cancellation(token.clone());
+
+        // A scan waiting for a permit gives up when cancelled
+        let held = processor.concurrency.clone().acquire_owned().await.unwrap();
+        let waiting = {
+            let processor = processor.clone();
+            let pattern = pattern.clone();
+            tokio::spawn(async move { processor.process_one(Fop::new(&pattern)).await })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        token.cancel();
+        let results = waiting.await.unwrap();
+        drop(held);
+
+        assert_eq!(results.len(), 1);
+        let err = results[0].err.as_ref().unwrap();
+        assert_eq!(err.kind, ErrorKind::Cancelled);
+        assert_eq!(processor.concurrency.available_permits(), 1);
+
+        // New scans are refused too
+        let results = processor.process_one(Fop::new(&pattern)).await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].err.as_ref().unwrap().kind, ErrorKind::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn test_async_cancellation_after_scan() {
+        let dir = tempdir().unwrap();
+        for i in 0..3 {
+            fs::write(dir.path().join(format!("{}.txt", i)), "x").unwrap();
+        }
+        let pattern = dir.path().join("*.txt").to_str().unwrap().to_string();
+        let token = CancellationToken::new();
+        let processor = FsstreamProcessor::new().with_cancellation(token.clone());
+
+        // Cancelling once the scan has finished is not reported as cutting it short
+        let mut stream = processor.process_stream(Fop::new(&pattern));
+        let first = stream.next().await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        token.cancel();
+        let mut results = vec![first];
+        results.extend(stream.collect::<Vec<_>>().await);
+
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|f| f.err.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_async_process_stream() {
+        let dir = tempdir().unwrap();
+        for i in 0..5 {
+            fs::write(dir.path().join(format!("{}.txt", i)), "x").unwrap();
+        }
+        let pattern = dir.path().join("*.txt").to_str().unwrap().to_string();
+        let processor = FsstreamProcessor::new().with_concurrency(1);
+
+        let streamed: Vec<Fop> = processor.process_stream(Fop::new(&pattern)).collect().await;
+        assert_eq!(streamed.len(), 5);
+        assert!(streamed
+            .iter()
+            .all(|f| f.pattern.is_some() && f.err.is_none()));
+
+        // Dropping a stream part way releases its scan permit
+        let mut partial = processor.process_stream(Fop::new(&pattern));
+        assert!(partial.next().await.unwrap().filename.is_some());
+        assert_eq!(processor.concurrency.available_permits(), 0);
+        drop(partial);
+        assert_eq!(processor.concurrency.available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_async_progress() {
+        let dir = tempdir().unwrap();
+        for i in 0..3 {
+            fs::write(dir.path().join(format!("{}.txt", i)), "x").unwrap();
+        }
+        let pattern = dir.path().join("*.txt").to_str().unwrap().to_string();
+        let progress = Progress::new();
+        let processor = FsstreamProcessor::new().with_progress(progress.clone());
+
+        processor.process_one(Fop::new(&pattern)).await;
+        let mut concrete = Fop::new("concrete");
+        concrete.filename = Some(PathBuf::from("concrete"));
+        processor.process_one(concrete).await;
+
+        let snapshot = progress.snapshot();
+        assert_eq!(snapshot.patterns_started, 1);
+        assert_eq!(snapshot.patterns_finished, 1);
+        assert_eq!(snapshot.files_matched, 3);
+    }
+
+    fn write_zip(path: &std::path::Path, files: &[(&str, &[u8])]) {
+        use std::io::Write;
+        let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, data) in files {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_async_archive_expansion() {
+        let dir = tempdir().unwrap();
+        let archive = dir.path().join("release.zip");
+        write_zip(
+            &archive,
+            &[
+                ("a.json", b"{}"),
+                ("config/b.json", b"{}"),
+                ("config/c.txt", b"c"),
+            ],
+        );
+
+        let processor = FsstreamProcessor::new().with_archives(true);
+        let pattern = format!("{}/**/*.json", archive.display());
+        let results = processor.process_one(Fop::new(pattern)).await;
+
+        assert_eq!(
+            results.len(),
+            2,
+            "Expected 2 .json members, got {:?}",
+            results
+        );
+        let member = results[1].member.as_ref().unwrap();
+        assert_eq!(member.path, "config/b.json");
+        assert_eq!(
+            results[1].filename.as_ref().unwrap(),
+            &archive.join("config/b.json")
+        );
+        assert!(results[1].pattern.is_some());
+
+        // Single-level wildcards don't cross directories
+        let pattern = format!("{}/*.json", archive.display());
+        let results = processor.process_one(Fop::new(pattern)).await;
+        assert_eq!(results.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_async_archive_disabled() {
+        let dir = tempdir().unwrap();
+        let archive = dir.path().join("release.zip");
+        write_zip(&archive, &[("a.json", b"{}")]);
+
+        let processor = FsstreamProcessor::new();
+        let pattern = format!("{}/*.json", archive.display());
+        let results = processor.process_one(Fop::new(pattern)).await;
+
+        // Without archive support the zip is not a directory to scan
+        assert!(results.iter().all(|fop| fop.member.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_async_archive_corrupt() {
+        let dir = tempdir().unwrap();
+        let archive = dir.path().join("broken.zip");
+        fs::write(&archive, "not a zip").unwrap();
+
+        let processor = FsstreamProcessor::new().with_archives(true);
+        let pattern = format!("{}/*", archive.display());
+        let results = processor.process_one(Fop::new(pattern)).await;
+
+        assert_eq!(results.len(), 1);
+        assert!(results[0].err.is_some());
+    }
+
     #[test]
     fn test_default() {
         let processor = FsstreamProcessor::default();
@@ -528,5 +1070,6 @@ mod tests {
 
         // Clones share the same semaphore
         assert!(Arc::ptr_eq(&processor.concurrency, &cloned.concurrency));
+        assert!(!cloned.archives);
     }
 }
@@ -1,19 +1,24 @@
 //! ParserProcessor implementation.
 
-use crate::fop::{Fop, ProcessorError};
-use crate::processor::Processor;
+use crate::fop::{ContentRange, Fop, ProcessorError};
+use crate::processor::{AsyncProcessor, Processor};
 
 /// Processor for converting user strings into Fop objects.
 ///
 /// This is usually the first step in the pipeline, creating the flyweight object.
+#[derive(Clone)]
 pub struct ParserProcessor {
     guard: bool,
+    ranges: bool,
 }
 
 impl ParserProcessor {
-    /// Create a new ParserProcessor with guard disabled.
+    /// Create a new ParserProcessor with guard and range suffixes disabled.
     pub fn new() -> Self {
-        Self { guard: false }
+        Self {
+            guard: false,
+            ranges: false,
+        }
     }
 
     /// Set whether to validate existing objects have file_or_pattern field.
@@ -25,6 +30,42 @@ impl ParserProcessor {
         self.guard = value;
         self
     }
+
+    /// Set whether to recognise `:lines` and `@bytes` range suffixes.
+    ///
+    /// When enabled, `src/lib.rs:10-40` becomes a Fop for `src/lib.rs` with
+    /// `range` set, so later processors only see the path. Disabled by default
+    /// since colons and at-signs are valid filename characters.
+    pub fn with_ranges(mut self, value: bool) -> Self {
+        self.ranges = value;
+        self
+    }
+
+    /// Apply guard and range parsing to a single Fop.
+    fn parse_one(&self, mut fop: Fop) -> Fop {
+        let name = "ParserProcessor";
+        if self.guard && fop.file_or_pattern.is_empty() {
+            fop.err = Some(ProcessorError::new(
+                name,
+                "Invalid Fop: file_or_pattern field is empty",
+            ));
+            return fop;
+        }
+
+        if self.ranges && fop.range.is_none() {
+            let input = fop.file_or_pattern.clone();
+            if let Some((path, range)) = ContentRange::split_suffix(&input) {
+                match range {
+                    Ok(range) => {
+                        fop.file_or_pattern = path.into();
+                        fop.range = Some(range);
+                    }
+                    Err(e) => fop.err = Some(ProcessorError::new(name, e)),
+                }
+            }
+        }
+        fop
+    }
 }
 
 impl Default for ParserProcessor {
@@ -38,21 +79,8 @@ impl Processor for ParserProcessor {
     where
         I: Iterator<Item = Fop> + 'a,
     {
-        let guard = self.guard;
-        let name = self.name().to_string();
-        input.map(move |fop| {
-            if guard && fop.file_or_pattern.is_empty() {
-                let err = ProcessorError::new(
-                    name.as_str(),
-                    "Invalid Fop: file_or_pattern field is empty",
-                );
-                let mut result = fop;
-                result.err = Some(err);
-                result
-            } else {
-                fop
-            }
-        })
+        let parser = self.clone();
+        input.map(move |fop| parser.parse_one(fop))
     }
 
     fn name(&self) -> &str {
@@ -60,6 +88,16 @@ impl Processor for ParserProcessor {
     }
 }
 
+impl AsyncProcessor for ParserProcessor {
+    fn name(&self) -> &'static str {
+        "ParserProcessor"
+    }
+
+    async fn process_one(&self, fop: Fop) -> Vec<Fop> {
+        vec![self.parse_one(fop)]
+    }
+}
+
 /// Helper function to convert strings into Fop objects for ParserProcessor.
 ///
 /// This is a convenience function for the common case where you have
@@ -75,8 +113,9 @@ mod tests {
     #[test]
     fn test_parser_processor() {
         let processor = ParserProcessor::new();
-        assert_eq!(processor.name(), "ParserProcessor");
+        assert_eq!(Processor::name(&processor), "ParserProcessor");
         assert!(!processor.guard);
+        assert!(!processor.ranges);
     }
 
     #[test]
@@ -108,10 +147,73 @@ mod tests {
         assert_eq!(&*fops[2].file_or_pattern, "data.json");
     }
 
+    #[tokio::test]
+    async fn test_async_parser_guard_empty() {
+        let processor = ParserProcessor::new().guard(true);
+        let results = processor.process_one(Fop::new("")).await;
+
+        assert_eq!(results.len(), 1);
+        assert!(results[0].err.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_async_parser_ranges_disabled() {
+        let processor = ParserProcessor::new();
+        let results = processor.process_one(Fop::new("src/lib.rs:10-40")).await;
+
+        assert_eq!(&*results[0].file_or_pattern, "src/lib.rs:10-40");
+        assert!(results[0].range.is_none());
+    }
+
+    #[test]
+    fn test_parser_ranges() {
+        let processor = ParserProcessor::new().with_ranges(true);
+        let fops = vec![
+            Fop::new("src/lib.rs:10-40"),
+            Fop::new("path.bin@0+4096"),
+            Fop::new("plain.txt"),
+        ];
+
+        let results: Vec<_> = processor.process(fops.into_iter()).collect();
+
+        assert_eq!(&*results[0].file_or_pattern, "src/lib.rs");
+        assert_eq!(
+            results[0].range,
+            Some(ContentRange::Lines {
+                start: 10,
+                end: Some(40)
+            })
+        );
+        assert_eq!(&*results[1].file_or_pattern, "path.bin");
+        assert_eq!(
+            results[1].range,
+            Some(ContentRange::Bytes {
+                offset: 0,
+                len: Some(4096)
+            })
+        );
+        assert_eq!(&*results[2].file_or_pattern, "plain.txt");
+        assert!(results[2].range.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_async_parser_invalid_range() {
+        let processor = ParserProcessor::new().with_ranges(true);
+
+        let results = processor.process_one(Fop::new("src/lib.rs:40-10")).await;
+
+        assert_eq!(results.len(), 1);
+        assert!(results[0].err.is_some());
+        assert_eq!(
+            results[0].err.as_ref().unwrap().processor,
+            "ParserProcessor"
+        );
+    }
+
     #[test]
     fn test_default() {
         let processor = ParserProcessor::default();
-        assert_eq!(processor.name(), "ParserProcessor");
+        assert_eq!(Processor::name(&processor), "ParserProcessor");
         assert!(!processor.guard);
     }
 }
@@ -0,0 +1,726 @@
+//! Bounding strategies: how much of a bound each Fop consumes, how fast
+//! Fops may pass, and bounds that adapt to observed latency.
+
+use crate::fop::Fop;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+
+/// Strategy deciding how many permits a Fop takes from a bound.
+///
+/// With weighted bounding the limit reflects real resource use, such as
+/// memory or I/O bandwidth, rather than the number of items in flight. Costs
+/// above the bound's size are capped at it, so an oversized item runs alone
+/// instead of waiting forever, and every item takes at least one permit.
+/// Costs are computed on the blocking thread pool, so they may stat files.
+/// Closures taking `&Fop` and returning a `u32` implement this trait.
+pub trait Cost: Send + Sync {
+    /// Number of permits `fop` takes while it is processed.
+    fn cost(&self, fop: &Fop) -> u32;
+}
+
+/// Weigh `fop` with `cost` on the blocking pool, clamped to `1..=limit`.
+pub(crate) async fn weigh(cost: Arc<dyn Cost>, fop: Fop, limit: u32) -> (u32, Fop) {
+    let weighed = tokio::task::spawn_blocking(move || (cost.cost(&fop), fop)).await;
+    match weighed {
+        Ok((permits, fop)) => (permits.min(limit).max(1), fop),
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+impl<F> Cost for F
+where
+    F: Fn(&Fop) -> u32 + Send + Sync,
+{
+    fn cost(&self, fop: &Fop) -> u32 {
+        self(fop)
+    }
+}
+
+/// Every Fop takes the same number of permits, e.g. a high cost for
+/// executions sharing a bound with reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedCost(pub u32);
+
+impl Cost for FixedCost {
+    fn cost(&self, _fop: &Fop) -> u32 {
+        self.0
+    }
+}
+
+/// One permit per started block of file size, from the filename's metadata.
+///
+/// Fops without a readable file, e.g. unmatched patterns, take one permit.
+/// Content already on the Fop is measured instead of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileSizeCost {
+    bytes_per_permit: u64,
+}
+
+impl FileSizeCost {
+    /// Charge one permit per `bytes_per_permit` bytes, rounded up.
+    pub fn new(bytes_per_permit: u64) -> Self {
+        Self {
+            bytes_per_permit: bytes_per_permit.max(1),
+        }
+    }
+}
+
+impl Cost for FileSizeCost {
+    fn cost(&self, fop: &Fop) -> u32 {
+        let size = match &fop.content {
+            Some(content) => Some(content.as_bytes().len() as u64),
+            None => fop
+                .filename
+                .as_ref()
+                .and_then(|path| std::fs::metadata(path).ok())
+                .map(|metadata| metadata.len()),
+        };
+        let permits = size.map_or(1, |size| size.div_ceil(self.bytes_per_permit).max(1));
+        u32::try_from(permits).unwrap_or(u32::MAX)
+    }
+}
+
+/// Token bucket limiting throughput to N Fops per interval.
+///
+/// Tokens refill continuously at N per interval, up to the burst size, and
+/// each Fop takes one. The bucket starts full, so the first `burst` Fops
+/// pass at once. Clones share one bucket, so several stages can draw on the
+/// same budget, e.g. all executions hitting one local service.
+///
+/// Waiters are served in arrival order: each takes its token up front and
+/// sleeps until the bucket would have refilled it. A waiter dropped before
+/// then, e.g. on cancellation, gives its token back.
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    /// Tokens added per second
+    rate: f64,
+    burst: f64,
+    state: Arc<Mutex<BucketState>>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    /// Tokens available; negative when waiters have reserved future tokens
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    /// Allow `count` Fops per `interval`, with a burst of `count`.
+    pub fn new(count: u32, interval: Duration) -> Self {
+        let count = count.max(1);
+        Self {
+            rate: f64::from(count) / interval.as_secs_f64().max(f64::EPSILON),
+            burst: f64::from(count),
+            state: Arc::new(Mutex::new(BucketState {
+                tokens: f64::from(count),
+                refilled: Instant::now(),
+            })),
+        }
+    }
+
+    /// Allow up to `burst` Fops at once after an idle spell. The bucket is
+    /// refilled to the new size.
+    pub fn with_burst(self, burst: u32) -> Self {
+        let burst = f64::from(burst.max(1));
+        if let Ok(mut state) = self.state.lock() {
+            state.tokens = burst;
+        }
+        Self { burst, ..self }
+    }
+
+    /// Take a token, waiting until one is available. Returns how long it
+    /// waited.
+    pub async fn acquire(&self) -> Duration {
+        let wait = self.reserve(Instant::now());
+        if !wait.is_zero() {
+            let reservation = Reservation { bucket: Some(self) };
+            tokio::time::sleep(wait).await;
+            reservation.keep();
+        }
+        wait
+    }
+
+    /// Give back a token reserved by a waiter that gave up.
+    fn refund(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.tokens += 1.0;
+        }
+    }
+
+    /// Take a token, returning how long until it is actually available.
+    fn reserve(&self, now: Instant) -> Duration {
+        let Ok(mut state) = self.state.lock() else {
+            return Duration::ZERO;
+        };
+        let elapsed = now.saturating_duration_since(state.refilled).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.rate).min(self.burst);
+        state.refilled = now;
+        state.tokens -= 1.0;
+        if state.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.tokens / self.rate)
+        }
+    }
+}
+
+/// A token reserved by a waiting `acquire`, refunded unless kept.
+struct Reservation<'a> {
+    bucket: Option<&'a TokenBucket>,
+}
+
+impl Reservation<'_> {
+    /// The wait completed, so the token is spent.
+    fn keep(mut self) {
+        self.bucket = None;
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        if let Some(bucket) = self.bucket {
+            bucket.refund();
+        }
+    }
+}
+
+/// One finished item, as seen by a `LimitAlgorithm`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    /// How long the item took, from permit to release
+    pub latency: Duration,
+    /// Items in flight when it started, itself included
+    pub in_flight: usize,
+    /// The item failed in a way that signals overload, e.g. a timeout
+    pub dropped: bool,
+}
+
+/// Strategy adjusting an adaptive limit after each finished item.
+pub trait LimitAlgorithm: Send {
+    /// The new limit, given the current one and a sample. The limiter
+    /// clamps the result to its bounds.
+    fn update(&mut self, limit: usize, sample: &Sample) -> usize;
+}
+
+/// Additive increase, multiplicative decrease.
+///
+/// The limit grows by one after each success that used at least half of
+/// it, and is cut by the backoff ratio after a drop or a sample slower than
+/// the latency threshold.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aimd {
+    backoff: f64,
+    threshold: Option<Duration>,
+}
+
+impl Aimd {
+    /// Back off to 90% of the limit on drops only.
+    pub fn new() -> Self {
+        Self {
+            backoff: 0.9,
+            threshold: None,
+        }
+    }
+
+    /// Multiply the limit by `ratio`, between 0.5 and 1, when backing off.
+    pub fn with_backoff(mut self, ratio: f64) -> Self {
+        self.backoff = ratio.clamp(0.5, 1.0);
+        self
+    }
+
+    /// Also back off when an item takes longer than `threshold`.
+    pub fn with_threshold(mut self, threshold: Duration) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+}
+
+impl Default for Aimd {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LimitAlgorithm for Aimd {
+    fn update(&mut self, limit: usize, sample: &Sample) -> usize {
+        let slow = self.threshold.is_some_and(|t| sample.latency > t);
+        if sample.dropped || slow {
+            (limit as f64 * self.backoff) as usize
+        } else if sample.in_flight * 2 >= limit {
+            limit + 1
+        } else {
+            limit
+        }
+    }
+}
+
+/// Gradient-based limit, after Netflix's concurrency-limits Gradient2.
+///
+/// Compares each item's latency with a slow-moving average. While latency
+/// stays near the average the limit grows by a queue allowance of about
+/// the square root of the limit; as latency rises above it the limit
+/// shrinks in proportion, so queueing in the device shows up before errors
+/// do. A drop halves the estimate without the allowance. Changes are
+/// smoothed to ride out noise, and like `Aimd` a limit that is less than
+/// half used does not grow.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gradient {
+    smoothing: f64,
+    tolerance: f64,
+    baseline: Option<f64>,
+    /// Unrounded limit, so smoothed steps smaller than one still add up
+    estimate: Option<f64>,
+}
+
+impl Gradient {
+    /// Move 20% of the way to each new estimate, and tolerate latency up to
+    /// 1.5 times the average before shrinking.
+    pub fn new() -> Self {
+        Self {
+            smoothing: 0.2,
+            tolerance: 1.5,
+            baseline: None,
+            estimate: None,
+        }
+    }
+
+    /// Weight of each new estimate in the limit, between 0 and 1.
+    pub fn with_smoothing(mut self, smoothing: f64) -> Self {
+        self.smoothing = smoothing.clamp(0.01, 1.0);
+        self
+    }
+
+    /// How many times the average latency is tolerated before shrinking.
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance.max(1.0);
+        self
+    }
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LimitAlgorithm for Gradient {
+    fn update(&mut self, limit: usize, sample: &Sample) -> usize {
+        let latency = sample.latency.as_secs_f64().max(1e-6);
+        let baseline = *self.baseline.get_or_insert(latency);
+        // The baseline follows latency slowly, so sustained change still
+        // registers as a gradient for a while
+        self.baseline = Some(baseline * 0.95 + latency * 0.05);
+
+        // Items in flight well under the limit say nothing about capacity
+        if !sample.dropped && sample.in_flight * 2 < limit {
+            return limit;
+        }
+        // Carry on from the unrounded limit, unless it was moved elsewhere
+        let current = match self.estimate {
+            Some(estimate) if estimate.round() as usize == limit => estimate,
+            _ => limit as f64,
+        };
+        let target = if sample.dropped {
+            current * 0.5
+        } else {
+            let gradient = (self.tolerance * baseline / latency).clamp(0.5, 1.0);
+            current * gradient + current.sqrt()
+        };
+        let next = current * (1.0 - self.smoothing) + target * self.smoothing;
+        self.estimate = Some(next);
+        next.round() as usize
+    }
+}
+
+/// Concurrency bound whose limit adapts to observed latency.
+///
+/// Items take a permit with `acquire` and report back when it is dropped;
+/// the algorithm (`Aimd` by default) then moves the limit within its
+/// bounds. Clones share one limiter, and `limit` reports the current value
+/// for diagnostics.
+///
+/// Waiters are served in arrival order, and a release wakes only as many of
+/// them as the new limit admits.
+#[derive(Clone)]
+pub struct AdaptiveLimiter {
+    inner: Arc<LimiterInner>,
+}
+
+struct LimiterInner {
+    state: Mutex<LimiterState>,
+}
+
+struct LimiterState {
+    limit: usize,
+    in_flight: usize,
+    min: usize,
+    max: usize,
+    algorithm: Box<dyn LimitAlgorithm>,
+    /// Waiting acquires in arrival order, each sent the in-flight count
+    /// when its permit is granted
+    waiters: VecDeque<oneshot::Sender<usize>>,
+}
+
+impl LimiterState {
+    /// Grant permits to waiters, oldest first, while the limit allows.
+    fn dispatch(&mut self) {
+        while self.in_flight < self.limit {
+            let Some(waiter) = self.waiters.pop_front() else {
+                break;
+            };
+            // A waiter that gave up has closed its end, so skip it
+            if waiter.send(self.in_flight + 1).is_ok() {
+                self.in_flight += 1;
+            }
+        }
+    }
+}
+
+impl AdaptiveLimiter {
+    /// Start at `initial`, adapting between 1 and 1000 with `Aimd`.
+    pub fn new(initial: usize) -> Self {
+        Self {
+            inner: Arc::new(LimiterInner {
+                state: Mutex::new(LimiterState {
+                    limit: initial.clamp(1, 1000),
+                    in_flight: 0,
+                    min: 1,
+                    max: 1000,
+                    algorithm: Box::new(Aimd::new()),
+                    waiters: VecDeque::new(),
+                }),
+            }),
+        }
+    }
+
+    /// Adjust the limit with `algorithm` instead of `Aimd`.
+    pub fn with_algorithm(self, algorithm: impl LimitAlgorithm + 'static) -> Self {
+        if let Ok(mut state) = self.inner.state.lock() {
+            state.algorithm = Box::new(algorithm);
+        }
+        self
+    }
+
+    /// Keep the limit between `min` and `max`.
+    pub fn with_bounds(self, min: usize, max: usize) -> Self {
+        if let Ok(mut state) = self.inner.state.lock() {
+            state.min = min.max(1);
+            state.max = max.max(state.min);
+            state.limit = state.limit.clamp(state.min, state.max);
+        }
+        self
+    }
+
+    /// The current limit.
+    pub fn limit(&self) -> usize {
+        self.inner.state.lock().map_or(0, |state| state.limit)
+    }
+
+    /// Items currently holding a permit.
+    pub fn in_flight(&self) -> usize {
+        self.inner.state.lock().map_or(0, |state| state.in_flight)
+    }
+
+    /// Wait until an item may start under the current limit.
+    pub async fn acquire(&self) -> AdaptivePermit {
+        let mut waiter = {
+            // A poisoned limiter no longer bounds anything
+            let Ok(mut state) = self.inner.state.lock() else {
+                return self.permit(0);
+            };
+            if state.waiters.is_empty() && state.in_flight < state.limit {
+                state.in_flight += 1;
+                return self.permit(state.in_flight);
+            }
+            let (granted, waiting) = oneshot::channel();
+            state.waiters.push_back(granted);
+            Waiter {
+                limiter: self,
+                waiting,
+                done: false,
+            }
+        };
+        let in_flight = (&mut waiter.waiting).await.unwrap_or(0);
+        waiter.done = true;
+        self.permit(in_flight)
+    }
+
+    fn permit(&self, in_flight: usize) -> AdaptivePermit {
+        AdaptivePermit {
+            limiter: self.clone(),
+            started: Instant::now(),
+            in_flight,
+            dropped: false,
+        }
+    }
+
+    fn release(&self, sample: Sample) {
+        if let Ok(mut state) = self.inner.state.lock() {
+            state.in_flight = state.in_flight.saturating_sub(1);
+            let state = &mut *state;
+            let limit = state.algorithm.update(state.limit, &sample);
+            state.limit = limit.clamp(state.min, state.max);
+            state.dispatch();
+        }
+    }
+
+    /// Return a permit that was granted but never used, without a sample.
+    fn hand_back(&self) {
+        if let Ok(mut state) = self.inner.state.lock() {
+            state.in_flight = state.in_flight.saturating_sub(1);
+            state.dispatch();
+        }
+    }
+}
+
+/// A queued `acquire`, returning its permit if dropped just as it was
+/// granted.
+struct Waiter<'a> {
+    limiter: &'a AdaptiveLimiter,
+    waiting: oneshot::Receiver<usize>,
+    done: bool,
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        self.waiting.close();
+        if self.waiting.try_recv().is_ok() {
+            self.limiter.hand_back();
+        }
+    }
+}
+
+impl std::fmt::Debug for AdaptiveLimiter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AdaptiveLimiter")
+            .field("limit", &self.limit())
+            .field("in_flight", &self.in_flight())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A permit from an `AdaptiveLimiter`, reporting its latency when dropped.
+#[derive(Debug)]
+pub struct AdaptivePermit {
+    limiter: AdaptiveLimiter,
+    started: Instant,
+    in_flight: usize,
+    dropped: bool,
+}
+
+impl AdaptivePermit {
+    /// Report the item as failed from overload, e.g. timed out.
+    pub fn dropped(mut self) {
+        self.dropped = true;
+    }
+}
+
+impl Drop for AdaptivePermit {
+    fn drop(&mut self) {
+        self.limiter.release(Sample {
+            latency: self.started.elapsed(),
+            in_flight: self.in_flight,
+            dropped: self.dropped,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fop::Content;
+
+    #[test]
+    fn test_file_size_cost() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("big.bin");
+        std::fs::write(&path, vec![0u8; 2500]).unwrap();
+        let cost = FileSizeCost::new(1000);
+
+        let mut fop = Fop::new("big.bin");
+        fop.filename = Some(path);
+        assert_eq!(cost.cost(&fop), 3);
+
+        fop.content = Some(Content::Text("tiny".to_string()));
+        assert_eq!(cost.cost(&fop), 1);
+
+        assert_eq!(cost.cost(&Fop::new("*.missing")), 1);
+    }
+
+    #[test]
+    fn test_fixed_cost_and_closures() {
+        assert_eq!(FixedCost(8).cost(&Fop::new("run.sh")), 8);
+
+        let by_name = |fop: &Fop| if fop.file_or_pattern.ends_with(".sh") { 4 } else { 1 };
+        assert_eq!(by_name.cost(&Fop::new("run.sh")), 4);
+        assert_eq!(by_name.cost(&Fop::new("notes.txt")), 1);
+    }
+
+    #[test]
+    fn test_token_bucket_reservations() {
+        let bucket = TokenBucket::new(10, Duration::from_secs(1)).with_burst(2);
+        let now = Instant::now();
+
+        // The burst passes at once, then tokens come every 100ms
+        assert_eq!(bucket.reserve(now), Duration::ZERO);
+        assert_eq!(bucket.reserve(now), Duration::ZERO);
+        assert_eq!(bucket.reserve(now).as_millis(), 100);
+        assert_eq!(bucket.reserve(now).as_millis(), 200);
+
+        // Refills are capped at the burst size
+        let later = now + Duration::from_secs(10);
+        assert_eq!(bucket.reserve(later), Duration::ZERO);
+        assert_eq!(bucket.reserve(later), Duration::ZERO);
+        assert!(!bucket.reserve(later).is_zero());
+    }
+
+    #[tokio::test]
+    async fn test_token_bucket_acquire_shared() {
+        let bucket = TokenBucket::new(20, Duration::from_secs(1)).with_burst(1);
+        let shared = bucket.clone();
+
+        let started = Instant::now();
+        assert_eq!(bucket.acquire().await, Duration::ZERO);
+        let waited = shared.acquire().await;
+
+        assert!(waited >= Duration::from_millis(40), "{:?}", waited);
+        assert!(started.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn test_token_bucket_refunds_dropped_waiter() {
+        let bucket = TokenBucket::new(10, Duration::from_secs(1)).with_burst(1);
+        assert_eq!(bucket.acquire().await, Duration::ZERO);
+
+        // Gives up 10ms into its 100ms wait
+        let gave_up = tokio::time::timeout(Duration::from_millis(10), bucket.acquire()).await;
+        assert!(gave_up.is_err());
+
+        // Its token is back, so the next waiter is not pushed behind it
+        let waited = bucket.acquire().await;
+        assert!(waited <= Duration::from_millis(100), "{:?}", waited);
+        assert!(waited >= Duration::from_millis(50), "{:?}", waited);
+    }
+
+    fn sample(latency_ms: u64, in_flight: usize, dropped: bool) -> Sample {
+        Sample {
+            latency: Duration::from_millis(latency_ms),
+            in_flight,
+            dropped,
+        }
+    }
+
+    #[test]
+    fn test_aimd() {
+        let mut aimd = Aimd::new()
+            .with_backoff(0.5)
+            .with_threshold(Duration::from_millis(100));
+
+        assert_eq!(aimd.update(10, &sample(10, 5, false)), 11);
+        // Underused limits don't grow
+        assert_eq!(aimd.update(10, &sample(10, 2, false)), 10);
+        assert_eq!(aimd.update(10, &sample(10, 5, true)), 5);
+        assert_eq!(aimd.update(10, &sample(500, 5, false)), 5);
+    }
+
+    #[test]
+    fn test_gradient() {
+        let mut gradient = Gradient::new().with_smoothing(1.0).with_tolerance(1.0);
+
+        // Steady latency grows the limit by its square root
+        assert_eq!(gradient.update(16, &sample(10, 16, false)), 20);
+        assert_eq!(gradient.update(16, &sample(10, 16, false)), 20);
+        // Doubled latency halves it, plus the allowance; a drop just halves it
+        assert_eq!(gradient.update(16, &sample(20, 16, false)), 12);
+        assert_eq!(gradient.update(16, &sample(10, 16, true)), 8);
+        // A limit less than half used is left alone
+        assert_eq!(gradient.update(16, &sample(10, 7, false)), 16);
+    }
+
+    #[test]
+    fn test_gradient_default_smoothing() {
+        let run = |start: usize, samples: usize, in_flight: Option<usize>, dropped: bool| {
+            let mut gradient = Gradient::new();
+            let mut limit = start;
+            for _ in 0..samples {
+                let busy = in_flight.unwrap_or(limit);
+                limit = gradient.update(limit, &sample(10, busy, dropped));
+            }
+            limit
+        };
+
+        // Small steps add up instead of rounding away
+        assert!(run(4, 20, None, false) > 4);
+        assert!(run(6, 5, None, true) < 6);
+        // Growth stops once the limit is well beyond what is in flight
+        assert!(run(10, 100, Some(10), false) <= 21);
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_limiter_serves_waiters_in_order() {
+        let limiter = AdaptiveLimiter::new(1).with_bounds(1, 1);
+        let first = limiter.acquire().await;
+
+        // A waiter that gives up holds no place and no permit
+        let gave_up = tokio::time::timeout(Duration::from_millis(10), limiter.acquire()).await;
+        assert!(gave_up.is_err());
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut tasks = Vec::new();
+        for i in 0..3 {
+            let limiter = limiter.clone();
+            let order = order.clone();
+            tasks.push(tokio::spawn(async move {
+                let _permit = limiter.acquire().await;
+                order.lock().unwrap().push(i);
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }));
+            // Let it queue before the next arrives
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        drop(first);
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(*order.lock().unwrap(), vec![0, 1, 2]);
+        assert_eq!(limiter.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_limiter() {
+        let limiter = AdaptiveLimiter::new(2)
+            .with_algorithm(Aimd::new().with_backoff(0.5))
+            .with_bounds(1, 3);
+        assert_eq!(limiter.limit(), 2);
+
+        let first = limiter.acquire().await;
+        let second = limiter.acquire().await;
+        assert_eq!(limiter.in_flight(), 2);
+        let blocked = tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await;
+        assert!(blocked.is_err());
+
+        // A full limit grows on success, up to the bound, and wakes waiters
+        let waiter = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire().await }
+        });
+        drop(first);
+        let third = waiter.await.unwrap();
+        assert_eq!(limiter.limit(), 3);
+        assert_eq!(limiter.in_flight(), 2);
+
+        // Drops back off, down to the lower bound
+        second.dropped();
+        third.dropped();
+        assert_eq!(limiter.limit(), 1);
+        assert_eq!(limiter.in_flight(), 0);
+    }
+}
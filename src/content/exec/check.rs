@@ -0,0 +1,233 @@
+//! Fail checkers: strategies deciding whether an execution succeeded.
+
+use super::run::Execution;
+
+/// Outcome of checking an execution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// The run succeeded; stdout becomes the Fop's content
+    Success,
+    /// The run failed for the given reason; `err` is set
+    Failure(String),
+    /// Not an error, but the output isn't the content either
+    ///
+    /// `content` is left unset so a later `ReadContentProcessor` falls back
+    /// to reading the file itself.
+    Fallback,
+}
+
+/// Strategy deciding whether an execution succeeded.
+///
+/// Only consulted for executions that started and finished; spawn failures
+/// and timeouts are always errors. Closures taking `&Execution` and returning
+/// a `Verdict` implement this trait.
+pub trait FailChecker: Send + Sync {
+    /// Judge a finished execution.
+    fn check(&self, execution: &Execution) -> Verdict;
+
+    /// Whether the verdict depends on stdout, which is empty for streaming
+    /// executions. Closures are assumed not to read it.
+    fn reads_stdout(&self) -> bool {
+        false
+    }
+
+    /// Whether the verdict depends on stderr, which is empty when it is
+    /// captured combined with stdout. Closures are assumed not to read it.
+    fn reads_stderr(&self) -> bool {
+        false
+    }
+
+    /// Combine with another checker; the first non-success verdict wins.
+    fn and<C: FailChecker>(self, other: C) -> And<Self, C>
+    where
+        Self: Sized,
+    {
+        And(self, other)
+    }
+}
+
+impl<F> FailChecker for F
+where
+    F: Fn(&Execution) -> Verdict + Send + Sync,
+{
+    fn check(&self, execution: &Execution) -> Verdict {
+        self(execution)
+    }
+}
+
+/// Succeeds when the exit code is in a set, e.g. 0 and 1 for `grep`.
+///
+/// The default accepts only 0. Runs ended by a signal always fail.
+#[derive(Debug, Clone)]
+pub struct SuccessCodes {
+    codes: Vec<i32>,
+}
+
+impl SuccessCodes {
+    pub fn new(codes: impl IntoIterator<Item = i32>) -> Self {
+        Self {
+            codes: codes.into_iter().collect(),
+        }
+    }
+}
+
+impl Default for SuccessCodes {
+    fn default() -> Self {
+        Self::new([0])
+    }
+}
+
+impl FailChecker for SuccessCodes {
+    fn check(&self, execution: &Execution) -> Verdict {
+        match execution.code {
+            Some(code) if self.codes.contains(&code) => Verdict::Success,
+            _ => Verdict::Failure(format!("Command exited with {}", execution)),
+        }
+    }
+}
+
+/// Fails when anything was written to stderr.
+///
+/// Ignores the exit code; combine with `SuccessCodes` to check both. Needs
+/// stderr captured separately from stdout.
+#[derive(Debug, Clone, Default)]
+pub struct StderrEmpty;
+
+impl FailChecker for StderrEmpty {
+    fn check(&self, execution: &Execution) -> Verdict {
+        if execution.stderr.is_empty() {
+            Verdict::Success
+        } else {
+            Verdict::Failure("Command wrote to stderr".to_string())
+        }
+    }
+
+    fn reads_stderr(&self) -> bool {
+        true
+    }
+}
+
+/// Fails unless stdout parses as JSON.
+///
+/// Ignores the exit code; combine with `SuccessCodes` to check both.
+#[derive(Debug, Clone, Default)]
+pub struct JsonStdout;
+
+impl FailChecker for JsonStdout {
+    fn check(&self, execution: &Execution) -> Verdict {
+        match serde_json::from_slice::<serde_json::Value>(&execution.stdout) {
+            Ok(_) => Verdict::Success,
+            Err(e) => Verdict::Failure(format!("Command output is not valid JSON: {}", e)),
+        }
+    }
+
+    fn reads_stdout(&self) -> bool {
+        true
+    }
+}
+
+/// Two checkers in sequence, created with `FailChecker::and`.
+#[derive(Debug, Clone)]
+pub struct And<A, B>(A, B);
+
+impl<A: FailChecker, B: FailChecker> FailChecker for And<A, B> {
+    fn check(&self, execution: &Execution) -> Verdict {
+        match self.0.check(execution) {
+            Verdict::Success => self.1.check(execution),
+            other => other,
+        }
+    }
+
+    fn reads_stdout(&self) -> bool {
+        self.0.reads_stdout() || self.1.reads_stdout()
+    }
+
+    fn reads_stderr(&self) -> bool {
+        self.0.reads_stderr() || self.1.reads_stderr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn execution(code: Option<i32>, stdout: &[u8], stderr: &[u8]) -> Execution {
+        Execution {
+            code,
+            signal: if code.is_none() { Some(9) } else { None },
+            stdout: stdout.to_vec(),
+            stderr: stderr.to_vec(),
+            duration: Duration::ZERO,
+            stdin_error: None,
+        }
+    }
+
+    #[test]
+    fn test_success_codes() {
+        let grep = SuccessCodes::new([0, 1]);
+        assert_eq!(grep.check(&execution(Some(1), b"", b"")), Verdict::Success);
+        assert_eq!(
+            grep.check(&execution(Some(2), b"", b"")),
+            Verdict::Failure("Command exited with exit code 2".to_string())
+        );
+        assert!(matches!(
+            SuccessCodes::default().check(&execution(None, b"", b"")),
+            Verdict::Failure(reason) if reason.contains("signal 9")
+        ));
+    }
+
+    #[test]
+    fn test_stderr_empty_and_json_stdout() {
+        assert_eq!(
+            StderrEmpty.check(&execution(Some(0), b"", b"")),
+            Verdict::Success
+        );
+        assert!(matches!(
+            StderrEmpty.check(&execution(Some(0), b"", b"warning\n")),
+            Verdict::Failure(_)
+        ));
+        assert_eq!(
+            JsonStdout.check(&execution(Some(0), b"{\"ok\": true}", b"")),
+            Verdict::Success
+        );
+        assert!(matches!(
+            JsonStdout.check(&execution(Some(0), b"ok", b"")),
+            Verdict::Failure(reason) if reason.contains("not valid JSON")
+        ));
+    }
+
+    #[test]
+    fn test_and_and_closures() {
+        let checker = SuccessCodes::default().and(JsonStdout);
+        assert!(matches!(
+            checker.check(&execution(Some(1), b"{}", b"")),
+            Verdict::Failure(reason) if reason.contains("exit code 1")
+        ));
+        assert!(matches!(
+            checker.check(&execution(Some(0), b"nope", b"")),
+            Verdict::Failure(reason) if reason.contains("JSON")
+        ));
+        assert!(checker.reads_stdout());
+        assert!(!SuccessCodes::default().and(StderrEmpty).reads_stdout());
+        assert!(SuccessCodes::default().and(StderrEmpty).reads_stderr());
+        assert!(!checker.reads_stderr());
+
+        let empty_is_fallback = |execution: &Execution| {
+            if execution.stdout.is_empty() {
+                Verdict::Fallback
+            } else {
+                Verdict::Success
+            }
+        };
+        let checker = SuccessCodes::default().and(empty_is_fallback);
+        assert_eq!(
+            checker.check(&execution(Some(0), b"", b"")),
+            Verdict::Fallback
+        );
+        assert_eq!(
+            checker.check(&execution(Some(0), b"x", b"")),
+            Verdict::Success
+        );
+    }
+}
@@ -0,0 +1,394 @@
+//! Command construction for DoExecuteProcessor: arguments, environment,
+//! working directory and stdin.
+
+use super::interpreter::Executable;
+use super::run::StdinData;
+use crate::fop::Fop;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Working directory for executed files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum WorkingDir {
+    /// Inherit the current process's working directory
+    #[default]
+    Inherit,
+    /// The directory containing the executable
+    ExecutableDir,
+    /// A fixed directory
+    Fixed(PathBuf),
+}
+
+/// What to feed an executed file on stdin.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum StdinSource {
+    /// Closed stdin
+    #[default]
+    Null,
+    /// The Fop's existing content; closed stdin if it has none
+    Content,
+    /// Fixed bytes
+    Bytes(Vec<u8>),
+    /// Contents of another file
+    File(PathBuf),
+}
+
+impl StdinSource {
+    /// Resolve the data to feed for `fop`, or None for closed stdin.
+    pub fn resolve(&self, fop: &Fop) -> Option<StdinData> {
+        match self {
+            StdinSource::Null => None,
+            StdinSource::Content => fop
+                .content
+                .as_ref()
+                .map(|content| StdinData::Bytes(content.as_bytes().to_vec())),
+            StdinSource::Bytes(bytes) => Some(StdinData::Bytes(bytes.clone())),
+            StdinSource::File(path) => Some(StdinData::File(path.clone())),
+        }
+    }
+}
+
+/// A command-line argument, either literal or expanded per Fop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Arg {
+    /// Passed through unchanged
+    Static(OsString),
+    /// Expanded per Fop; see `expand_template` for placeholders
+    Template(String),
+}
+
+/// Argument, environment and working directory settings for executions.
+#[derive(Debug, Clone, Default)]
+pub struct CommandOptions {
+    /// Arguments in order
+    pub args: Vec<Arg>,
+    /// Start from an empty environment instead of inheriting
+    pub env_clear: bool,
+    /// Environment changes in order: Some sets a variable, None removes it
+    pub envs: Vec<(OsString, Option<OsString>)>,
+    /// Working directory for the child
+    pub working_dir: WorkingDir,
+    /// Data fed to the child's stdin
+    pub stdin: StdinSource,
+}
+
+impl CommandOptions {
+    /// Build a command running `path` for `fop` with these options, either
+    /// directly or as the script argument of an interpreter.
+    ///
+    /// The script path is made absolute so that it neither gets looked up
+    /// on `PATH` nor resolved against a changed working directory.
+    pub fn build(
+        &self,
+        fop: &Fop,
+        path: &Path,
+        executable: &Executable,
+    ) -> Result<Command, String> {
+        self.build_resolved(fop, path, path, executable)
+    }
+
+    /// Like `build`, but run `resolved` in place of `path`, e.g. the target
+    /// a trust policy actually checked. Templates and the executable's
+    /// working directory still follow `path`.
+    pub fn build_resolved(
+        &self,
+        fop: &Fop,
+        path: &Path,
+        resolved: &Path,
+        executable: &Executable,
+    ) -> Result<Command, String> {
+        let absolute = |path: &Path| {
+            std::path::absolute(path)
+                .map_err(|e| format!("Failed to resolve {}: {}", path.display(), e))
+        };
+        let program = absolute(path)?;
+        let target = absolute(resolved)?;
+        let mut command = match executable {
+            Executable::Direct => Command::new(&target),
+            Executable::Interpreter(interpreter) => {
+                let mut command = Command::new(&interpreter.program);
+                command.args(&interpreter.args).arg(&target);
+                command
+            }
+        };
+
+        for arg in &self.args {
+            match arg {
+                Arg::Static(value) => command.arg(value),
+                Arg::Template(template) => command.arg(expand_template(template, fop, path)?),
+            };
+        }
+
+        if self.env_clear {
+            command.env_clear();
+        }
+        for (key, value) in &self.envs {
+            match value {
+                Some(value) => command.env(key, value),
+                None => command.env_remove(key),
+            };
+        }
+
+        match &self.working_dir {
+            WorkingDir::Inherit => {}
+            WorkingDir::ExecutableDir => {
+                if let Some(dir) = program.parent() {
+                    command.current_dir(dir);
+                }
+            }
+            WorkingDir::Fixed(dir) => {
+                command.current_dir(dir);
+            }
+        }
+
+        Ok(command)
+    }
+}
+
+/// Expand `{placeholder}`s in an argument template.
+///
+/// | Placeholder | Value |
+/// |-------------|-------|
+/// | `{file_or_pattern}` | the original user input |
+/// | `{filename}` | the path being executed |
+/// | `{match}` | the whole path matched by the pattern |
+/// | `{match.N}` | what the Nth wildcard of the pattern matched, from 1 |
+///
+/// `{{` and `}}` produce literal braces.
+pub fn expand_template(template: &str, fop: &Fop, path: &Path) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut captures: Option<Vec<String>> = None;
+    let mut rest = template;
+
+    while let Some(idx) = rest.find(['{', '}']) {
+        out.push_str(&rest[..idx]);
+        let tail = &rest[idx..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            out.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        if tail.starts_with('}') {
+            return Err(format!("Unmatched '}}' in argument template: {}", template));
+        }
+
+        let end = tail
+            .find('}')
+            .ok_or_else(|| format!("Unclosed '{{' in argument template: {}", template))?;
+        let placeholder = &tail[1..end];
+        match placeholder {
+            "file_or_pattern" => out.push_str(&fop.file_or_pattern),
+            "filename" => out.push_str(&path.to_string_lossy()),
+            _ if placeholder == "match" || placeholder.starts_with("match.") => {
+                let index = match placeholder.strip_prefix("match.") {
+                    Some(n) => n
+                        .parse::<usize>()
+                        .map_err(|_| format!("Invalid placeholder {{{}}}", placeholder))?,
+                    None => 0,
+                };
+                if captures.is_none() {
+                    let pattern = fop.pattern.as_ref().ok_or_else(|| {
+                        format!("{{{}}} requires a pattern match", placeholder)
+                    })?;
+                    captures = Some(pattern.captures(&path.to_string_lossy()).ok_or_else(
+                        || {
+                            format!(
+                                "{} does not match pattern {}",
+                                path.display(),
+                                pattern.pattern
+                            )
+                        },
+                    )?);
+                }
+                let value = captures
+                    .as_ref()
+                    .and_then(|c| c.get(index))
+                    .ok_or_else(|| format!("Pattern has no {{{}}}", placeholder))?;
+                out.push_str(value);
+            }
+            _ => return Err(format!("Unknown placeholder {{{}}}", placeholder)),
+        }
+        rest = &tail[end + 1..];
+    }
+    out.push_str(rest);
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fop::Pattern;
+    use std::sync::Arc;
+
+    fn matched_fop() -> Fop {
+        let mut fop = Fop::new("gen/*-*.sh");
+        fop.pattern = Some(Arc::new(Pattern::new("gen/*-*.sh")));
+        fop
+    }
+
+    #[test]
+    fn test_expand_template() {
+        let fop = matched_fop();
+        let path = Path::new("gen/build-docs.sh");
+
+        assert_eq!(
+            expand_template("--input={file_or_pattern}", &fop, path).unwrap(),
+            "--input=gen/*-*.sh"
+        );
+        assert_eq!(
+            expand_template("{filename}", &fop, path).unwrap(),
+            "gen/build-docs.sh"
+        );
+        assert_eq!(
+            expand_template("{match.1}:{match.2}", &fop, path).unwrap(),
+            "build:docs"
+        );
+        assert_eq!(
+            expand_template("{match}", &fop, path).unwrap(),
+            "gen/build-docs.sh"
+        );
+        assert_eq!(
+            expand_template("{{literal}}", &fop, path).unwrap(),
+            "{literal}"
+        );
+    }
+
+    #[test]
+    fn test_expand_template_errors() {
+        let fop = matched_fop();
+        let path = Path::new("gen/build-docs.sh");
+
+        assert!(expand_template("{nope}", &fop, path).is_err());
+        assert!(expand_template("{match.9}", &fop, path).is_err());
+        assert!(expand_template("{match.x}", &fop, path).is_err());
+        assert!(expand_template("{filename", &fop, path).is_err());
+        assert!(expand_template("filename}", &fop, path).is_err());
+
+        // No pattern on the Fop
+        let fop = Fop::new("gen/build-docs.sh");
+        assert!(expand_template("{match.1}", &fop, path).is_err());
+    }
+
+    #[test]
+    fn test_expand_template_bare_pattern() {
+        let mut fop = Fop::new("*.sh");
+        fop.pattern = Some(Arc::new(Pattern::new("*.sh")));
+        let path = Path::new("./top.sh");
+
+        assert_eq!(expand_template("{match.1}", &fop, path).unwrap(), "top");
+        assert_eq!(expand_template("{match}", &fop, path).unwrap(), "./top.sh");
+    }
+
+    #[test]
+    fn test_build_command() {
+        let options = CommandOptions {
+            args: vec![
+                Arg::Static("--verbose".into()),
+                Arg::Template("{match.1}".into()),
+            ],
+            env_clear: true,
+            envs: vec![
+                ("A".into(), Some("1".into())),
+                ("B".into(), Some("2".into())),
+                ("B".into(), None),
+            ],
+            working_dir: WorkingDir::ExecutableDir,
+            ..Default::default()
+        };
+        let fop = matched_fop();
+
+        let err = options
+            .build(
+                &fop,
+                Path::new("/opt/gen/build-docs.sh"),
+                &Executable::Direct,
+            )
+            .unwrap_err();
+        assert!(err.contains("does not match pattern"));
+
+        let command = options
+            .build(&fop, Path::new("gen/build-docs.sh"), &Executable::Direct)
+            .unwrap();
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, vec!["--verbose", "build"]);
+        assert!(command.get_program().to_string_lossy().ends_with("gen/build-docs.sh"));
+        assert!(Path::new(command.get_program()).is_absolute());
+        assert_eq!(
+            command.get_current_dir(),
+            Path::new(command.get_program()).parent()
+        );
+        let envs: Vec<_> = command.get_envs().collect();
+        assert!(envs.contains(&("A".as_ref(), Some("1".as_ref()))));
+        // With a cleared environment a removed variable is simply never set
+        assert!(envs.iter().all(|(key, _)| *key != "B"));
+    }
+
+    #[test]
+    fn test_build_interpreted_command() {
+        let options = CommandOptions {
+            args: vec![Arg::Static("--check".into())],
+            ..Default::default()
+        };
+        let deno = Executable::Interpreter(crate::content::exec::Interpreter::new("deno run"));
+
+        let command = options
+            .build(&Fop::new("x"), Path::new("/src/gen.ts"), &deno)
+            .unwrap();
+
+        assert_eq!(command.get_program(), "deno");
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, vec!["run", "/src/gen.ts", "--check"]);
+
+        // A resolved target replaces the script but not templates or cwd
+        let options = CommandOptions {
+            args: vec![Arg::Template("{filename}".into())],
+            working_dir: WorkingDir::ExecutableDir,
+            ..Default::default()
+        };
+        let command = options
+            .build_resolved(
+                &Fop::new("x"),
+                Path::new("/src/link.ts"),
+                Path::new("/opt/gen.ts"),
+                &deno,
+            )
+            .unwrap();
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args, vec!["run", "/opt/gen.ts", "/src/link.ts"]);
+        assert_eq!(command.get_current_dir(), Some(Path::new("/src")));
+    }
+
+    #[test]
+    fn test_stdin_source_resolve() {
+        let mut fop = Fop::new("x");
+        assert_eq!(StdinSource::Null.resolve(&fop), None);
+        assert_eq!(StdinSource::Content.resolve(&fop), None);
+
+        fop.content = Some(crate::fop::Content::Text("hello".to_string()));
+        assert_eq!(
+            StdinSource::Content.resolve(&fop),
+            Some(StdinData::Bytes(b"hello".to_vec()))
+        );
+        assert_eq!(
+            StdinSource::File("in.txt".into()).resolve(&fop),
+            Some(StdinData::File("in.txt".into()))
+        );
+    }
+
+    #[test]
+    fn test_build_command_fixed_dir() {
+        let options = CommandOptions {
+            working_dir: WorkingDir::Fixed("/tmp".into()),
+            ..Default::default()
+        };
+
+        let command = options
+            .build(&Fop::new("x"), Path::new("/bin/true"), &Executable::Direct)
+            .unwrap();
+
+        assert_eq!(command.get_current_dir(), Some(Path::new("/tmp")));
+        assert_eq!(command.get_args().count(), 0);
+    }
+}
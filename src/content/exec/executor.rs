@@ -0,0 +1,218 @@
+//! Executors: strategies that run a built command for DoExecuteProcessor.
+
+use super::run::{self, Execution, Invocation, RunError, Tee, TeeTarget};
+use futures::future::BoxFuture;
+use std::ffi::OsString;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Strategy that runs invocations for DoExecuteProcessor.
+pub trait Executor: Send + Sync {
+    /// Run the invocation to completion.
+    fn execute(&self, invocation: Invocation) -> BoxFuture<'_, Result<Execution, RunError>>;
+}
+
+impl<E: Executor + ?Sized> Executor for Arc<E> {
+    fn execute(&self, invocation: Invocation) -> BoxFuture<'_, Result<Execution, RunError>> {
+        (**self).execute(invocation)
+    }
+}
+
+/// Runs the command and captures its output silently. The default.
+#[derive(Debug, Clone, Default)]
+pub struct CaptureExecutor;
+
+impl Executor for CaptureExecutor {
+    fn execute(&self, invocation: Invocation) -> BoxFuture<'_, Result<Execution, RunError>> {
+        Box::pin(run::run(invocation, None))
+    }
+}
+
+/// Runs the command, streaming its output to the terminal as it arrives
+/// while still capturing it.
+///
+/// Each line is prefixed with the executed file's name, e.g. `[build.sh] `,
+/// so output of concurrent executions can be told apart. Lines are written
+/// whole, from the blocking thread pool rather than the async workers.
+#[derive(Debug, Clone)]
+pub struct TeeExecutor {
+    target: TeeTarget,
+}
+
+impl TeeExecutor {
+    /// Create a TeeExecutor writing to our stdout and stderr.
+    pub fn new() -> Self {
+        Self {
+            target: TeeTarget::Terminal,
+        }
+    }
+
+    /// Write both streams to a shared writer instead of the terminal.
+    pub fn with_target(mut self, target: TeeTarget) -> Self {
+        self.target = target;
+        self
+    }
+}
+
+impl Default for TeeExecutor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Executor for TeeExecutor {
+    fn execute(&self, invocation: Invocation) -> BoxFuture<'_, Result<Execution, RunError>> {
+        let tee = Tee {
+            prefix: format!("[{}] ", invocation.label),
+            target: self.target.clone(),
+        };
+        Box::pin(async move { run::run(invocation, Some(&tee)).await })
+    }
+}
+
+/// An invocation seen by a MockExecutor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedCall {
+    /// Program that would have run
+    pub program: OsString,
+    /// Arguments it would have been given
+    pub args: Vec<OsString>,
+    /// Label of the executed file
+    pub label: String,
+}
+
+type Respond = dyn Fn(&Invocation) -> Result<Execution, RunError> + Send + Sync;
+
+/// Records invocations and answers them without running anything. For tests.
+///
+/// For streaming invocations the answer's stdout is sent to the output sink.
+pub struct MockExecutor {
+    respond: Box<Respond>,
+    calls: Mutex<Vec<RecordedCall>>,
+}
+
+impl MockExecutor {
+    /// Create a MockExecutor answering every invocation with `respond`.
+    pub fn new(
+        respond: impl Fn(&Invocation) -> Result<Execution, RunError> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            respond: Box::new(respond),
+            calls: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Create a MockExecutor where every run exits 0 printing `stdout`.
+    pub fn with_stdout(stdout: impl Into<Vec<u8>>) -> Self {
+        let stdout = stdout.into();
+        Self::new(move |_| {
+            Ok(Execution {
+                code: Some(0),
+                signal: None,
+                stdout: stdout.clone(),
+                stderr: Vec::new(),
+                duration: Duration::ZERO,
+                stdin_error: None,
+            })
+        })
+    }
+
+    /// Invocations seen so far, in order.
+    pub fn calls(&self) -> Vec<RecordedCall> {
+        self.calls.lock().map(|c| c.clone()).unwrap_or_default()
+    }
+}
+
+impl std::fmt::Debug for MockExecutor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockExecutor")
+            .field("calls", &self.calls)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Executor for MockExecutor {
+    fn execute(&self, mut invocation: Invocation) -> BoxFuture<'_, Result<Execution, RunError>> {
+        let call = RecordedCall {
+            program: invocation.command.get_program().to_os_string(),
+            args: invocation
+                .command
+                .get_args()
+                .map(|a| a.to_os_string())
+                .collect(),
+            label: invocation.label.clone(),
+        };
+        if let Ok(mut calls) = self.calls.lock() {
+            calls.push(call);
+        }
+        let mut result = (self.respond)(&invocation);
+        let output = invocation.output.take();
+        Box::pin(async move {
+            if let (Some(mut sink), Ok(execution)) = (output, &mut result) {
+                sink.send(&std::mem::take(&mut execution.stdout)).await;
+                sink.finish().await;
+            }
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::exec::run::CaptureMode;
+    use std::process::Command;
+
+    fn invocation(label: &str) -> Invocation {
+        let mut command = Command::new("/bin/sh");
+        command.arg("-c").arg("echo hi; echo oops >&2");
+        Invocation {
+            command,
+            capture: CaptureMode::Separate,
+            deadline: None,
+            stdin: None,
+            output: None,
+            max_output: None,
+            label: label.to_string(),
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_capture_executor() {
+        let execution = CaptureExecutor.execute(invocation("x")).await.unwrap();
+        assert_eq!(execution.stdout, b"hi\n");
+        assert_eq!(execution.stderr, b"oops\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_tee_executor() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let executor = TeeExecutor::new().with_target(TeeTarget::Writer(written.clone()));
+
+        let execution = executor.execute(invocation("a.sh")).await.unwrap();
+
+        assert_eq!(execution.stdout, b"hi\n");
+        let written = String::from_utf8(written.lock().unwrap().clone()).unwrap();
+        assert!(written.contains("[a.sh] hi\n"));
+        assert!(written.contains("[a.sh] oops\n"));
+    }
+
+    #[tokio::test]
+    async fn test_mock_executor() {
+        let executor = MockExecutor::with_stdout("mocked");
+
+        let execution = executor.execute(invocation("a.sh")).await.unwrap();
+
+        assert_eq!(execution.stdout, b"mocked");
+        assert_eq!(
+            executor.calls(),
+            vec![RecordedCall {
+                program: "/bin/sh".into(),
+                args: vec!["-c".into(), "echo hi; echo oops >&2".into()],
+                label: "a.sh".to_string(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! Interpreters for scripts without execute permission, by extension or shebang.
+
+pub use crate::execution::{Executable, Interpreter};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Maps extensions and shebang lines to interpreters.
+///
+/// A shebang line takes precedence over the extension. The interpreter named
+/// by a shebang (`#!/usr/bin/python3`, or `#!/usr/bin/env python3`) is looked
+/// up by its base name in the shebang map, so only mapped interpreters run.
+/// Arguments after the name, as in `#!/bin/bash -e`, are passed on after the
+/// mapped command's own.
+/// A file with an unmapped shebang is not executable, unless
+/// `with_unmapped_shebangs` opts into running it as written.
+#[derive(Debug, Clone)]
+pub struct InterpreterRegistry {
+    extensions: HashMap<String, Interpreter>,
+    shebangs: HashMap<String, Interpreter>,
+    unmapped_shebangs: bool,
+}
+
+impl InterpreterRegistry {
+    /// Create a registry with common interpreters: `.py` → `python3`,
+    /// `.sh` → `sh`, `.bash` → `bash`, `.js` → `node`, `.ts` → `deno run`,
+    /// `.rb` → `ruby`, `.pl` → `perl`, and the same interpreters when named
+    /// by a shebang, with `python` run as `python3`.
+    pub fn new() -> Self {
+        Self::empty()
+            .with_extension("py", "python3")
+            .with_extension("sh", "sh")
+            .with_extension("bash", "bash")
+            .with_extension("js", "node")
+            .with_extension("ts", "deno run")
+            .with_extension("rb", "ruby")
+            .with_extension("pl", "perl")
+            .with_shebang("python", "python3")
+            .with_shebang("python3", "python3")
+            .with_shebang("sh", "sh")
+            .with_shebang("bash", "bash")
+            .with_shebang("node", "node")
+            .with_shebang("deno", "deno run")
+            .with_shebang("ruby", "ruby")
+            .with_shebang("perl", "perl")
+    }
+
+    /// Create a registry with no mappings.
+    pub fn empty() -> Self {
+        Self {
+            extensions: HashMap::new(),
+            shebangs: HashMap::new(),
+            unmapped_shebangs: false,
+        }
+    }
+
+    /// Map a file extension (without the dot) to an interpreter command.
+    pub fn with_extension(mut self, extension: &str, command: &str) -> Self {
+        self.extensions
+            .insert(extension.to_ascii_lowercase(), Interpreter::new(command));
+        self
+    }
+
+    /// Map an interpreter named in shebang lines, e.g. `python`, to a command.
+    pub fn with_shebang(mut self, name: &str, command: &str) -> Self {
+        self.shebangs.insert(name.to_string(), Interpreter::new(command));
+        self
+    }
+
+    /// Run files whose shebang names an unmapped interpreter as written,
+    /// with whatever program the shebang names. Off by default.
+    pub fn with_unmapped_shebangs(mut self, enabled: bool) -> Self {
+        self.unmapped_shebangs = enabled;
+        self
+    }
+
+    /// Find the interpreter for `path`, whose file starts with `head`.
+    ///
+    /// Returns None for a file whose shebang names an unmapped interpreter,
+    /// unless unmapped shebangs are enabled, whatever its extension.
+    pub fn resolve(&self, path: &Path, head: &[u8]) -> Option<Interpreter> {
+        if let Some(shebang) = self.resolve_shebang(head) {
+            return shebang;
+        }
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        self.extensions.get(&extension).cloned()
+    }
+
+    /// The interpreter a shebang line picks, or None if `head` has no
+    /// usable shebang.
+    fn resolve_shebang(&self, head: &[u8]) -> Option<Option<Interpreter>> {
+        let line = head.strip_prefix(b"#!")?;
+        let line = &line[..line.iter().position(|&b| b == b'\n').unwrap_or(line.len())];
+        let line = std::str::from_utf8(line).ok()?;
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (&program, rest) = words.split_first()?;
+
+        // `#!/usr/bin/env [options] name args..` names the interpreter after env
+        let (name, args) = if base_name(program) == "env" {
+            let command = env_command(rest);
+            let (&name, args) = command.split_first()?;
+            (name, args)
+        } else {
+            (program, rest)
+        };
+
+        if let Some(mapped) = self.shebangs.get(base_name(name)) {
+            let mut interpreter = mapped.clone();
+            // `#!/usr/bin/env -S deno run ..` already names the mapped arguments
+            let named = args.len() >= mapped.args.len()
+                && args.iter().zip(&mapped.args).all(|(arg, own)| arg == own);
+            let args = if named {
+                &args[mapped.args.len()..]
+            } else {
+                args
+            };
+            interpreter.args.extend(args.iter().map(|w| w.to_string()));
+            return Some(Some(interpreter));
+        }
+        Some(self.unmapped_shebangs.then(|| Interpreter {
+            program: program.to_string(),
+            args: rest.iter().map(|w| w.to_string()).collect(),
+        }))
+    }
+}
+
+impl Default for InterpreterRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn base_name(program: &str) -> &str {
+    program.rsplit('/').next().unwrap_or(program)
+}
+
+/// The command an `env` shebang runs: its arguments after env's own options
+/// and `NAME=value` assignments.
+fn env_command<'a, 'b>(args: &'b [&'a str]) -> &'b [&'a str] {
+    let mut index = 0;
+    while let Some(&arg) = args.get(index) {
+        match arg {
+            "--" => return &args[index + 1..],
+            // Options taking the next word as their operand
+            "-u" | "--unset" | "-C" | "--chdir" => index += 2,
+            _ if arg.starts_with('-') || arg.contains('=') => index += 1,
+            _ => break,
+        }
+    }
+    args.get(index..).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpreter_new() {
+        let deno = Interpreter::new("deno run");
+        assert_eq!(deno.program, "deno");
+        assert_eq!(deno.args, vec!["run"]);
+        assert_eq!(deno.to_string(), "deno run");
+    }
+
+    #[test]
+    fn test_resolve_by_extension() {
+        let registry = InterpreterRegistry::new();
+        assert_eq!(
+            registry.resolve(Path::new("gen.py"), b"print(1)\n"),
+            Some(Interpreter::new("python3"))
+        );
+        assert_eq!(
+            registry.resolve(Path::new("tools/build.TS"), b""),
+            Some(Interpreter::new("deno run"))
+        );
+        assert_eq!(registry.resolve(Path::new("notes.txt"), b""), None);
+        assert_eq!(
+            InterpreterRegistry::empty().resolve(Path::new("gen.py"), b""),
+            None
+        );
+    }
+
+    #[test]
+    fn test_resolve_by_shebang() {
+        let registry = InterpreterRegistry::new().with_shebang("python", "python3");
+
+        // Shebang beats extension
+        assert_eq!(
+            registry.resolve(Path::new("gen.sh"), b"#!/usr/bin/env python\nprint(1)\n"),
+            Some(Interpreter::new("python3"))
+        );
+        // Shebang arguments follow the mapped command's
+        assert_eq!(
+            registry.resolve(Path::new("gen"), b"#!/bin/bash -e\necho\n"),
+            Some(Interpreter::new("bash -e"))
+        );
+        assert_eq!(
+            registry.resolve(
+                Path::new("gen"),
+                b"#!/usr/bin/env -S deno run --allow-read\n"
+            ),
+            Some(Interpreter::new("deno run --allow-read"))
+        );
+        // env's own options and their operands are skipped
+        assert_eq!(
+            registry.resolve(
+                Path::new("gen"),
+                b"#!/usr/bin/env -u VAR LANG=C python -u\n"
+            ),
+            Some(Interpreter::new("python3 -u"))
+        );
+        assert_eq!(
+            InterpreterRegistry::new().resolve(Path::new("gen"), b"#!/usr/bin/env python\n"),
+            Some(Interpreter::new("python3"))
+        );
+        // Unmapped shebangs are not executable, whatever the extension
+        assert_eq!(
+            registry.resolve(Path::new("gen.sh"), b"#!/tmp/evil\n"),
+            None
+        );
+        assert_eq!(registry.resolve(Path::new("gen"), b"#!\n"), None);
+
+        // Unless opted into, when they run as written
+        let registry = InterpreterRegistry::empty().with_unmapped_shebangs(true);
+        assert_eq!(
+            registry.resolve(Path::new("gen"), b"#!/bin/bash -e\necho\n"),
+            Some(Interpreter::new("/bin/bash -e"))
+        );
+        assert_eq!(
+            registry.resolve(Path::new("gen"), b"#!/usr/bin/env -S node --no-warnings\n"),
+            Some(Interpreter::new("/usr/bin/env -S node --no-warnings"))
+        );
+        assert_eq!(registry.resolve(Path::new("gen"), b"#!\n"), None);
+    }
+}
@@ -0,0 +1,1353 @@
+//! DoExecuteProcessor implementation.
+
+pub mod check;
+pub mod command;
+pub mod executor;
+pub mod interpreter;
+pub mod output;
+pub mod run;
+pub mod sandbox;
+pub mod trust;
+
+pub use check::{FailChecker, JsonStdout, StderrEmpty, SuccessCodes, Verdict};
+pub use command::{Arg, CommandOptions, StdinSource, WorkingDir};
+pub use executor::{CaptureExecutor, Executor, MockExecutor, TeeExecutor};
+pub use interpreter::{Executable, Interpreter, InterpreterRegistry};
+pub use output::{split_output, OutputEvent, OutputMode, OutputStream};
+pub use run::{CaptureMode, Execution, Invocation};
+pub use sandbox::{Isolation, Sandbox};
+pub use trust::{Denial, TrustPolicy};
+
+use crate::fop::{Content, ErrorKind, Fop, ProcessorError};
+use crate::processor::{AsyncProcessor, Processor};
+use crate::progress::Progress;
+use crate::stamper::{Stamper, StamperOptions};
+use output::OutputSink;
+use run::{Deadline, RunError};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+/// Default time between SIGTERM and SIGKILL for timed-out executions.
+pub const DEFAULT_KILL_GRACE: Duration = Duration::from_secs(5);
+
+/// Output events buffered for a streaming execution before the child is
+/// held back waiting for the consumer.
+const STREAM_BUFFER: usize = 16;
+
+/// Processor that runs executable files and captures their output as content.
+///
+/// Arguments, environment and working directory are configured with the
+/// builder methods below; argument templates are expanded per Fop.
+///
+/// Each run is recorded in `fop.execution` with its exit status, raw stdout
+/// and stderr, and duration, whether or not it succeeded. The fail checker
+/// (by default, exit code 0 succeeds) then decides: on success stdout is
+/// decoded into `content`, on failure `err` is set, and on fallback neither,
+/// leaving a later `ReadContentProcessor` to read the file.
+///
+/// Executions run in their own process group. With `with_timeout`, a run
+/// past its deadline has the group sent SIGTERM, then SIGKILL after the
+/// grace period, and the Fop gets an `ErrorKind::Timeout` error. Dropping an
+/// in-flight `process_one` future kills the group, and so does cancelling
+/// the token given to `with_cancellation`.
+///
+/// The deprecated sync `Processor` only runs executable files directly and
+/// captures their output. It refuses to run files, setting `err`, when a
+/// sandbox, timeout, stdin, executor, combined capture, streaming or
+/// interpreters are configured, and it neither reports progress, stamps
+/// executions, nor observes cancellation.
+///
+/// # Example
+///
+/// ```ignore
+/// let processor = DoExecuteProcessor::new()
+///     .arg("--format=json")
+///     .arg_template("--name={match.1}")
+///     .env("RUST_LOG", "info")
+///     .with_working_dir(WorkingDir::ExecutableDir)
+///     .with_timeout(Duration::from_secs(30));
+/// ```
+pub struct DoExecuteProcessor {
+    expect_execution: bool,
+    command: CommandOptions,
+    executor: Option<Arc<dyn Executor>>,
+    capture: CaptureMode,
+    fail_checker: Arc<dyn FailChecker>,
+    timeout: Option<Duration>,
+    kill_grace: Duration,
+    interpreters: Option<InterpreterRegistry>,
+    streaming: Option<OutputMode>,
+    sandbox: Option<Sandbox>,
+    trust: Option<TrustPolicy>,
+    cancel: Option<CancellationToken>,
+    progress: Option<Progress>,
+    execution_stamper: Option<Box<dyn Stamper>>,
+    execution_name: String,
+}
+
+impl DoExecuteProcessor {
+    pub fn new() -> Self {
+        Self {
+            expect_execution: false,
+            command: CommandOptions::default(),
+            executor: None,
+            capture: CaptureMode::default(),
+            fail_checker: Arc::new(SuccessCodes::default()),
+            timeout: None,
+            kill_grace: DEFAULT_KILL_GRACE,
+            interpreters: None,
+            streaming: None,
+            sandbox: None,
+            trust: None,
+            cancel: None,
+            progress: None,
+            execution_stamper: None,
+            execution_name: "executionStamp".to_string(),
+        }
+    }
+
+    pub fn expect_execution(mut self, value: bool) -> Self {
+        self.expect_execution = value;
+        self
+    }
+
+    /// Add a static argument.
+    pub fn arg(mut self, arg: impl Into<OsString>) -> Self {
+        self.command.args.push(Arg::Static(arg.into()));
+        self
+    }
+
+    /// Add several static arguments.
+    pub fn args(mut self, args: impl IntoIterator<Item = impl Into<OsString>>) -> Self {
+        self.command
+            .args
+            .extend(args.into_iter().map(|arg| Arg::Static(arg.into())));
+        self
+    }
+
+    /// Add an argument template expanded per Fop.
+    ///
+    /// Supports `{file_or_pattern}`, `{filename}`, `{match}` and `{match.N}`;
+    /// see `command::expand_template`.
+    pub fn arg_template(mut self, template: impl Into<String>) -> Self {
+        self.command.args.push(Arg::Template(template.into()));
+        self
+    }
+
+    /// Set an environment variable for the child.
+    pub fn env(mut self, key: impl Into<OsString>, value: impl Into<OsString>) -> Self {
+        self.command.envs.push((key.into(), Some(value.into())));
+        self
+    }
+
+    /// Remove an inherited environment variable from the child.
+    pub fn env_remove(mut self, key: impl Into<OsString>) -> Self {
+        self.command.envs.push((key.into(), None));
+        self
+    }
+
+    /// Set whether the child starts from an empty environment instead of inheriting.
+    ///
+    /// Variables set with `env` are still applied.
+    pub fn env_clear(mut self, clear: bool) -> Self {
+        self.command.env_clear = clear;
+        self
+    }
+
+    /// Set the working directory for the child.
+    pub fn with_working_dir(mut self, dir: WorkingDir) -> Self {
+        self.command.working_dir = dir;
+        self
+    }
+
+    /// Set what is fed to the child's stdin: the Fop's content, fixed bytes,
+    /// or another file. Stdin is closed by default.
+    ///
+    /// Input is written concurrently with reading output. Write failures
+    /// are recorded in `execution.stdin_error` and reported in `err`,
+    /// separately from the exit status.
+    pub fn with_stdin(mut self, stdin: StdinSource) -> Self {
+        self.command.stdin = stdin;
+        self
+    }
+
+    /// Set the strategy that runs executions.
+    ///
+    /// Defaults to `CaptureExecutor`; `TeeExecutor` also shows output as it
+    /// runs, and `MockExecutor` runs nothing.
+    pub fn with_executor(mut self, executor: impl Executor + 'static) -> Self {
+        self.executor = Some(Arc::new(executor));
+        self
+    }
+
+    /// Set how stdout and stderr are captured.
+    pub fn with_capture(mut self, capture: CaptureMode) -> Self {
+        self.capture = capture;
+        self
+    }
+
+    /// Set the strategy deciding whether an execution succeeded.
+    ///
+    /// Accepts the built-in checkers, combinations made with
+    /// `FailChecker::and`, or a closure `Fn(&Execution) -> Verdict`.
+    pub fn with_fail_checker(mut self, checker: impl FailChecker + 'static) -> Self {
+        self.fail_checker = Arc::new(checker);
+        self
+    }
+
+    /// Set a deadline for each execution.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set how long a timed-out execution gets between SIGTERM and SIGKILL.
+    pub fn with_kill_grace(mut self, grace: Duration) -> Self {
+        self.kill_grace = grace;
+        self
+    }
+
+    /// Run files without execute permission through an interpreter picked
+    /// by their shebang line or extension. Off by default.
+    ///
+    /// Files with execute permission still run directly. A shebang naming an
+    /// interpreter the registry doesn't map leaves the file unexecuted.
+    /// `fop.executable` records which way a file was run.
+    pub fn with_interpreters(mut self, registry: InterpreterRegistry) -> Self {
+        self.interpreters = Some(registry);
+        self
+    }
+
+    /// Stream stdout instead of waiting for the process to exit.
+    ///
+    /// `process_one` returns as soon as the process starts, with a live
+    /// `OutputStream` as `fop.output` in place of `content`. Its last event
+    /// carries the execution record and any error. `split_output` turns it
+    /// into one Fop per line or chunk.
+    ///
+    /// Streamed stdout is not captured, so the fail checker sees it empty:
+    /// files are refused with an error when the checker reads stdout, as
+    /// `JsonStdout` does, and a `Fallback` verdict counts as success.
+    pub fn with_streaming(mut self, mode: OutputMode) -> Self {
+        self.streaming = Some(mode);
+        self
+    }
+
+    /// Run every execution in a sandbox: resource limits, a clean
+    /// environment, a restricted working directory or namespace isolation.
+    ///
+    /// If the sandbox cannot be applied the file is not run, and `err` is
+    /// set with a `Sandbox` kind.
+    pub fn with_sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    /// Only run files the policy trusts, see `TrustPolicy`.
+    ///
+    /// A denied file is not run and gets an `err` with a `Denied` kind
+    /// saying why, whether or not execution is expected.
+    pub fn with_trust_policy(mut self, policy: TrustPolicy) -> Self {
+        self.trust = Some(policy);
+        self
+    }
+
+    /// Kill running executions when `token` is cancelled, streaming ones
+    /// included, and refuse to start new ones.
+    ///
+    /// Affected Fops get an `err` with a `Cancelled` kind.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Report executions as they start and finish.
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Set a stamper for measuring each execution, recorded in `fop.stamps`
+    /// under the execution name and in `fop.timestamp`, except when
+    /// streaming.
+    pub fn with_execution_stamper(mut self, stamper: impl Stamper + 'static) -> Self {
+        self.execution_stamper = Some(Box::new(stamper));
+        self
+    }
+
+    /// Set the field name for the execution stamp, `executionStamp` by
+    /// default.
+    pub fn with_execution_name(mut self, name: impl Into<String>) -> Self {
+        self.execution_name = name.into();
+        self
+    }
+
+    /// The configured executor, `CaptureExecutor` by default.
+    fn executor(&self) -> Arc<dyn Executor> {
+        self.executor
+            .clone()
+            .unwrap_or_else(|| Arc::new(CaptureExecutor))
+    }
+
+    /// The first configured option the sync processor can't honour, if any.
+    fn unsupported_by_sync(&self) -> Option<&'static str> {
+        [
+            (self.timeout.is_some(), "timeouts"),
+            (self.command.stdin != StdinSource::Null, "stdin"),
+            (self.executor.is_some(), "executors"),
+            (self.capture == CaptureMode::Combined, "combined capture"),
+            (self.streaming.is_some(), "streaming"),
+            (self.interpreters.is_some(), "interpreters"),
+        ]
+        .into_iter()
+        .find_map(|(set, option)| set.then_some(option))
+    }
+
+    /// Decide how to run `path`, or None if it can't be run.
+    async fn resolve_executable(&self, path: &Path) -> Option<Executable> {
+        if Self::is_executable(path) {
+            return Some(Executable::Direct);
+        }
+        let registry = self.interpreters.as_ref()?;
+        // Only regular files: opening a FIFO to sniff its shebang would block
+        if !tokio::fs::metadata(path).await.ok()?.is_file() {
+            return None;
+        }
+        let mut head = vec![0; 256];
+        let mut file = tokio::fs::File::open(path).await.ok()?;
+        let n = file.read(&mut head).await.ok()?;
+        head.truncate(n);
+        registry.resolve(path, &head).map(Executable::Interpreter)
+    }
+
+    /// Apply the fail checker's verdict on a finished execution to the Fop.
+    fn record(
+        fop: &mut Fop,
+        execution: Execution,
+        checker: &dyn FailChecker,
+        capture: CaptureMode,
+    ) {
+        match Self::judge(&execution, checker, capture) {
+            Ok(true) => {
+                let stdout = String::from_utf8_lossy(&execution.stdout).to_string();
+                fop.content = Some(Content::Text(stdout));
+            }
+            Ok(false) => {}
+            Err(e) => fop.err = Some(e),
+        }
+        fop.execution = Some(execution);
+    }
+
+    /// Judge a finished execution: whether its stdout is the content
+    /// (false for a fallback), or the error to report.
+    fn judge(
+        execution: &Execution,
+        checker: &dyn FailChecker,
+        capture: CaptureMode,
+    ) -> Result<bool, ProcessorError> {
+        let use_stdout = match checker.check(execution) {
+            Verdict::Success => true,
+            Verdict::Fallback => false,
+            Verdict::Failure(reason) => {
+                let diagnostics = match capture {
+                    CaptureMode::Separate => &execution.stderr,
+                    CaptureMode::Combined => &execution.stdout,
+                };
+                let message = if diagnostics.is_empty() {
+                    reason
+                } else {
+                    format!("{}: {}", reason, String::from_utf8_lossy(diagnostics))
+                };
+                return Err(ProcessorError::new("DoExecuteProcessor", message));
+            }
+        };
+        match &execution.stdin_error {
+            Some(e) => Err(ProcessorError::new(
+                "DoExecuteProcessor",
+                format!("Failed to write stdin: {}", e),
+            )),
+            None => Ok(use_stdout),
+        }
+    }
+
+    /// Describe a run that did not finish.
+    fn run_error(e: RunError, path: &Path) -> ProcessorError {
+        match e {
+            RunError::TimedOut(after) => ProcessorError::new(
+                "DoExecuteProcessor",
+                format!(
+                    "Execution of {} timed out after {:?}",
+                    path.display(),
+                    after
+                ),
+            )
+            .with_kind(ErrorKind::Timeout),
+            RunError::OutputLimit(limit) => ProcessorError::new(
+                "DoExecuteProcessor",
+                format!(
+                    "Execution of {} was killed after writing more than {} bytes",
+                    path.display(),
+                    limit
+                ),
+            )
+            .with_kind(ErrorKind::OutputLimit),
+            RunError::Io(e) => ProcessorError::new(
+                "DoExecuteProcessor",
+                format!("Failed to execute {}: {}", path.display(), e),
+            ),
+        }
+    }
+
+    fn cancelled(path: &Path) -> ProcessorError {
+        ProcessorError::new(
+            "DoExecuteProcessor",
+            format!("Execution of {} was cancelled", path.display()),
+        )
+        .with_kind(ErrorKind::Cancelled)
+    }
+
+    /// Run `invocation` unless `cancel` fires first, in which case the run
+    /// is dropped, killing its process group.
+    async fn execute(
+        executor: &dyn Executor,
+        invocation: Invocation,
+        cancel: Option<&CancellationToken>,
+        progress: Option<&Progress>,
+        path: &Path,
+    ) -> Result<Execution, ProcessorError> {
+        let _running = progress.map(Progress::execution);
+        let run = executor.execute(invocation);
+        let result = match cancel {
+            Some(token) => tokio::select! {
+                result = run => result,
+                _ = token.cancelled() => return Err(Self::cancelled(path)),
+            },
+            None => run.await,
+        };
+        result.map_err(|e| Self::run_error(e, path))
+    }
+
+    fn denied(path: &Path, denial: Denial) -> ProcessorError {
+        ProcessorError::new(
+            "DoExecuteProcessor",
+            format!("Execution of {} denied: {}", path.display(), denial),
+        )
+        .with_kind(ErrorKind::Denied(denial))
+    }
+
+    fn sandbox_error(path: &Path, reason: impl std::fmt::Display) -> ProcessorError {
+        ProcessorError::new(
+            "DoExecuteProcessor",
+            format!(
+                "Sandbox could not be applied to {}: {}",
+                path.display(),
+                reason
+            ),
+        )
+        .with_kind(ErrorKind::Sandbox)
+    }
+
+    /// Path to execute: the filename, falling back to file_or_pattern.
+    fn target_path(fop: &Fop) -> PathBuf {
+        fop.filename
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(&*fop.file_or_pattern))
+    }
+
+    #[cfg(unix)]
+    fn is_executable(path: &Path) -> bool {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .ok()
+            .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    }
+
+    #[cfg(windows)]
+    fn is_executable(path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| matches!(e, "exe" | "bat" | "cmd" | "ps1") && path.is_file())
+            .unwrap_or(false)
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    fn is_executable(path: &Path) -> bool {
+        path.is_file()
+    }
+}
+
+impl Default for DoExecuteProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AsyncProcessor for DoExecuteProcessor {
+    fn name(&self) -> &'static str {
+        "DoExecuteProcessor"
+    }
+
+    async fn process_one(&self, mut fop: Fop) -> Vec<Fop> {
+        let expect_execution = self.expect_execution;
+        let path = Self::target_path(&fop);
+
+        if self
+            .cancel
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            fop.err = Some(Self::cancelled(&path));
+            return vec![fop];
+        }
+
+        let Some(executable) = self.resolve_executable(&path).await else {
+            if expect_execution {
+                let err = ProcessorError::new(
+                    "DoExecuteProcessor",
+                    format!("File is not executable: {}", path.display()),
+                );
+                fop.err = Some(err);
+            }
+            return vec![fop];
+        };
+
+        // Run what the policy checked, not whatever `path` points at by now
+        let mut resolved = path.clone();
+        if let Some(policy) = &self.trust {
+            let policy = policy.clone();
+            let target = path.clone();
+            let verdict = tokio::task::spawn_blocking(move || policy.check(&target))
+                .await
+                .unwrap_or_else(|e| Err(Denial::Unverifiable(e.to_string())));
+            match verdict {
+                Ok(checked) => resolved = checked,
+                Err(denial) => {
+                    fop.err = Some(Self::denied(&path, denial));
+                    return vec![fop];
+                }
+            }
+        }
+
+        let built = self
+            .command
+            .build_resolved(&fop, &path, &resolved, &executable);
+        fop.executable = Some(executable);
+        let mut command = match built {
+            Ok(command) => command,
+            Err(e) => {
+                fop.err = Some(ProcessorError::new("DoExecuteProcessor", e));
+                return vec![fop];
+            }
+        };
+        if let Some(sandbox) = &self.sandbox {
+            if let Err(e) = sandbox.apply(&mut command) {
+                fop.err = Some(Self::sandbox_error(&path, e));
+                return vec![fop];
+            }
+        }
+
+        let deadline = self.timeout.map(|timeout| Deadline {
+            timeout,
+            grace: self.kill_grace,
+        });
+        let mut invocation = Invocation {
+            command,
+            capture: self.capture,
+            deadline,
+            stdin: self.command.stdin.resolve(&fop),
+            output: None,
+            max_output: self.sandbox.as_ref().and_then(Sandbox::max_output),
+            label: path.file_name().map_or_else(
+                || path.display().to_string(),
+                |name| name.to_string_lossy().into_owned(),
+            ),
+        };
+
+        if self.capture == CaptureMode::Combined && self.fail_checker.reads_stderr() {
+            let err = ProcessorError::new(
+                "DoExecuteProcessor",
+                "Fail checker reads stderr, which is merged into stdout by combined capture",
+            );
+            fop.err = Some(err);
+            return vec![fop];
+        }
+
+        if let Some(mode) = self.streaming {
+            if self.fail_checker.reads_stdout() {
+                let err = ProcessorError::new(
+                    "DoExecuteProcessor",
+                    "Fail checker reads stdout, which is not captured when streaming",
+                );
+                fop.err = Some(err);
+                return vec![fop];
+            }
+            let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+            invocation.output = Some(OutputSink::new(tx.clone(), mode));
+            let executor = self.executor();
+            let checker = self.fail_checker.clone();
+            let capture = self.capture;
+            let cancel = self.cancel.clone();
+            let progress = self.progress.clone();
+            let task = tokio::spawn(async move {
+                let run = Self::execute(
+                    executor.as_ref(),
+                    invocation,
+                    cancel.as_ref(),
+                    progress.as_ref(),
+                    &path,
+                );
+                let (execution, err) = match run.await {
+                    Ok(execution) => {
+                        let err = Self::judge(&execution, checker.as_ref(), capture).err();
+                        (Some(execution), err)
+                    }
+                    Err(err) => (None, Some(err)),
+                };
+                let _ = tx.send(OutputEvent::Exit { execution, err }).await;
+            });
+            fop.output = Some(OutputStream::new(rx, mode, task.abort_handle()));
+            return vec![fop];
+        }
+
+        let options = StamperOptions {
+            processor: Some("DoExecuteProcessor"),
+        };
+        let span = self
+            .execution_stamper
+            .as_ref()
+            .map(|stamper| stamper.span(&options, "DoExecuteProcessor", &fop));
+        let executor = self.executor();
+        let run = Self::execute(
+            executor.as_ref(),
+            invocation,
+            self.cancel.as_ref(),
+            self.progress.as_ref(),
+            &path,
+        );
+        let result = run.await;
+        if let Some(span) = span {
+            fop.timestamp = Some(span.finish(&mut fop, &self.execution_name));
+        }
+        match result {
+            Ok(execution) => Self::record(
+                &mut fop,
+                execution,
+                self.fail_checker.as_ref(),
+                self.capture,
+            ),
+            Err(err) => fop.err = Some(err),
+        }
+        vec![fop]
+    }
+}
+
+impl Processor for DoExecuteProcessor {
+    fn process<'a, I>(&self, input: I) -> impl Iterator<Item = Fop> + 'a
+    where
+        I: Iterator<Item = Fop> + 'a,
+    {
+        let expect_execution = self.expect_execution;
+        let name = "DoExecuteProcessor".to_string();
+        let options = self.command.clone();
+        let checker = self.fail_checker.clone();
+        let sandboxed = self.sandbox.is_some();
+        let unsupported = self.unsupported_by_sync();
+        let trust = self.trust.clone();
+
+        input.map(move |mut fop| {
+            let path = Self::target_path(&fop);
+
+            if !Self::is_executable(&path) {
+                if expect_execution {
+                    let err = ProcessorError::new(
+                        name.as_str(),
+                        format!("File is not executable: {}", path.display()),
+                    );
+                    fop.err = Some(err);
+                }
+                return fop;
+            }
+
+            let resolved = match trust.as_ref().map(|policy| policy.check(&path)) {
+                Some(Ok(checked)) => checked,
+                Some(Err(denial)) => {
+                    fop.err = Some(Self::denied(&path, denial));
+                    return fop;
+                }
+                None => path.clone(),
+            };
+            if sandboxed {
+                let reason = "sandboxing is only supported by the async processor";
+                fop.err = Some(Self::sandbox_error(&path, reason));
+                return fop;
+            }
+            if let Some(option) = unsupported {
+                let err = ProcessorError::new(
+                    name.as_str(),
+                    format!(
+                        "Not running {}: {} are only supported by the async processor",
+                        path.display(),
+                        option
+                    ),
+                );
+                fop.err = Some(err);
+                return fop;
+            }
+
+            fop.executable = Some(Executable::Direct);
+            let start = std::time::Instant::now();
+            let output = options
+                .build_resolved(&fop, &path, &resolved, &Executable::Direct)
+                .map_err(|e| ProcessorError::new(name.as_str(), e))
+                .and_then(|mut command| {
+                    command.output().map_err(|e| {
+                        ProcessorError::new(
+                            name.as_str(),
+                            format!("Failed to execute {}: {}", path.display(), e),
+                        )
+                    })
+                });
+
+            match output {
+                Ok(o) => {
+                    let execution = Execution::new(o.status, o.stdout, o.stderr, start.elapsed());
+                    Self::record(&mut fop, execution, checker.as_ref(), CaptureMode::Separate);
+                }
+                Err(e) => fop.err = Some(e),
+            }
+            fop
+        })
+    }
+
+    fn name(&self) -> &str {
+        "DoExecuteProcessor"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_do_execute_processor() {
+        let p = DoExecuteProcessor::new();
+        assert_eq!(Processor::name(&p), "DoExecuteProcessor");
+        assert!(!p.expect_execution);
+    }
+
+    #[test]
+    fn test_expect_execution() {
+        let p = DoExecuteProcessor::new().expect_execution(true);
+        assert!(p.expect_execution);
+    }
+
+    #[test]
+    fn test_non_executable_file() {
+        let p = DoExecuteProcessor::new();
+        let mut fop = Fop::new("notexec.txt");
+        fop.filename = Some("/some/file.txt".into());
+
+        let results: Vec<_> = p.process(vec![fop].into_iter()).collect();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].executable, None);
+        assert!(results[0].content.is_none());
+    }
+
+    #[test]
+    fn test_expect_execution_non_executable() {
+        let p = DoExecuteProcessor::new().expect_execution(true);
+        let mut fop = Fop::new("notexec.txt");
+        fop.filename = Some("/some/file.txt".into());
+
+        let results: Vec<_> = p.process(vec![fop].into_iter()).collect();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].err.is_some());
+        assert_eq!(
+            results[0].err.as_ref().unwrap().processor,
+            "DoExecuteProcessor"
+        );
+    }
+
+    #[test]
+    fn test_default() {
+        let p = DoExecuteProcessor::default();
+        assert_eq!(Processor::name(&p), "DoExecuteProcessor");
+    }
+
+    #[tokio::test]
+    async fn test_async_do_execute_processor() {
+        let p = DoExecuteProcessor::new();
+        assert_eq!(AsyncProcessor::name(&p), "DoExecuteProcessor");
+        assert!(!p.expect_execution);
+    }
+
+    #[tokio::test]
+    async fn test_async_expect_execution() {
+        let p = DoExecuteProcessor::new().expect_execution(true);
+        assert!(p.expect_execution);
+    }
+
+    #[tokio::test]
+    async fn test_async_non_executable_file() {
+        let p = DoExecuteProcessor::new();
+        let mut fop = Fop::new("notexec.txt");
+        fop.filename = Some("/some/file.txt".into());
+
+        let results = p.process_one(fop).await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].executable, None);
+        assert!(results[0].content.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_async_expect_execution_non_executable() {
+        let p = DoExecuteProcessor::new().expect_execution(true);
+        let mut fop = Fop::new("notexec.txt");
+        fop.filename = Some("/some/file.txt".into());
+
+        let results = p.process_one(fop).await;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].err.is_some());
+        assert_eq!(
+            results[0].err.as_ref().unwrap().processor,
+            "DoExecuteProcessor"
+        );
+    }
+
+    #[cfg(unix)]
+    fn write_script(dir: &Path, name: &str, body: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}", body)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_async_args_env_and_working_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = write_script(
+            dir.path(),
+            "report-build.sh",
+            "echo \"$1|$2|$GREETING|${HOME:-unset}|$(pwd)\"\n",
+        );
+        let p = DoExecuteProcessor::new()
+            .arg("--verbose")
+            .arg_template("{match.1}")
+            .env_clear(true)
+            .env("GREETING", "hi")
+            .with_working_dir(WorkingDir::ExecutableDir);
+        let pattern = format!("{}/report-*.sh", dir.path().display());
+        let mut fop = Fop::new(pattern.as_str());
+        fop.filename = Some(script.clone());
+        fop.pattern = Some(std::sync::Arc::new(crate::fop::Pattern::new(&pattern)));
+
+        let results = p.process_one(fop).await;
+
+        assert!(results[0].err.is_none(), "{:?}", results[0].err);
+        assert_eq!(results[0].filename.as_ref(), Some(&script));
+        let dir = dir.path().canonicalize().unwrap();
+        assert_eq!(
+            results[0].content.as_ref().unwrap().as_bytes(),
+            format!("--verbose|build|hi|unset|{}\n", dir.display()).as_bytes()
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_async_template_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = write_script(dir.path(), "run.sh", "echo ran\n");
+        let p = DoExecuteProcessor::new().arg_template("{match.1}");
+        let mut fop = Fop::new("run.sh");
+        fop.filename = Some(script);
+
+        let results = p.process_one(fop).await;
+
+        assert!(results[0].content.is_none());
+        assert_eq!(results[0].executable, Some(Executable::Direct));
+        let err = results[0].err.as_ref().unwrap();
+        assert!(err.source.contains("requires a pattern match"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_async_timeout() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = write_script(dir.path(), "hang.sh", "sleep 10\n");
+        let p = DoExecuteProcessor::new()
+            .with_timeout(Duration::from_millis(100))
+            .with_kill_grace(Duration::from_millis(100));
+        let mut fop = Fop::new("hang.sh");
+        fop.filename = Some(script);
+
+        let results = p.process_one(fop).await;
+
+        assert!(results[0].content.is_none());
+        let err = results[0].err.as_ref().unwrap();
+        assert_eq!(err.kind, ErrorKind::Timeout);
+        assert!(err.source.contains("timed out"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_async_execution_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = write_script(dir.path(), "warn.sh", "echo result\necho careful >&2\n");
+        let mut fop = Fop::new("warn.sh");
+        fop.filename = Some(script);
+
+        let results = DoExecuteProcessor::new().process_one(fop).await;
+
+        assert!(results[0].err.is_none());
+        let execution = results[0].execution.as_ref().unwrap();
+        assert_eq!(execution.code, Some(0));
+        assert_eq!(execution.stdout, b"result\n");
+        assert_eq!(execution.stderr, b"careful\n");
+        assert_eq!(results[0].content.as_ref().unwrap().as_bytes(), b"result\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_async_combined_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = write_script(dir.path(), "fail.sh", "echo step\necho broke >&2\nexit 2\n");
+        let mut fop = Fop::new("fail.sh");
+        fop.filename = Some(script);
+
+        let results = DoExecuteProcessor::new()
+            .with_capture(CaptureMode::Combined)
+            .process_one(fop)
+            .await;
+
+        let execution = results[0].execution.as_ref().unwrap();
+        assert_eq!(execution.code, Some(2));
+        assert_eq!(execution.stdout, b"step\nbroke\n");
+        let err = results[0].err.as_ref().unwrap();
+        assert!(err.source.contains("exit code 2"));
+        assert!(err.source.contains("step\nbroke"));
+        assert!(results[0].content.is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_async_combined_refuses_stderr_checker() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = write_script(dir.path(), "warn.sh", "echo warning >&2\n");
+        let mut fop = Fop::new("warn.sh");
+        fop.filename = Some(script);
+
+        let results = DoExecuteProcessor::new()
+            .with_capture(CaptureMode::Combined)
+            .with_fail_checker(SuccessCodes::default().and(StderrEmpty))
+            .process_one(fop)
+            .await;
+
+        let err = results[0].err.as_ref().unwrap();
+        assert!(err.source.contains("reads stderr"));
+        assert!(results[0].execution.is_none());
+        assert!(results[0].content.is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_async_fail_checker() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = write_script(dir.path(), "search.sh", "echo warned >&2\nexit 1\n");
+        let fop = || {
+            let mut fop = Fop::new("search.sh");
+            fop.filename = Some(script.clone());
+            fop
+        };
+
+        let grep_like = DoExecuteProcessor::new().with_fail_checker(SuccessCodes::new([0, 1]));
+        let results = grep_like.process_one(fop()).await;
+        assert!(results[0].err.is_none());
+        assert!(results[0].content.is_some());
+
+        let strict =
+            DoExecuteProcessor::new().with_fail_checker(SuccessCodes::new([0, 1]).and(StderrEmpty));
+        let results = strict.process_one(fop()).await;
+        let err = results[0].err.as_ref().unwrap();
+        assert_eq!(err.source, "Command wrote to stderr: warned\n");
+
+        let fallback =
+            DoExecuteProcessor::new().with_fail_checker(|_: &Execution| Verdict::Fallback);
+        let results = fallback.process_one(fop()).await;
+        assert!(results[0].err.is_none());
+        assert!(results[0].content.is_none());
+        assert_eq!(results[0].execution.as_ref().unwrap().code, Some(1));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_async_mock_executor() {
+        let executor = Arc::new(MockExecutor::with_stdout("mocked"));
+        let p = DoExecuteProcessor::new()
+            .arg("--check")
+            .with_executor(executor.clone());
+        let mut fop = Fop::new("sh");
+        fop.filename = Some("/bin/sh".into());
+
+        let results = p.process_one(fop).await;
+
+        assert_eq!(results[0].content.as_ref().unwrap().as_bytes(), b"mocked");
+        let calls = executor.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].program, "/bin/sh");
+        assert_eq!(calls[0].args, vec!["--check"]);
+        assert_eq!(calls[0].label, "sh");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_async_execution_stamp() {
+        use crate::stamper::PerformanceMeasureStamper;
+
+        let p = DoExecuteProcessor::new()
+            .with_executor(MockExecutor::with_stdout("mocked"))
+            .with_execution_stamper(PerformanceMeasureStamper::new());
+        let mut fop = Fop::new("sh");
+        fop.filename = Some("/bin/sh".into());
+
+        let results = p.process_one(fop).await;
+
+        let stamp = &results[0].stamps["executionStamp"];
+        assert!(stamp.start.unwrap() <= stamp.end.unwrap());
+        assert_eq!(stamp.measure.as_deref(), Some("fop-sh-end"));
+        assert_eq!(results[0].timestamp.as_ref().unwrap().end, stamp.end);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_async_stdin_from_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = write_script(dir.path(), "upper.sh", "tr a-z A-Z\n");
+        let mut fop = Fop::new("upper.sh");
+        fop.filename = Some(script);
+        fop.content = Some(Content::Text("template\n".to_string()));
+
+        let results = DoExecuteProcessor::new()
+            .with_stdin(StdinSource::Content)
+            .process_one(fop)
+            .await;
+
+        assert!(results[0].err.is_none());
+        assert_eq!(
+            results[0].content.as_ref().unwrap().as_bytes(),
+            b"TEMPLATE\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_async_stdin_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = write_script(dir.path(), "cat.sh", "cat\n");
+        let mut fop = Fop::new("cat.sh");
+        fop.filename = Some(script);
+
+        let results = DoExecuteProcessor::new()
+            .with_stdin(StdinSource::File(dir.path().join("missing")))
+            .process_one(fop)
+            .await;
+
+        assert_eq!(results[0].execution.as_ref().unwrap().code, Some(0));
+        let err = results[0].err.as_ref().unwrap();
+        assert!(err.source.starts_with("Failed to write stdin"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_async_interpreter() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("gen.sh");
+        std::fs::write(&script, "echo \"interpreted $1\"\n").unwrap();
+        let fop = || {
+            let mut fop = Fop::new("gen.sh");
+            fop.filename = Some(script.clone());
+            fop
+        };
+
+        // Without execute permission, the script is skipped by default
+        let results = DoExecuteProcessor::new().process_one(fop()).await;
+        assert_eq!(results[0].executable, None);
+        assert!(results[0].content.is_none());
+
+        let results = DoExecuteProcessor::new()
+            .arg("ok")
+            .with_interpreters(InterpreterRegistry::new())
+            .process_one(fop())
+            .await;
+        assert!(results[0].err.is_none(), "{:?}", results[0].err);
+        assert_eq!(
+            results[0].executable,
+            Some(Executable::Interpreter(Interpreter::new("sh")))
+        );
+        assert_eq!(
+            results[0].content.as_ref().unwrap().as_bytes(),
+            b"interpreted ok\n"
+        );
+
+        // A shebang naming an unmapped program is not run
+        std::fs::write(&script, "#!/usr/bin/env cat\necho \"interpreted $1\"\n").unwrap();
+        let results = DoExecuteProcessor::new()
+            .with_interpreters(InterpreterRegistry::new())
+            .process_one(fop())
+            .await;
+        assert_eq!(results[0].executable, None);
+        assert!(results[0].content.is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_async_streaming_lines() {
+        use futures::StreamExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = write_script(
+            dir.path(),
+            "gen.sh",
+            "echo first\nsleep 0.2\necho second\nexit 3\n",
+        );
+        let mut fop = Fop::new("gen.sh");
+        fop.filename = Some(script);
+
+        let started = std::time::Instant::now();
+        let results = DoExecuteProcessor::new()
+            .with_streaming(OutputMode::Lines)
+            .process_one(fop)
+            .await;
+        let output = results[0].output.as_ref().unwrap();
+        assert_eq!(output.mode(), OutputMode::Lines);
+        let mut events = output.take().unwrap();
+        assert!(output.take().is_none());
+
+        // The first line arrives before the process exits
+        match events.next().await {
+            Some(OutputEvent::Data(line)) => assert_eq!(line, b"first"),
+            other => panic!("expected data, got {:?}", other),
+        }
+        assert!(started.elapsed() < Duration::from_millis(200));
+
+        let rest: Vec<_> = events.collect().await;
+        assert!(matches!(&rest[0], OutputEvent::Data(line) if line == b"second"));
+        match &rest[1] {
+            OutputEvent::Exit { execution, err } => {
+                assert_eq!(execution.as_ref().unwrap().code, Some(3));
+                assert!(execution.as_ref().unwrap().stdout.is_empty());
+                assert!(err.as_ref().unwrap().source.contains("exit code 3"));
+            }
+            other => panic!("expected exit, got {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_async_streaming_split_output() {
+        use futures::StreamExt;
+
+        let executor = MockExecutor::with_stdout("a\nb\nc\n");
+        let p = DoExecuteProcessor::new()
+            .with_executor(executor)
+            .with_streaming(OutputMode::Lines);
+        let mut fop = Fop::new("sh");
+        fop.filename = Some("/bin/sh".into());
+
+        let results = p.process_one(fop).await;
+        let fops: Vec<Fop> = split_output(results.into_iter().next().unwrap())
+            .collect()
+            .await;
+
+        let lines: Vec<_> = fops
+            .iter()
+            .map(|f| f.content.as_ref().unwrap().as_bytes().to_vec())
+            .collect();
+        assert_eq!(lines, vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+        assert!(fops[..2].iter().all(|f| f.execution.is_none()));
+        assert_eq!(fops[2].execution.as_ref().unwrap().code, Some(0));
+        assert!(fops[2].err.is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_async_streaming_refuses_stdout_checker() {
+        let executor = Arc::new(MockExecutor::with_stdout("{}"));
+        let p = DoExecuteProcessor::new()
+            .with_executor(executor.clone())
+            .with_fail_checker(SuccessCodes::default().and(JsonStdout))
+            .with_streaming(OutputMode::Lines);
+        let mut fop = Fop::new("sh");
+        fop.filename = Some("/bin/sh".into());
+
+        let results = p.process_one(fop).await;
+
+        let err = results[0].err.as_ref().unwrap();
+        assert!(err.source.contains("not captured when streaming"));
+        assert!(results[0].output.is_none());
+        assert!(executor.calls().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_async_sandbox() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = write_script(dir.path(), "flood.sh", "yes\n");
+        let p = DoExecuteProcessor::new().with_sandbox(
+            Sandbox::new()
+                .with_clean_env()
+                .with_restricted_dir(dir.path())
+                .with_max_output(1000),
+        );
+        let mut fop = Fop::new("flood.sh");
+        fop.filename = Some(script.clone());
+
+        let results = p.process_one(fop).await;
+
+        let err = results[0].err.as_ref().unwrap();
+        assert_eq!(err.kind, ErrorKind::OutputLimit);
+        assert!(
+            err.source.contains("more than 1000 bytes"),
+            "{}",
+            err.source
+        );
+
+        // A working directory outside the restricted one is refused
+        let p = DoExecuteProcessor::new()
+            .with_working_dir(WorkingDir::Fixed("/".into()))
+            .with_sandbox(Sandbox::new().with_restricted_dir(dir.path()));
+        let mut fop = Fop::new("flood.sh");
+        fop.filename = Some(script);
+
+        let results = p.process_one(fop).await;
+
+        let err = results[0].err.as_ref().unwrap();
+        assert_eq!(err.kind, ErrorKind::Sandbox);
+        assert!(err.source.contains("Sandbox could not be applied"));
+        assert!(results[0].execution.is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_async_trust_policy() {
+        let trusted = tempfile::tempdir().unwrap();
+        let other = tempfile::tempdir().unwrap();
+        let good = write_script(trusted.path(), "good.sh", "echo ran\n");
+        let stray = write_script(other.path(), "stray.sh", "echo ran\n");
+        let p = DoExecuteProcessor::new()
+            .with_trust_policy(TrustPolicy::new().with_trusted_root(trusted.path()));
+
+        let mut fop = Fop::new("good.sh");
+        fop.filename = Some(good);
+        let results = p.process_one(fop).await;
+        assert_eq!(results[0].content.as_ref().unwrap().as_bytes(), b"ran\n");
+
+        let mut fop = Fop::new("stray.sh");
+        fop.filename = Some(stray.clone());
+        let results = p.process_one(fop).await;
+        let err = results[0].err.as_ref().unwrap();
+        assert_eq!(err.kind, ErrorKind::Denied(Denial::OutsideTrustedRoots));
+        assert!(err.source.contains("not under a trusted directory"));
+        assert!(results[0].execution.is_none());
+
+        // The sync processor enforces the policy too
+        let mut fop = Fop::new("stray.sh");
+        fop.filename = Some(stray);
+        #[allow(deprecated)]
+        let results: Vec<Fop> = p.process(std::iter::once(fop)).collect();
+        assert!(matches!(
+            results[0].err.as_ref().unwrap().kind,
+            ErrorKind::Denied(_)
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[allow(deprecated)]
+    fn test_sync_refuses_async_only_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = write_script(dir.path(), "ran.sh", "echo ran\n");
+        let fop = || {
+            let mut fop = Fop::new("ran.sh");
+            fop.filename = Some(script.clone());
+            fop
+        };
+
+        let results: Vec<Fop> = DoExecuteProcessor::new()
+            .process(std::iter::once(fop()))
+            .collect();
+        assert_eq!(results[0].content.as_ref().unwrap().as_bytes(), b"ran\n");
+
+        let refusing = [
+            DoExecuteProcessor::new().with_timeout(Duration::from_secs(5)),
+            DoExecuteProcessor::new().with_stdin(StdinSource::Bytes(b"in".to_vec())),
+            DoExecuteProcessor::new().with_executor(MockExecutor::with_stdout("mocked")),
+            DoExecuteProcessor::new().with_capture(CaptureMode::Combined),
+            DoExecuteProcessor::new().with_streaming(OutputMode::Lines),
+            DoExecuteProcessor::new().with_interpreters(InterpreterRegistry::default()),
+        ];
+        for p in refusing {
+            let results: Vec<Fop> = p.process(std::iter::once(fop())).collect();
+            let err = results[0].err.as_ref().unwrap();
+            assert!(err.source.contains("only supported by the async processor"));
+            assert!(results[0].content.is_none());
+            assert!(results[0].execution.is_none());
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_async_cancellation() {
+        let dir = tempfile::tempdir().unwrap();
+        let pid_file = dir.path().join("pid");
+        let script = write_script(
+            dir.path(),
+            "slow.sh",
+            &format!("echo $$ > {}\nexec sleep 30\n", pid_file.display()),
+        );
+        let token = CancellationToken::new();
+        let p = DoExecuteProcessor::new().with_cancellation(token.clone());
+        let mut fop = Fop::new("slow.sh");
+        fop.filename = Some(script.clone());
+
+        let canceller = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            canceller.cancel();
+        });
+        let started = std::time::Instant::now();
+        let results = p.process_one(fop).await;
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let err = results[0].err.as_ref().unwrap();
+        assert_eq!(err.kind, ErrorKind::Cancelled);
+        assert!(err.source.ends_with("was cancelled"));
+
+        // The child was killed, not left running
+        let pid = std::fs::read_to_string(&pid_file).unwrap();
+        let stat = format!("/proc/{}/stat", pid.trim());
+        for _ in 0..50 {
+            let state = std::fs::read_to_string(&stat).unwrap_or_default();
+            if state.is_empty() || state.contains(") Z") {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let state = std::fs::read_to_string(&stat).unwrap_or_default();
+        assert!(state.is_empty() || state.contains(") Z"), "{}", state);
+
+        // Nothing new starts once cancelled
+        let mut fop = Fop::new("slow.sh");
+        fop.filename = Some(script);
+        let results = p.process_one(fop).await;
+        assert_eq!(results[0].err.as_ref().unwrap().kind, ErrorKind::Cancelled);
+        assert!(results[0].execution.is_none());
+    }
+}
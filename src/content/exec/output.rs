@@ -0,0 +1,230 @@
+//! Streaming execution output: stdout delivered while the process runs.
+
+pub use crate::execution::{OutputEvent, OutputMode, OutputStream};
+
+use crate::fop::{Content, Fop};
+use futures::stream::{self, BoxStream, StreamExt};
+use tokio::sync::mpsc;
+
+/// Sending half of a streaming execution, used by executors.
+///
+/// If the receiving side goes away, further output is discarded rather than
+/// blocking the child.
+#[derive(Debug)]
+pub struct OutputSink {
+    tx: mpsc::Sender<OutputEvent>,
+    mode: OutputMode,
+    pending: Vec<u8>,
+    closed: bool,
+}
+
+impl OutputSink {
+    pub fn new(tx: mpsc::Sender<OutputEvent>, mode: OutputMode) -> Self {
+        Self {
+            tx,
+            mode,
+            pending: Vec::new(),
+            closed: false,
+        }
+    }
+
+    /// Send output read from the child, waiting while the receiver is behind.
+    pub async fn send(&mut self, data: &[u8]) {
+        for item in self.split(data) {
+            self.send_item(item).await;
+        }
+    }
+
+    /// Flush a final unterminated line.
+    pub async fn finish(&mut self) {
+        if let Some(rest) = self.rest() {
+            self.send_item(rest).await;
+        }
+    }
+
+    /// `send` for use off the async runtime.
+    pub fn blocking_send(&mut self, data: &[u8]) {
+        for item in self.split(data) {
+            self.blocking_send_item(item);
+        }
+    }
+
+    /// `finish` for use off the async runtime.
+    pub fn blocking_finish(&mut self) {
+        if let Some(rest) = self.rest() {
+            self.blocking_send_item(rest);
+        }
+    }
+
+    async fn send_item(&mut self, item: Vec<u8>) {
+        if !self.closed && self.tx.send(OutputEvent::Data(item)).await.is_err() {
+            self.closed = true;
+        }
+    }
+
+    fn blocking_send_item(&mut self, item: Vec<u8>) {
+        if !self.closed && self.tx.blocking_send(OutputEvent::Data(item)).is_err() {
+            self.closed = true;
+        }
+    }
+
+    fn split(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        if self.closed || data.is_empty() {
+            return Vec::new();
+        }
+        match self.mode {
+            OutputMode::Chunks => vec![data.to_vec()],
+            OutputMode::Lines => {
+                self.pending.extend_from_slice(data);
+                let mut lines = Vec::new();
+                while let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+                    let mut line: Vec<u8> = self.pending.drain(..=end).collect();
+                    line.pop();
+                    lines.push(line);
+                }
+                lines
+            }
+        }
+    }
+
+    fn rest(&mut self) -> Option<Vec<u8>> {
+        (!self.pending.is_empty()).then(|| std::mem::take(&mut self.pending))
+    }
+}
+
+/// Fan a Fop with streamed output out into one Fop per output event.
+///
+/// Each Fop carries one line (or chunk) as its `content`. The exit status is
+/// attached to the last one, as `execution` and `err`; a run with no output
+/// yields a single Fop carrying just those. Fops without an untaken output
+/// stream pass through unchanged.
+pub fn split_output(mut fop: Fop) -> BoxStream<'static, Fop> {
+    let Some(events) = fop.output.take().and_then(|output| output.take()) else {
+        return stream::iter([fop]).boxed();
+    };
+    fop.content = None;
+
+    struct State {
+        template: Fop,
+        events: BoxStream<'static, OutputEvent>,
+        held: Option<Fop>,
+        done: bool,
+    }
+
+    let state = State {
+        template: fop,
+        events,
+        held: None,
+        done: false,
+    };
+    stream::unfold(state, |mut state| async move {
+        if state.done {
+            return None;
+        }
+        loop {
+            match state.events.next().await {
+                Some(OutputEvent::Data(data)) => {
+                    let mut next = state.template.clone();
+                    next.content = Some(Content::Text(
+                        String::from_utf8_lossy(&data).into_owned(),
+                    ));
+                    // Hold each Fop back until we know whether it is the last
+                    if let Some(previous) = state.held.replace(next) {
+                        return Some((previous, state));
+                    }
+                }
+                Some(OutputEvent::Exit { execution, err }) => {
+                    state.done = true;
+                    let mut last = state
+                        .held
+                        .take()
+                        .unwrap_or_else(|| state.template.clone());
+                    last.execution = execution;
+                    last.err = err;
+                    return Some((last, state));
+                }
+                None => {
+                    state.done = true;
+                    return state.held.take().map(|last| (last, state));
+                }
+            }
+        }
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fop::ProcessorError;
+
+    fn sink(mode: OutputMode) -> (OutputSink, mpsc::Receiver<OutputEvent>) {
+        let (tx, rx) = mpsc::channel(16);
+        (OutputSink::new(tx, mode), rx)
+    }
+
+    fn data(event: Option<OutputEvent>) -> Vec<u8> {
+        match event {
+            Some(OutputEvent::Data(data)) => data,
+            other => panic!("expected data, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sink_lines() {
+        let (mut sink, mut rx) = sink(OutputMode::Lines);
+
+        sink.send(b"one\ntw").await;
+        sink.send(b"o\nthree").await;
+        sink.finish().await;
+        drop(sink);
+
+        assert_eq!(data(rx.recv().await), b"one");
+        assert_eq!(data(rx.recv().await), b"two");
+        assert_eq!(data(rx.recv().await), b"three");
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sink_chunks_and_closed_receiver() {
+        let (mut sink, mut rx) = sink(OutputMode::Chunks);
+        sink.send(b"a\nb").await;
+        assert_eq!(data(rx.recv().await), b"a\nb");
+
+        drop(rx);
+        sink.send(b"discarded").await;
+        assert!(sink.closed);
+    }
+
+    #[tokio::test]
+    async fn test_split_output() {
+        let (tx, rx) = mpsc::channel(16);
+        let task = tokio::spawn(async {});
+        let mut fop = Fop::new("gen.sh");
+        fop.output = Some(OutputStream::new(rx, OutputMode::Lines, task.abort_handle()));
+
+        tx.send(OutputEvent::Data(b"one".to_vec())).await.unwrap();
+        tx.send(OutputEvent::Data(b"two".to_vec())).await.unwrap();
+        tx.send(OutputEvent::Exit {
+            execution: None,
+            err: Some(ProcessorError::new("DoExecuteProcessor", "failed")),
+        })
+        .await
+        .unwrap();
+
+        let fops: Vec<Fop> = split_output(fop).collect().await;
+
+        assert_eq!(fops.len(), 2);
+        assert_eq!(fops[0].content.as_ref().unwrap().as_bytes(), b"one");
+        assert!(fops[0].err.is_none());
+        assert_eq!(fops[1].content.as_ref().unwrap().as_bytes(), b"two");
+        assert!(fops[1].err.is_some());
+        assert!(fops.iter().all(|f| f.output.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_split_output_passthrough() {
+        let fops: Vec<Fop> = split_output(Fop::new("plain")).collect().await;
+        assert_eq!(fops.len(), 1);
+    }
+}
@@ -0,0 +1,869 @@
+//! Running a built command to completion, with an optional deadline.
+//!
+//! On Unix the child is started as the leader of a new process group so that
+//! anything it spawns can be signalled together. A timed-out run gets SIGTERM
+//! sent to the whole group, then SIGKILL once the grace period passes. If the
+//! run is dropped before it finishes (e.g. the pipeline stream is cancelled),
+//! the group is killed rather than left orphaned.
+
+pub use crate::execution::Execution;
+
+use super::output::OutputSink;
+#[cfg(not(unix))]
+use std::io::Read;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, ChildStdin};
+
+/// How stdout and stderr are captured.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CaptureMode {
+    /// Capture stdout and stderr separately
+    #[default]
+    Separate,
+    /// Send both into one pipe, preserving their interleaving, captured as stdout
+    Combined,
+}
+
+/// Data written to the child's stdin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StdinData {
+    /// Fixed bytes
+    Bytes(Vec<u8>),
+    /// Contents of a file, streamed without loading it whole
+    File(PathBuf),
+}
+
+/// A single execution to perform.
+#[derive(Debug)]
+pub struct Invocation {
+    /// Fully configured command: program, arguments, environment and cwd
+    pub command: Command,
+    /// How stdout and stderr are captured
+    pub capture: CaptureMode,
+    /// Deadline, if the processor has a timeout
+    pub deadline: Option<Deadline>,
+    /// Data to feed to stdin; stdin is closed when None
+    pub stdin: Option<StdinData>,
+    /// Where to stream stdout instead of capturing it
+    pub output: Option<OutputSink>,
+    /// Bytes of output allowed before the process group is killed
+    pub max_output: Option<u64>,
+    /// Short name for the file being executed, for display: its file name
+    pub label: String,
+}
+
+/// Deadline settings for a single execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadline {
+    /// How long the execution may run before it is terminated
+    pub timeout: Duration,
+    /// How long to wait after SIGTERM before sending SIGKILL
+    pub grace: Duration,
+}
+
+/// Why a run did not produce output.
+#[derive(Debug)]
+pub enum RunError {
+    /// The process could not be started, or its output could not be read
+    Io(io::Error),
+    /// The deadline passed and the process group was terminated
+    TimedOut(Duration),
+    /// More output than allowed was written and the process group was killed
+    OutputLimit(u64),
+}
+
+impl std::fmt::Display for RunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunError::Io(e) => write!(f, "{}", e),
+            RunError::TimedOut(after) => write!(f, "timed out after {:?}", after),
+            RunError::OutputLimit(limit) => write!(f, "wrote more than {} bytes", limit),
+        }
+    }
+}
+
+impl From<io::Error> for RunError {
+    fn from(e: io::Error) -> Self {
+        RunError::Io(e)
+    }
+}
+
+/// Where teed output lines are written.
+#[derive(Clone)]
+pub enum TeeTarget {
+    /// Child stdout to our stdout, child stderr to our stderr
+    Terminal,
+    /// Both streams to one shared writer
+    Writer(Arc<Mutex<dyn Write + Send>>),
+}
+
+impl std::fmt::Debug for TeeTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TeeTarget::Terminal => write!(f, "Terminal"),
+            TeeTarget::Writer(_) => write!(f, "Writer(..)"),
+        }
+    }
+}
+
+/// Copy child output, line by line with a prefix, while it is captured.
+#[derive(Debug, Clone)]
+pub struct Tee {
+    /// Written before every line, e.g. `[build.sh] `
+    pub prefix: String,
+    /// Where lines go
+    pub target: TeeTarget,
+}
+
+/// Line splitter writing one child stream through a Tee.
+struct LineTee {
+    tee: Tee,
+    stderr: bool,
+    pending: Vec<u8>,
+}
+
+impl LineTee {
+    fn new(tee: Option<&Tee>, stderr: bool) -> Option<Self> {
+        tee.map(|tee| Self {
+            tee: tee.clone(),
+            stderr,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Take the complete lines of a chunk, prefixed and ready to write.
+    fn feed(&mut self, chunk: &[u8]) -> Option<Vec<u8>> {
+        self.pending.extend_from_slice(chunk);
+        let end = self.pending.iter().rposition(|&b| b == b'\n')?;
+        let lines: Vec<u8> = self.pending.drain(..=end).collect();
+        Some(self.prefixed(&lines))
+    }
+
+    /// Take the last, unterminated line, if any.
+    fn finish(&mut self) -> Option<Vec<u8>> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let mut line = std::mem::take(&mut self.pending);
+        line.push(b'\n');
+        Some(self.prefixed(&line))
+    }
+
+    fn prefixed(&self, lines: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(lines.len() + self.tee.prefix.len());
+        for line in lines.split_inclusive(|&b| b == b'\n') {
+            out.extend_from_slice(self.tee.prefix.as_bytes());
+            out.extend_from_slice(line);
+        }
+        out
+    }
+
+    /// Write whole lines in one call so concurrent executions don't
+    /// interleave mid-line. This blocks.
+    fn write(target: &TeeTarget, stderr: bool, out: &[u8]) {
+        // Teeing is best effort; the output is still captured
+        let _ = match target {
+            TeeTarget::Terminal if stderr => io::stderr().lock().write_all(out),
+            TeeTarget::Terminal => io::stdout().lock().write_all(out),
+            TeeTarget::Writer(writer) => match writer.lock() {
+                Ok(mut writer) => writer.write_all(out),
+                Err(_) => Ok(()),
+            },
+        };
+    }
+
+    /// Write whole lines from async code, on the blocking thread pool.
+    async fn write_async(&self, out: Vec<u8>) {
+        let (target, stderr) = (self.tee.target.clone(), self.stderr);
+        let _ = tokio::task::spawn_blocking(move || Self::write(&target, stderr, &out)).await;
+    }
+}
+
+/// Run an invocation to completion, capturing its output as it says.
+///
+/// With a `tee`, output is also copied line by line to the tee's target as
+/// it arrives. With an `output` sink, stdout (or the combined stream) is sent
+/// there as it arrives instead of being captured. Stdin is fed concurrently
+/// with reading output, so a child that writes before it has read all its
+/// input can't deadlock; without stdin data it is closed, as with
+/// `Command::output`.
+pub async fn run(invocation: Invocation, tee: Option<&Tee>) -> Result<Execution, RunError> {
+    let Invocation {
+        mut command,
+        capture,
+        deadline,
+        stdin,
+        output,
+        max_output,
+        ..
+    } = invocation;
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    command.stdin(if stdin.is_some() {
+        Stdio::piped()
+    } else {
+        Stdio::null()
+    });
+    let combined = match capture {
+        CaptureMode::Separate => {
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+            None
+        }
+        CaptureMode::Combined => {
+            let (reader, writer) = io::pipe()?;
+            command.stdout(writer.try_clone()?).stderr(writer);
+            Some(reader)
+        }
+    };
+
+    let (stdout_sink, combined_sink) = match capture {
+        CaptureMode::Separate => (output, None),
+        CaptureMode::Combined => (None, output),
+    };
+
+    let mut command = tokio::process::Command::from(command);
+    command.kill_on_drop(true);
+    let start = Instant::now();
+    let budget = max_output.map(Budget::new);
+    let mut child = command.spawn()?;
+    let mut guard = GroupGuard::new(&child);
+    // Close our copies of the combined pipe's write end so reads see EOF
+    drop(command);
+    // Read the combined pipe asynchronously, so that a timeout or a dropped
+    // run stops the read even while an escaped descendant holds it open
+    #[cfg(unix)]
+    let combined = combined
+        .map(|pipe| tokio::net::unix::pipe::Receiver::from_owned_fd(pipe.into()))
+        .transpose()?;
+
+    let pipe = child.stdin.take();
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let collect = async {
+        let (fed, output) = tokio::join!(write_stdin(pipe, stdin), async {
+            tokio::try_join!(
+                child.wait(),
+                read_all(
+                    stdout,
+                    LineTee::new(tee, false),
+                    stdout_sink,
+                    budget.clone()
+                ),
+                read_all(stderr, LineTee::new(tee, true), None, budget.clone()),
+                read_combined(
+                    combined,
+                    LineTee::new(tee, false),
+                    combined_sink,
+                    budget.clone()
+                )
+            )
+        });
+        let (status, stdout, stderr, combined) = output?;
+        let stdout = combined.unwrap_or(stdout);
+        let mut execution = Execution::new(status, stdout, stderr, start.elapsed());
+        execution.stdin_error = fed.err().map(|e| e.to_string());
+        Ok::<_, io::Error>(execution)
+    };
+
+    let result = match deadline {
+        None => collect.await.map_err(RunError::from),
+        Some(deadline) => match tokio::time::timeout(deadline.timeout, collect).await {
+            Ok(output) => output.map_err(RunError::from),
+            Err(_) => {
+                terminate(&mut child, &guard, deadline.grace).await;
+                Err(RunError::TimedOut(deadline.timeout))
+            }
+        },
+    };
+    let result = match result {
+        Err(RunError::Io(e)) if Budget::exceeded(&e) => {
+            terminate(&mut child, &guard, Duration::ZERO).await;
+            Err(RunError::OutputLimit(max_output.unwrap_or_default()))
+        }
+        other => other,
+    };
+    guard.disarm();
+    result
+}
+
+/// Output allowance shared by the readers of one run.
+#[derive(Debug, Clone)]
+struct Budget {
+    limit: u64,
+    used: Arc<AtomicU64>,
+}
+
+/// Marker error stopping the readers once the budget is spent.
+#[derive(Debug)]
+struct OutputLimitExceeded;
+
+impl std::fmt::Display for OutputLimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "output limit exceeded")
+    }
+}
+
+impl std::error::Error for OutputLimitExceeded {}
+
+impl Budget {
+    fn new(limit: u64) -> Self {
+        Self {
+            limit,
+            used: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    fn spend(budget: &Option<Budget>, bytes: usize) -> io::Result<()> {
+        let Some(budget) = budget else {
+            return Ok(());
+        };
+        let used = budget.used.fetch_add(bytes as u64, Ordering::Relaxed) + bytes as u64;
+        if used > budget.limit {
+            return Err(io::Error::other(OutputLimitExceeded));
+        }
+        Ok(())
+    }
+
+    fn exceeded(e: &io::Error) -> bool {
+        e.get_ref().is_some_and(|e| e.is::<OutputLimitExceeded>())
+    }
+}
+
+/// Write stdin data and close the pipe. A child that exits or closes stdin
+/// early (broken pipe) is not treated as a failure.
+async fn write_stdin(pipe: Option<ChildStdin>, data: Option<StdinData>) -> io::Result<()> {
+    let (Some(mut pipe), Some(data)) = (pipe, data) else {
+        return Ok(());
+    };
+    let written = match data {
+        StdinData::Bytes(bytes) => pipe.write_all(&bytes).await,
+        StdinData::File(path) => match tokio::fs::File::open(&path).await {
+            Ok(mut file) => tokio::io::copy(&mut file, &mut pipe).await.map(|_| ()),
+            Err(e) => Err(io::Error::new(
+                e.kind(),
+                format!("failed to open {}: {}", path.display(), e),
+            )),
+        },
+    };
+    let written = written.and(pipe.shutdown().await);
+    match written {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        other => other,
+    }
+}
+
+async fn read_all(
+    pipe: Option<impl AsyncRead + Unpin>,
+    mut tee: Option<LineTee>,
+    mut sink: Option<OutputSink>,
+    budget: Option<Budget>,
+) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    let Some(mut pipe) = pipe else {
+        return Ok(buffer);
+    };
+    let mut chunk = [0u8; 8192];
+    loop {
+        let n = pipe.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        Budget::spend(&budget, n)?;
+        if let Some(tee) = &mut tee {
+            if let Some(out) = tee.feed(&chunk[..n]) {
+                tee.write_async(out).await;
+            }
+        }
+        match &mut sink {
+            Some(sink) => sink.send(&chunk[..n]).await,
+            None => buffer.extend_from_slice(&chunk[..n]),
+        }
+    }
+    if let Some(tee) = &mut tee {
+        if let Some(out) = tee.finish() {
+            tee.write_async(out).await;
+        }
+    }
+    if let Some(sink) = &mut sink {
+        sink.finish().await;
+    }
+    Ok(buffer)
+}
+
+/// Read the combined pipe to its end, if there is one.
+#[cfg(unix)]
+async fn read_combined(
+    pipe: Option<tokio::net::unix::pipe::Receiver>,
+    tee: Option<LineTee>,
+    sink: Option<OutputSink>,
+    budget: Option<Budget>,
+) -> io::Result<Option<Vec<u8>>> {
+    match pipe {
+        Some(pipe) => read_all(Some(pipe), tee, sink, budget).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Read the combined pipe to its end, if there is one, on the blocking
+/// thread pool. The read can't be interrupted, so it lasts as long as some
+/// process holds the pipe open, whatever the deadline.
+#[cfg(not(unix))]
+async fn read_combined(
+    pipe: Option<io::PipeReader>,
+    mut tee: Option<LineTee>,
+    mut sink: Option<OutputSink>,
+    budget: Option<Budget>,
+) -> io::Result<Option<Vec<u8>>> {
+    let Some(mut pipe) = pipe else {
+        return Ok(None);
+    };
+    tokio::task::spawn_blocking(move || {
+        let mut buffer = Vec::new();
+        let mut chunk = [0u8; 8192];
+        loop {
+            let n = pipe.read(&mut chunk)?;
+            if n == 0 {
+                break;
+            }
+            Budget::spend(&budget, n)?;
+            if let Some(tee) = &mut tee {
+                if let Some(out) = tee.feed(&chunk[..n]) {
+                    LineTee::write(&tee.tee.target, tee.stderr, &out);
+                }
+            }
+            match &mut sink {
+                Some(sink) => sink.blocking_send(&chunk[..n]),
+                None => buffer.extend_from_slice(&chunk[..n]),
+            }
+        }
+        if let Some(tee) = &mut tee {
+            if let Some(out) = tee.finish() {
+                LineTee::write(&tee.tee.target, tee.stderr, &out);
+            }
+        }
+        if let Some(sink) = &mut sink {
+            sink.blocking_finish();
+        }
+        Ok(Some(buffer))
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+/// Terminate a timed-out child: SIGTERM to its group, SIGKILL after `grace`.
+///
+/// The group gets SIGKILL whether or not the leader exited on SIGTERM, as
+/// others in the group may have ignored it. Where the leader can be waited
+/// for without reaping it, that happens before it is reaped, while its pid
+/// still reserves the group id.
+async fn terminate(child: &mut Child, guard: &GroupGuard, grace: Duration) {
+    guard.signal(Signal::Term);
+    if !exited(child, grace).await {
+        let _ = child.start_kill();
+    }
+    guard.signal(Signal::Kill);
+    let _ = child.wait().await;
+}
+
+/// Wait up to `limit` for the child to exit, leaving it unreaped.
+#[cfg(target_os = "linux")]
+async fn exited(child: &mut Child, limit: Duration) -> bool {
+    // Already reaped
+    let Some(pid) = child.id() else {
+        return true;
+    };
+    let deadline = Instant::now() + limit;
+    loop {
+        if has_exited(pid) {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+/// Whether the process `pid` has exited, leaving it unreaped.
+#[cfg(target_os = "linux")]
+fn has_exited(pid: u32) -> bool {
+    // SAFETY: siginfo_t is plain data, and waitid only writes to it.
+    // WNOWAIT leaves the child waitable for `Child::wait` to reap.
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let status = unsafe {
+        libc::waitid(
+            libc::P_PID,
+            pid,
+            &mut info,
+            libc::WEXITED | libc::WNOHANG | libc::WNOWAIT,
+        )
+    };
+    // SAFETY: si_pid is set by waitid, and left zero if nothing exited
+    status != 0 || unsafe { info.si_pid() } != 0
+}
+
+/// Wait up to `limit` for the child to exit, reaping it.
+#[cfg(not(target_os = "linux"))]
+async fn exited(child: &mut Child, limit: Duration) -> bool {
+    tokio::time::timeout(limit, child.wait()).await.is_ok()
+}
+
+enum Signal {
+    Term,
+    Kill,
+}
+
+/// Kills the child's process group on drop unless disarmed.
+///
+/// Disarmed once the run is over. A group id stays reserved while any
+/// member of the group lives, so signalling it after the leader was reaped
+/// reaches what is left of the group; only once every member is gone could
+/// the id have been reused. `terminate` signals before reaping the leader
+/// where it can.
+struct GroupGuard {
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pgid: Option<u32>,
+}
+
+impl GroupGuard {
+    fn new(child: &Child) -> Self {
+        Self { pgid: child.id() }
+    }
+
+    fn disarm(&mut self) {
+        self.pgid = None;
+    }
+
+    #[cfg(unix)]
+    fn signal(&self, signal: Signal) {
+        let signal = match signal {
+            Signal::Term => libc::SIGTERM,
+            Signal::Kill => libc::SIGKILL,
+        };
+        if let Some(pgid) = self.pgid {
+            // SAFETY: killpg has no memory safety requirements
+            unsafe {
+                libc::killpg(pgid as libc::pid_t, signal);
+            }
+        }
+    }
+
+    /// Without process groups, `kill_on_drop` and `start_kill` cover the child.
+    #[cfg(not(unix))]
+    fn signal(&self, _signal: Signal) {}
+}
+
+impl Drop for GroupGuard {
+    fn drop(&mut self) {
+        self.signal(Signal::Kill);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn sh(script: &str, capture: CaptureMode, deadline: Option<Deadline>) -> Invocation {
+        let mut command = Command::new("/bin/sh");
+        command.arg("-c").arg(script);
+        Invocation {
+            command,
+            capture,
+            deadline,
+            stdin: None,
+            output: None,
+            max_output: None,
+            label: "sh".to_string(),
+        }
+    }
+
+    fn deadline(timeout_ms: u64, grace_ms: u64) -> Option<Deadline> {
+        Some(Deadline {
+            timeout: Duration::from_millis(timeout_ms),
+            grace: Duration::from_millis(grace_ms),
+        })
+    }
+
+    /// Whether a process is gone (or only a zombie awaiting its reaper).
+    fn is_gone(pid: i32) -> bool {
+        match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            Err(_) => true,
+            Ok(stat) => stat
+                .rsplit(')')
+                .next()
+                .is_some_and(|rest| rest.trim_start().starts_with('Z')),
+        }
+    }
+
+    async fn wait_gone(pid: i32) -> bool {
+        for _ in 0..100 {
+            if is_gone(pid) {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        false
+    }
+
+    #[tokio::test]
+    async fn test_run_captures_output() {
+        let execution = run(
+            sh(
+                "echo out; echo err >&2",
+                CaptureMode::Separate,
+                deadline(5000, 100),
+            ),
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(execution.success());
+        assert_eq!(execution.code, Some(0));
+        assert_eq!(execution.signal, None);
+        assert_eq!(execution.stdout, b"out\n");
+        assert_eq!(execution.stderr, b"err\n");
+        assert!(execution.duration > Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_run_combined_capture() {
+        let execution = run(
+            sh(
+                "echo one; echo two >&2; echo three; exit 3",
+                CaptureMode::Combined,
+                None,
+            ),
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(!execution.success());
+        assert_eq!(execution.code, Some(3));
+        assert_eq!(execution.to_string(), "exit code 3");
+        assert_eq!(execution.stdout, b"one\ntwo\nthree\n");
+        assert!(execution.stderr.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_run_signal() {
+        let execution = run(sh("kill -9 $$", CaptureMode::Separate, None), None)
+            .await
+            .unwrap();
+
+        assert_eq!(execution.code, None);
+        assert_eq!(execution.signal, Some(9));
+        assert_eq!(execution.to_string(), "signal 9");
+    }
+
+    #[tokio::test]
+    async fn test_run_timeout_terminates() {
+        let start = Instant::now();
+
+        let err = run(
+            sh("sleep 10", CaptureMode::Separate, deadline(100, 2000)),
+            None,
+        )
+        .await
+        .unwrap_err();
+
+        assert!(matches!(err, RunError::TimedOut(t) if t == Duration::from_millis(100)));
+        assert!(start.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn test_run_timeout_escalates_to_kill() {
+        let start = Instant::now();
+
+        // Ignored signals are inherited, so nothing in the group obeys SIGTERM
+        let err = run(
+            sh(
+                "trap '' TERM; while :; do sleep 0.05; done",
+                CaptureMode::Separate,
+                deadline(100, 200),
+            ),
+            None,
+        )
+        .await
+        .unwrap_err();
+
+        assert!(matches!(err, RunError::TimedOut(_)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_run_timeout_kills_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let pidfile = dir.path().join("pid");
+
+        let script = format!("sleep 30 & echo $! > {}; wait", pidfile.display());
+        let err = run(sh(&script, CaptureMode::Combined, deadline(300, 100)), None)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, RunError::TimedOut(_)));
+        let pid: i32 = std::fs::read_to_string(&pidfile)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        assert!(wait_gone(pid).await);
+    }
+
+    #[test]
+    fn test_run_timeout_stops_combined_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let pidfile = dir.path().join("pid");
+        let start = Instant::now();
+
+        // A descendant in its own session outlives the group kill, holding
+        // the combined pipe open; the run still ends, its read included
+        let script = format!("setsid sleep 5 & echo $! > {}; echo hi", pidfile.display());
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let err = runtime
+            .block_on(run(
+                sh(&script, CaptureMode::Combined, deadline(300, 100)),
+                None,
+            ))
+            .unwrap_err();
+        drop(runtime);
+
+        assert!(matches!(err, RunError::TimedOut(_)));
+        assert!(start.elapsed() < Duration::from_secs(3));
+        let pid: i32 = std::fs::read_to_string(&pidfile)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        // SAFETY: kill has no memory safety requirements
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dropped_run_kills_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let pidfile = dir.path().join("pid");
+
+        let script = format!("sleep 30 & echo $! > {}; wait", pidfile.display());
+        let cancelled = tokio::time::timeout(
+            Duration::from_millis(300),
+            run(sh(&script, CaptureMode::Separate, None), None),
+        )
+        .await
+        .is_err();
+
+        assert!(cancelled);
+        let pid: i32 = std::fs::read_to_string(&pidfile)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        assert!(wait_gone(pid).await);
+    }
+
+    #[tokio::test]
+    async fn test_run_tee() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let tee = Tee {
+            prefix: "[gen.sh] ".to_string(),
+            target: TeeTarget::Writer(written.clone()),
+        };
+
+        let execution = run(
+            sh("echo one; printf two", CaptureMode::Combined, None),
+            Some(&tee),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(execution.stdout, b"one\ntwo");
+        assert_eq!(
+            written.lock().unwrap().as_slice(),
+            b"[gen.sh] one\n[gen.sh] two\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_feeds_stdin() {
+        // Larger than a pipe buffer, so writing and reading must overlap
+        let input = vec![b'x'; 1 << 20];
+        let mut invocation = sh("cat", CaptureMode::Separate, deadline(5000, 100));
+        invocation.stdin = Some(StdinData::Bytes(input.clone()));
+
+        let execution = run(invocation, None).await.unwrap();
+
+        assert_eq!(execution.stdout, input);
+        assert_eq!(execution.stdin_error, None);
+    }
+
+    #[tokio::test]
+    async fn test_run_feeds_stdin_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.txt");
+        std::fs::write(&input, "from file\n").unwrap();
+        let mut invocation = sh("tr a-z A-Z", CaptureMode::Separate, None);
+        invocation.stdin = Some(StdinData::File(input));
+
+        let execution = run(invocation, None).await.unwrap();
+
+        assert_eq!(execution.stdout, b"FROM FILE\n");
+    }
+
+    #[tokio::test]
+    async fn test_run_stdin_errors() {
+        // Not reading stdin at all is fine
+        let mut invocation = sh("exit 0", CaptureMode::Separate, None);
+        invocation.stdin = Some(StdinData::Bytes(vec![b'x'; 1 << 20]));
+        let execution = run(invocation, None).await.unwrap();
+        assert_eq!(execution.stdin_error, None);
+
+        // A missing input file is reported without affecting the exit status
+        let mut invocation = sh("cat", CaptureMode::Separate, None);
+        invocation.stdin = Some(StdinData::File("/nonexistent/input".into()));
+        let execution = run(invocation, None).await.unwrap();
+        assert!(execution.success());
+        assert!(execution
+            .stdin_error
+            .unwrap()
+            .contains("failed to open /nonexistent/input"));
+    }
+
+    #[tokio::test]
+    async fn test_run_output_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let pidfile = dir.path().join("pid");
+        let script = format!("sleep 30 & echo $! > {}; yes", pidfile.display());
+        let mut invocation = sh(&script, CaptureMode::Combined, None);
+        invocation.max_output = Some(10_000);
+
+        let started = Instant::now();
+        let result = run(invocation, None).await;
+
+        assert!(matches!(result, Err(RunError::OutputLimit(10_000))));
+        assert!(started.elapsed() < Duration::from_secs(2));
+        let pid: i32 = std::fs::read_to_string(&pidfile)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        assert!(wait_gone(pid).await);
+
+        // Output within the limit is untouched
+        let mut invocation = sh("echo fits", CaptureMode::Separate, None);
+        invocation.max_output = Some(5);
+        assert_eq!(run(invocation, None).await.unwrap().stdout, b"fits\n");
+    }
+}
@@ -0,0 +1,525 @@
+//! Sandboxing for executed files: resource limits, a clean environment, a
+//! restricted working directory and namespace isolation.
+//!
+//! Limits and isolation are applied in the child between fork and exec, so a
+//! sandbox that cannot be applied fails the spawn instead of running the file
+//! without it. Isolation needs unprivileged user namespaces, which is probed
+//! once per process so an unsupported host gets a clear error up front.
+
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// `PATH` given to executions with a clean environment.
+pub const CLEAN_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+
+/// Restrictions applied to every execution.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sandbox {
+    cpu_seconds: Option<u64>,
+    address_space: Option<u64>,
+    open_files: Option<u64>,
+    max_output: Option<u64>,
+    clean_env: bool,
+    restricted_dir: Option<PathBuf>,
+    isolation: Option<Isolation>,
+}
+
+/// Namespace isolation, Linux only.
+///
+/// The child gets its own user and mount namespaces, and by default a network
+/// namespace with only a loopback interface.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Isolation {
+    network: bool,
+    read_only: Vec<PathBuf>,
+}
+
+impl Isolation {
+    /// Isolate without network access and with no read-only paths.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep the host network instead of an empty network namespace.
+    pub fn with_network(mut self) -> Self {
+        self.network = true;
+        self
+    }
+
+    /// Bind `path` read-only over itself. Mounts below it stay as they are.
+    pub fn with_read_only(mut self, path: impl Into<PathBuf>) -> Self {
+        self.read_only.push(path.into());
+        self
+    }
+}
+
+impl Sandbox {
+    /// A sandbox with no restrictions; add them with the builder methods.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit CPU time (`RLIMIT_CPU`); the child gets SIGXCPU, then SIGKILL.
+    pub fn with_cpu_seconds(mut self, seconds: u64) -> Self {
+        self.cpu_seconds = Some(seconds);
+        self
+    }
+
+    /// Limit virtual memory in bytes (`RLIMIT_AS`).
+    pub fn with_address_space(mut self, bytes: u64) -> Self {
+        self.address_space = Some(bytes);
+        self
+    }
+
+    /// Limit the number of open file descriptors (`RLIMIT_NOFILE`).
+    pub fn with_open_files(mut self, count: u64) -> Self {
+        self.open_files = Some(count);
+        self
+    }
+
+    /// Limit the bytes of stdout and stderr together. A run that writes
+    /// more has its process group killed and fails.
+    pub fn with_max_output(mut self, bytes: u64) -> Self {
+        self.max_output = Some(bytes);
+        self
+    }
+
+    /// Start from an empty environment with `PATH` set to `CLEAN_PATH`.
+    /// Variables set with `env` are kept.
+    pub fn with_clean_env(mut self) -> Self {
+        self.clean_env = true;
+        self
+    }
+
+    /// Only run inside `dir`: it becomes the working directory unless one
+    /// is configured, and a configured one outside it is an error.
+    pub fn with_restricted_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.restricted_dir = Some(dir.into());
+        self
+    }
+
+    /// Run in new namespaces, see `Isolation`.
+    pub fn with_isolation(mut self, isolation: Isolation) -> Self {
+        self.isolation = Some(isolation);
+        self
+    }
+
+    /// Output limit in bytes, enforced while reading the child's output.
+    pub fn max_output(&self) -> Option<u64> {
+        self.max_output
+    }
+
+    /// Apply the sandbox to a built command.
+    ///
+    /// Fails if any part of it is unsupported on this host or misconfigured.
+    pub fn apply(&self, command: &mut Command) -> Result<(), String> {
+        if self.clean_env {
+            let kept: Vec<(OsString, OsString)> = command
+                .get_envs()
+                .filter_map(|(k, v)| Some((k.to_os_string(), v?.to_os_string())))
+                .collect();
+            command.env_clear().env("PATH", CLEAN_PATH).envs(kept);
+        }
+
+        if let Some(dir) = &self.restricted_dir {
+            let root = dir
+                .canonicalize()
+                .map_err(|e| format!("restricted directory {}: {}", dir.display(), e))?;
+            match command.get_current_dir() {
+                None => {
+                    command.current_dir(&root);
+                }
+                Some(cwd) => {
+                    let resolved = cwd
+                        .canonicalize()
+                        .map_err(|e| format!("working directory {}: {}", cwd.display(), e))?;
+                    if !resolved.starts_with(&root) {
+                        return Err(format!(
+                            "working directory {} is outside {}",
+                            cwd.display(),
+                            root.display()
+                        ));
+                    }
+                }
+            }
+        }
+
+        self.apply_child(command)
+    }
+
+    #[cfg(unix)]
+    fn apply_child(&self, command: &mut Command) -> Result<(), String> {
+        use std::os::unix::process::CommandExt;
+
+        let limits = Limits {
+            cpu_seconds: self.cpu_seconds,
+            address_space: self.address_space,
+            open_files: self.open_files,
+        };
+        let namespaces = match &self.isolation {
+            Some(isolation) => Some(namespaces::Setup::new(isolation, command)?),
+            None => None,
+        };
+        if namespaces.is_none() && limits.is_empty() {
+            return Ok(());
+        }
+
+        // SAFETY: the hook only makes async-signal-safe system calls
+        unsafe {
+            command.pre_exec(move || {
+                if let Some(namespaces) = &namespaces {
+                    namespaces.enter()?;
+                }
+                limits.set()
+            });
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn apply_child(&self, _command: &mut Command) -> Result<(), String> {
+        let limited = self.cpu_seconds.is_some()
+            || self.address_space.is_some()
+            || self.open_files.is_some();
+        if limited || self.isolation.is_some() {
+            return Err("resource limits and isolation are only supported on Unix".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy)]
+struct Limits {
+    cpu_seconds: Option<u64>,
+    address_space: Option<u64>,
+    open_files: Option<u64>,
+}
+
+#[cfg(unix)]
+impl Limits {
+    fn is_empty(&self) -> bool {
+        self.cpu_seconds.is_none() && self.address_space.is_none() && self.open_files.is_none()
+    }
+
+    /// Set both soft and hard limits, so the child cannot raise them again.
+    fn set(&self) -> std::io::Result<()> {
+        fn limit(value: u64) -> libc::rlimit {
+            libc::rlimit {
+                rlim_cur: value as libc::rlim_t,
+                rlim_max: value as libc::rlim_t,
+            }
+        }
+        // SAFETY: setrlimit only reads the struct passed to it
+        unsafe {
+            if let Some(value) = self.cpu_seconds {
+                check(libc::setrlimit(libc::RLIMIT_CPU, &limit(value)))?;
+            }
+            if let Some(value) = self.address_space {
+                check(libc::setrlimit(libc::RLIMIT_AS, &limit(value)))?;
+            }
+            if let Some(value) = self.open_files {
+                check(libc::setrlimit(libc::RLIMIT_NOFILE, &limit(value)))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn check(result: libc::c_int) -> std::io::Result<()> {
+    if result == -1 {
+        Err(std::io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod namespaces {
+    use super::{check, Isolation};
+    use std::ffi::CString;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::process::Command;
+    use std::ptr;
+    use std::sync::OnceLock;
+
+    /// Everything the child needs to enter its namespaces, prepared in the
+    /// parent so the child doesn't allocate.
+    #[derive(Debug)]
+    pub(super) struct Setup {
+        network: bool,
+        uid_map: Vec<u8>,
+        gid_map: Vec<u8>,
+        read_only: Vec<CString>,
+        cwd: Option<CString>,
+    }
+
+    impl Setup {
+        pub(super) fn new(isolation: &Isolation, command: &Command) -> Result<Self, String> {
+            available()?;
+            let read_only = isolation
+                .read_only
+                .iter()
+                .map(|path| c_path("read-only path", path))
+                .collect::<Result<_, String>>()?;
+            let cwd = match command.get_current_dir() {
+                Some(cwd) => cwd.to_path_buf(),
+                None => std::env::current_dir()
+                    .map_err(|e| format!("working directory: {}", e))?,
+            };
+            Ok(Self {
+                network: isolation.network,
+                read_only,
+                cwd: Some(c_path("working directory", &cwd)?),
+                ..Self::bare()
+            })
+        }
+
+        fn bare() -> Self {
+            // SAFETY: getuid and getgid cannot fail
+            let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+            Self {
+                network: true,
+                uid_map: format!("{} {} 1", uid, uid).into_bytes(),
+                gid_map: format!("{} {} 1", gid, gid).into_bytes(),
+                read_only: Vec::new(),
+                cwd: None,
+            }
+        }
+
+        /// Enter the namespaces and set up mounts. Runs in the child.
+        pub(super) fn enter(&self) -> io::Result<()> {
+            let mut flags = libc::CLONE_NEWUSER | libc::CLONE_NEWNS;
+            if !self.network {
+                flags |= libc::CLONE_NEWNET;
+            }
+            // SAFETY: plain system calls on NUL-terminated strings we own
+            unsafe {
+                check(libc::unshare(flags))?;
+                write(c"/proc/self/setgroups", b"deny")?;
+                write(c"/proc/self/uid_map", &self.uid_map)?;
+                write(c"/proc/self/gid_map", &self.gid_map)?;
+                // Keep our mounts from propagating back to the host
+                check(libc::mount(
+                    ptr::null(),
+                    c"/".as_ptr(),
+                    ptr::null(),
+                    libc::MS_REC | libc::MS_PRIVATE,
+                    ptr::null(),
+                ))?;
+                for path in &self.read_only {
+                    bind_read_only(path)?;
+                }
+                // The working directory was entered before the new mounts
+                // existed; enter it again to see them
+                if let (Some(cwd), false) = (&self.cwd, self.read_only.is_empty()) {
+                    check(libc::chdir(cwd.as_ptr()))?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn c_path(what: &str, path: &std::path::Path) -> Result<CString, String> {
+        let path = path
+            .canonicalize()
+            .map_err(|e| format!("{} {}: {}", what, path.display(), e))?;
+        CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| format!("{} {} contains NUL", what, path.display()))
+    }
+
+    /// Whether this host lets us create user namespaces, checked once.
+    fn available() -> Result<(), String> {
+        static AVAILABLE: OnceLock<Result<(), String>> = OnceLock::new();
+        AVAILABLE
+            .get_or_init(|| {
+                probe().map_err(|e| format!("user namespaces are unavailable: {}", e))
+            })
+            .clone()
+    }
+
+    /// Try entering namespaces in a forked child that exits straight away.
+    fn probe() -> io::Result<()> {
+        let setup = Setup::bare();
+        // SAFETY: the child only makes async-signal-safe calls before _exit
+        unsafe {
+            match libc::fork() {
+                -1 => Err(io::Error::last_os_error()),
+                0 => {
+                    let code = match setup.enter() {
+                        Ok(()) => 0,
+                        Err(e) => e.raw_os_error().unwrap_or(libc::EINVAL),
+                    };
+                    libc::_exit(code)
+                }
+                pid => {
+                    let mut status = 0;
+                    while libc::waitpid(pid, &mut status, 0) == -1 {
+                        let e = io::Error::last_os_error();
+                        if e.kind() != io::ErrorKind::Interrupted {
+                            return Err(e);
+                        }
+                    }
+                    match libc::WIFEXITED(status).then(|| libc::WEXITSTATUS(status)) {
+                        Some(0) => Ok(()),
+                        Some(errno) => Err(io::Error::from_raw_os_error(errno)),
+                        None => Err(io::Error::other("probe process was killed")),
+                    }
+                }
+            }
+        }
+    }
+
+    unsafe fn write(path: &std::ffi::CStr, data: &[u8]) -> io::Result<()> {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let written = libc::write(fd, data.as_ptr().cast(), data.len());
+        libc::close(fd);
+        if written == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Bind `path` over itself, then remount the bind read-only. Flags the
+    /// namespace may not drop, such as nosuid, are carried over from the
+    /// existing mount or the remount is refused.
+    unsafe fn bind_read_only(path: &CString) -> io::Result<()> {
+        let flags = libc::MS_BIND | libc::MS_REC;
+        check(libc::mount(path.as_ptr(), path.as_ptr(), ptr::null(), flags, ptr::null()))?;
+
+        let mut stat: libc::statvfs = std::mem::zeroed();
+        check(libc::statvfs(path.as_ptr(), &mut stat))?;
+        let mut flags = libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY;
+        for (kept, flag) in [
+            (libc::ST_NOSUID, libc::MS_NOSUID),
+            (libc::ST_NODEV, libc::MS_NODEV),
+            (libc::ST_NOEXEC, libc::MS_NOEXEC),
+            (libc::ST_NOATIME, libc::MS_NOATIME),
+            (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+            (libc::ST_RELATIME, libc::MS_RELATIME),
+        ] {
+            if stat.f_flag & kept != 0 {
+                flags |= flag;
+            }
+        }
+        check(libc::mount(ptr::null(), path.as_ptr(), ptr::null(), flags, ptr::null()))
+    }
+
+    #[cfg(test)]
+    pub(super) fn supported() -> bool {
+        available().is_ok()
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+mod namespaces {
+    use super::Isolation;
+    use std::process::Command;
+
+    #[derive(Debug)]
+    pub(super) struct Setup;
+
+    impl Setup {
+        pub(super) fn new(_isolation: &Isolation, _command: &Command) -> Result<Self, String> {
+            Err("namespace isolation is only supported on Linux".to_string())
+        }
+
+        pub(super) fn enter(&self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_env_keeps_explicit_vars() {
+        let mut command = Command::new("env");
+        command.env("KEEP", "1").env("PATH", "/opt/bin");
+
+        Sandbox::new().with_clean_env().apply(&mut command).unwrap();
+
+        let envs: Vec<_> = command.get_envs().collect();
+        assert!(envs.contains(&("KEEP".as_ref(), Some("1".as_ref()))));
+        assert!(envs.contains(&("PATH".as_ref(), Some("/opt/bin".as_ref()))));
+
+        let mut command = Command::new("env");
+        Sandbox::new().with_clean_env().apply(&mut command).unwrap();
+        let envs: Vec<_> = command.get_envs().collect();
+        assert_eq!(envs, vec![("PATH".as_ref(), Some(CLEAN_PATH.as_ref()))]);
+    }
+
+    #[test]
+    fn test_restricted_dir() {
+        let root = tempfile::tempdir().unwrap();
+        let inner = root.path().join("inner");
+        std::fs::create_dir(&inner).unwrap();
+        let sandbox = Sandbox::new().with_restricted_dir(root.path());
+
+        let mut command = Command::new("pwd");
+        sandbox.apply(&mut command).unwrap();
+        assert_eq!(
+            command.get_current_dir(),
+            Some(root.path().canonicalize().unwrap().as_path())
+        );
+
+        let mut command = Command::new("pwd");
+        command.current_dir(&inner);
+        assert!(sandbox.apply(&mut command).is_ok());
+
+        let mut command = Command::new("pwd");
+        command.current_dir(inner.join(".."));
+        assert!(sandbox.apply(&mut command).is_ok());
+        command.current_dir(root.path().join(".."));
+        let err = sandbox.apply(&mut command).unwrap_err();
+        assert!(err.contains("is outside"), "{}", err);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resource_limits() {
+        let mut command = Command::new("/bin/sh");
+        command.arg("-c").arg("ulimit -t; ulimit -n; ulimit -v");
+
+        Sandbox::new()
+            .with_cpu_seconds(7)
+            .with_open_files(32)
+            .with_address_space(1 << 30)
+            .apply(&mut command)
+            .unwrap();
+        let output = command.output().unwrap();
+
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "7\n32\n1048576\n");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_isolation() {
+        if !namespaces::supported() {
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let script = "touch x; echo touch=$?; tail -n +3 /proc/net/dev | wc -l";
+        let mut command = Command::new("/bin/sh");
+        command.arg("-c").arg(script).current_dir(dir.path());
+
+        Sandbox::new()
+            .with_isolation(Isolation::new().with_read_only(dir.path()))
+            .apply(&mut command)
+            .unwrap();
+        let output = command.output().unwrap();
+
+        // Read-only, and loopback is the only interface
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(stdout.split_whitespace().collect::<Vec<_>>(), ["touch=1", "1"]);
+        assert!(!dir.path().join("x").exists());
+    }
+}
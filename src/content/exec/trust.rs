@@ -0,0 +1,220 @@
+//! Trust policy deciding which matched files may be executed at all.
+
+pub use crate::execution::Denial;
+
+use crate::content::hash::HashAlgorithm;
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Rules a file must pass before DoExecuteProcessor runs it.
+///
+/// Every configured rule must pass; a policy with no rules allows everything.
+/// Paths are checked after resolving symlinks, and DoExecuteProcessor runs
+/// the resolved path it checked, so re-pointing a symlink after the check
+/// does not change what runs. Replacing the resolved file itself still
+/// races the check; keep trusted roots writable only by trusted users.
+#[derive(Debug, Clone, Default)]
+pub struct TrustPolicy {
+    roots: Vec<PathBuf>,
+    safe_owner: bool,
+    allowlist: Option<Arc<HashSet<String>>>,
+}
+
+impl TrustPolicy {
+    /// A policy with no rules; add them with the builder methods.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only allow files under `root`. May be given several times.
+    pub fn with_trusted_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.roots.push(root.into());
+        self
+    }
+
+    /// Only allow files owned by the current user or root, and not writable
+    /// by group or others. Unix only; elsewhere every file is denied.
+    pub fn with_safe_owner(mut self) -> Self {
+        self.safe_owner = true;
+        self
+    }
+
+    /// Only allow files whose SHA-256, in hex, is one of `hashes`.
+    pub fn with_allowlist(mut self, hashes: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        let hashes = hashes
+            .into_iter()
+            .map(|h| h.as_ref().to_ascii_lowercase())
+            .collect();
+        self.allowlist = Some(Arc::new(hashes));
+        self
+    }
+
+    /// Load the allowlist from a file in `sha256sum` format: one hex digest
+    /// per line, optionally followed by a file name. Blank lines and lines
+    /// starting with `#` are skipped.
+    pub fn with_allowlist_file(self, path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let mut hashes = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let hash = line.split_whitespace().next().unwrap_or_default();
+            if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}:{}: not a sha256 digest", path.display(), index + 1),
+                ));
+            }
+            hashes.push(hash);
+        }
+        Ok(self.with_allowlist(hashes))
+    }
+
+    /// Check `path` against every rule and return the symlink-free path
+    /// that was checked; run that rather than `path`. Blocking; hashes the
+    /// file if an allowlist is configured.
+    pub fn check(&self, path: &Path) -> Result<PathBuf, Denial> {
+        let unverifiable = |e: io::Error| Denial::Unverifiable(e.to_string());
+        let resolved = path.canonicalize().map_err(unverifiable)?;
+
+        if !self.roots.is_empty() {
+            let trusted = self.roots.iter().any(|root| {
+                root.canonicalize()
+                    .is_ok_and(|root| resolved.starts_with(root))
+            });
+            if !trusted {
+                return Err(Denial::OutsideTrustedRoots);
+            }
+        }
+
+        if self.safe_owner {
+            let metadata = std::fs::metadata(&resolved).map_err(unverifiable)?;
+            Self::check_owner(&metadata)?;
+        }
+
+        if let Some(allowlist) = &self.allowlist {
+            let sha256 = HashAlgorithm::Sha256
+                .digest_file(&resolved)
+                .map_err(unverifiable)?
+                .to_hex();
+            if !allowlist.contains(&sha256) {
+                return Err(Denial::NotAllowlisted { sha256 });
+            }
+        }
+        Ok(resolved)
+    }
+
+    #[cfg(unix)]
+    fn check_owner(metadata: &std::fs::Metadata) -> Result<(), Denial> {
+        use std::os::unix::fs::MetadataExt;
+
+        // SAFETY: geteuid cannot fail
+        let us = unsafe { libc::geteuid() };
+        let uid = metadata.uid();
+        if uid != us && uid != 0 {
+            return Err(Denial::UntrustedOwner { uid });
+        }
+        let mode = metadata.mode();
+        if mode & 0o022 != 0 {
+            return Err(Denial::Writable { mode });
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn check_owner(_metadata: &std::fs::Metadata) -> Result<(), Denial> {
+        Err(Denial::Unverifiable(
+            "ownership checks are only supported on Unix".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn test_trusted_roots() {
+        let trusted = tempfile::tempdir().unwrap();
+        let other = tempfile::tempdir().unwrap();
+        let inside = trusted.path().join("gen.sh");
+        let outside = other.path().join("gen.sh");
+        std::fs::write(&inside, "hello").unwrap();
+        std::fs::write(&outside, "hello").unwrap();
+        let policy = TrustPolicy::new().with_trusted_root(trusted.path());
+
+        assert_eq!(policy.check(&inside), Ok(inside.canonicalize().unwrap()));
+        assert_eq!(policy.check(&outside), Err(Denial::OutsideTrustedRoots));
+        assert!(matches!(
+            policy.check(&trusted.path().join("missing")),
+            Err(Denial::Unverifiable(_))
+        ));
+
+        // A symlink inside a root is judged by its target
+        #[cfg(unix)]
+        {
+            let link = trusted.path().join("link.sh");
+            std::os::unix::fs::symlink(&outside, &link).unwrap();
+            assert_eq!(policy.check(&link), Err(Denial::OutsideTrustedRoots));
+
+            let inner = trusted.path().join("inner.sh");
+            std::os::unix::fs::symlink(&inside, &inner).unwrap();
+            assert_eq!(policy.check(&inner), Ok(inside.canonicalize().unwrap()));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_safe_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gen.sh");
+        std::fs::write(&path, "hello").unwrap();
+        let policy = TrustPolicy::new().with_safe_owner();
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(policy.check(&path).is_ok());
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o775)).unwrap();
+        let denial = policy.check(&path).unwrap_err();
+        assert_eq!(denial, Denial::Writable { mode: 0o100775 });
+        assert_eq!(
+            denial.to_string(),
+            "writable by group or others (mode 775)"
+        );
+    }
+
+    #[test]
+    fn test_allowlist_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("gen.sh");
+        std::fs::write(&path, "hello").unwrap();
+        let list = dir.path().join("allowlist");
+        std::fs::write(
+            &list,
+            format!("# reviewed scripts\n\n{}  gen.sh\n", HELLO_SHA256.to_uppercase()),
+        )
+        .unwrap();
+
+        let policy = TrustPolicy::new().with_allowlist_file(&list).unwrap();
+        assert!(policy.check(&path).is_ok());
+
+        std::fs::write(&path, "hello!").unwrap();
+        assert!(matches!(
+            policy.check(&path),
+            Err(Denial::NotAllowlisted { sha256 }) if sha256 != HELLO_SHA256
+        ));
+
+        std::fs::write(&list, "abc123 gen.sh\n").unwrap();
+        let err = TrustPolicy::new().with_allowlist_file(&list).unwrap_err();
+        assert!(err.to_string().ends_with("allowlist:1: not a sha256 digest"));
+    }
+}
@@ -0,0 +1,565 @@
+//! HashProcessor implementation.
+
+use crate::archive::ArchiveReader;
+use crate::fop::{ContentRange, Fop, ProcessorError};
+use crate::processor::AsyncProcessor;
+use sha2::Digest as _;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Size of the buffer used when streaming files into the hashers.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Digest algorithms supported by HashProcessor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HashAlgorithm {
+    /// SHA-256, for keys shared with other tools
+    Sha256,
+    /// BLAKE3, fast cryptographic hash
+    Blake3,
+    /// XXH3 64-bit, fast non-cryptographic hash
+    Xxh3,
+}
+
+impl HashAlgorithm {
+    /// Short lowercase name of the algorithm.
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Xxh3 => "xxh3",
+        }
+    }
+
+    /// Hash a whole file, streaming it from disk.
+    ///
+    /// This is blocking I/O; async callers should run it via `spawn_blocking`.
+    pub(crate) fn digest_file(&self, path: &Path) -> io::Result<Digest> {
+        let mut hashers = [self.hasher()];
+        hash_all(File::open(path)?, &mut hashers)?;
+        let [hasher] = hashers;
+        Ok(hasher.finish(*self))
+    }
+
+    fn hasher(&self) -> Hasher {
+        match self {
+            HashAlgorithm::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+            HashAlgorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+            HashAlgorithm::Xxh3 => Hasher::Xxh3(Box::new(xxhash_rust::xxh3::Xxh3::new())),
+        }
+    }
+}
+
+impl std::fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A computed digest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Digest {
+    /// Algorithm that produced the digest
+    pub algorithm: HashAlgorithm,
+    /// Raw digest bytes
+    pub bytes: Vec<u8>,
+}
+
+impl Digest {
+    /// Lowercase hex encoding of the digest.
+    pub fn to_hex(&self) -> String {
+        self.bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+impl std::fmt::Display for Digest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.algorithm, self.to_hex())
+    }
+}
+
+/// Incremental hasher state for one algorithm.
+enum Hasher {
+    Sha256(sha2::Sha256),
+    Blake3(Box<blake3::Hasher>),
+    Xxh3(Box<xxhash_rust::xxh3::Xxh3>),
+}
+
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Blake3(h) => {
+                h.update(data);
+            }
+            Hasher::Xxh3(h) => h.update(data),
+        }
+    }
+
+    fn finish(self, algorithm: HashAlgorithm) -> Digest {
+        let bytes = match self {
+            Hasher::Sha256(h) => h.finalize().to_vec(),
+            Hasher::Blake3(h) => h.finalize().as_bytes().to_vec(),
+            Hasher::Xxh3(h) => h.digest().to_be_bytes().to_vec(),
+        };
+        Digest { algorithm, bytes }
+    }
+}
+
+/// Where HashProcessor takes the bytes to hash from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashSource {
+    /// Hash `content` if present, otherwise stream the file
+    #[default]
+    Auto,
+    /// Hash `content`; Fops without content get an error
+    Content,
+    /// Stream the file from disk without keeping its content. Only the
+    /// Fop's `range` is hashed when it has one, as it would be read.
+    File,
+}
+
+/// Processor for computing content digests.
+///
+/// Records one digest per configured algorithm in `fop.digests`. Fops that
+/// already carry an error pass through untouched.
+///
+/// # Example
+///
+/// ```ignore
+/// let processor = HashProcessor::new()
+///     .with_algorithms([HashAlgorithm::Sha256, HashAlgorithm::Blake3])
+///     .with_source(HashSource::File);
+/// ```
+pub struct HashProcessor {
+    algorithms: Vec<HashAlgorithm>,
+    source: HashSource,
+    archives: ArchiveReader,
+}
+
+impl HashProcessor {
+    /// Create a new HashProcessor computing SHA-256 with `HashSource::Auto`.
+    pub fn new() -> Self {
+        Self {
+            algorithms: vec![HashAlgorithm::Sha256],
+            source: HashSource::Auto,
+            archives: ArchiveReader::new(),
+        }
+    }
+
+    /// Set the algorithms to compute, replacing the default.
+    pub fn with_algorithms(mut self, algorithms: impl IntoIterator<Item = HashAlgorithm>) -> Self {
+        self.algorithms = algorithms.into_iter().collect();
+        self
+    }
+
+    /// Set where the bytes to hash come from.
+    pub fn with_source(mut self, source: HashSource) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Run `feed` on the blocking pool with a fresh hasher per configured
+    /// algorithm, so hashing large inputs doesn't stall the async workers.
+    /// `feed` passes the bytes through the hashers and returns whatever it
+    /// needs handed back.
+    async fn hash_blocking<T, F>(&self, feed: F) -> io::Result<(T, Vec<Digest>)>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut [Hasher]) -> io::Result<T> + Send + 'static,
+    {
+        let algorithms = self.algorithms.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut hashers: Vec<_> = algorithms.iter().map(|a| a.hasher()).collect();
+            let handed_back = feed(&mut hashers)?;
+            let digests = hashers
+                .into_iter()
+                .zip(&algorithms)
+                .map(|(hasher, algorithm)| hasher.finish(*algorithm))
+                .collect();
+            Ok((handed_back, digests))
+        })
+        .await
+        .unwrap_or_else(|e| Err(io::Error::other(e)))
+    }
+
+    /// Stream a file, or archive member, through every configured algorithm.
+    ///
+    /// A Fop with a `range` hashes only that range, the same bytes
+    /// `ReadContentProcessor` would read into its content.
+    async fn hash_file(&self, fop: &Fop) -> io::Result<Vec<Digest>> {
+        let range = fop.range;
+        let (_, digests) = match &fop.member {
+            Some(member) => {
+                let member = member.clone();
+                let archives = self.archives.clone();
+                self.hash_blocking(move |hashers| {
+                    let bytes = archives.read(&member)?;
+                    hash_range(io::Cursor::new(bytes), range, hashers)
+                })
+                .await?
+            }
+            None => {
+                let path = fop.filename.clone().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::NotFound, "no filename to hash")
+                })?;
+                self.hash_blocking(move |hashers| hash_range(File::open(path)?, range, hashers))
+                    .await?
+            }
+        };
+        Ok(digests)
+    }
+
+    /// Compute a single digest over a whole result set, for use as a cache key.
+    ///
+    /// Fops are sorted by filename (falling back to file_or_pattern), then by
+    /// digest, so the key does not depend on pipeline completion order even
+    /// when several Fops share a name. Each Fop contributes
+    /// its name and its own digest for `algorithm`. Returns None if any Fop
+    /// lacks that digest, e.g. because it carries an error.
+    pub fn aggregate(fops: &[Fop], algorithm: HashAlgorithm) -> Option<Digest> {
+        let mut entries = fops
+            .iter()
+            .map(|fop| {
+                let name = fop
+                    .filename
+                    .as_ref()
+                    .map(|p| p.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| fop.file_or_pattern.to_string());
+                let digest = fop
+                    .digests
+                    .as_ref()?
+                    .iter()
+                    .find(|d| d.algorithm == algorithm)?;
+                Some((name, digest))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        entries.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.bytes.cmp(&b.1.bytes)));
+
+        let mut hasher = algorithm.hasher();
+        for (name, digest) in entries {
+            hasher.update(&(name.len() as u64).to_be_bytes());
+            hasher.update(name.as_bytes());
+            hasher.update(&digest.bytes);
+        }
+        Some(hasher.finish(algorithm))
+    }
+}
+
+impl Default for HashProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AsyncProcessor for HashProcessor {
+    fn name(&self) -> &'static str {
+        "HashProcessor"
+    }
+
+    async fn process_one(&self, mut fop: Fop) -> Vec<Fop> {
+        if fop.err.is_some() {
+            return vec![fop];
+        }
+
+        let digests = match (self.source, fop.content.take()) {
+            (HashSource::Content | HashSource::Auto, Some(content)) => self
+                .hash_blocking(move |hashers| {
+                    update_all(hashers, content.as_bytes());
+                    Ok(content)
+                })
+                .await
+                .map(|(content, digests)| {
+                    fop.content = Some(content);
+                    digests
+                })
+                .map_err(|e| format!("Failed to hash content of {}: {}", fop.file_or_pattern, e)),
+            (HashSource::Content, None) => Err(format!(
+                "No content to hash for {}",
+                fop.file_or_pattern
+            )),
+            (HashSource::File, content) | (HashSource::Auto, content @ None) => {
+                fop.content = content;
+                self.hash_file(&fop).await.map_err(|e| {
+                    format!("Failed to hash file {}: {}", fop.file_or_pattern, e)
+                })
+            }
+        };
+
+        match digests {
+            Ok(digests) => fop.digests = Some(digests),
+            Err(e) => fop.err = Some(ProcessorError::new("HashProcessor", e)),
+        }
+        vec![fop]
+    }
+}
+
+/// Pass the same bytes to every hasher.
+fn update_all(hashers: &mut [Hasher], data: &[u8]) {
+    for hasher in hashers {
+        hasher.update(data);
+    }
+}
+
+/// Stream everything a reader yields through the hashers.
+fn hash_all(mut reader: impl Read, hashers: &mut [Hasher]) -> io::Result<()> {
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(());
+        }
+        update_all(hashers, &buffer[..read]);
+    }
+}
+
+/// Stream the bytes of `range` through the hashers, or all of them without
+/// one. Selects the same bytes as `ReadContentProcessor::read_range`
+/// without holding them in memory.
+fn hash_range(
+    mut reader: impl Read + Seek,
+    range: Option<ContentRange>,
+    hashers: &mut [Hasher],
+) -> io::Result<()> {
+    match range {
+        None => hash_all(reader, hashers),
+        Some(ContentRange::Bytes { offset, len }) => {
+            reader.seek(SeekFrom::Start(offset))?;
+            match len {
+                Some(len) => hash_all(reader.take(len), hashers),
+                None => hash_all(reader, hashers),
+            }
+        }
+        Some(ContentRange::Lines { start, end }) => {
+            let mut reader = BufReader::with_capacity(CHUNK_SIZE, reader);
+            let mut line = 1;
+            while end.is_none_or(|end| line <= end) {
+                let chunk = reader.fill_buf()?;
+                if chunk.is_empty() {
+                    break;
+                }
+                // Up to and including the next newline, or the whole chunk
+                let (used, ends_line) = match chunk.iter().position(|&b| b == b'\n') {
+                    Some(i) => (i + 1, true),
+                    None => (chunk.len(), false),
+                };
+                if line >= start {
+                    update_all(hashers, &chunk[..used]);
+                }
+                reader.consume(used);
+                if ends_line {
+                    line += 1;
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fop::Content;
+    use std::fs;
+    use tempfile::tempdir;
+
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn test_hash_processor() {
+        let processor = HashProcessor::new();
+        assert_eq!(processor.name(), "HashProcessor");
+        assert_eq!(processor.algorithms, vec![HashAlgorithm::Sha256]);
+        assert_eq!(processor.source, HashSource::Auto);
+    }
+
+    #[test]
+    fn test_with_algorithms() {
+        let processor =
+            HashProcessor::new().with_algorithms([HashAlgorithm::Blake3, HashAlgorithm::Xxh3]);
+        assert_eq!(
+            processor.algorithms,
+            vec![HashAlgorithm::Blake3, HashAlgorithm::Xxh3]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_async_hash_content() {
+        let processor = HashProcessor::new();
+        let mut fop = Fop::new("hello.txt");
+        fop.content = Some(Content::Text("hello".to_string()));
+
+        let results = processor.process_one(fop).await;
+
+        // Content is handed back after hashing
+        assert_eq!(results[0].content.as_ref().unwrap().as_bytes(), b"hello");
+        let digests = results[0].digests.as_ref().unwrap();
+        assert_eq!(digests.len(), 1);
+        assert_eq!(digests[0].to_hex(), HELLO_SHA256);
+        assert_eq!(digests[0].to_string(), format!("sha256:{}", HELLO_SHA256));
+    }
+
+    #[tokio::test]
+    async fn test_async_hash_file_matches_content() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("hello.txt");
+        fs::write(&file_path, "hello").unwrap();
+
+        let processor = HashProcessor::new()
+            .with_algorithms([
+                HashAlgorithm::Sha256,
+                HashAlgorithm::Blake3,
+                HashAlgorithm::Xxh3,
+            ])
+            .with_source(HashSource::File);
+        let mut fop = Fop::new(file_path.to_str().unwrap());
+        fop.filename = Some(file_path.clone());
+
+        let results = processor.process_one(fop.clone()).await;
+        assert!(results[0].content.is_none());
+        let from_file = results[0].digests.clone().unwrap();
+        assert_eq!(from_file.len(), 3);
+        assert_eq!(from_file[0].to_hex(), HELLO_SHA256);
+        assert_eq!(from_file[1].to_hex(), blake3::hash(b"hello").to_hex().as_str());
+        assert_eq!(
+            from_file[2].bytes,
+            xxhash_rust::xxh3::xxh3_64(b"hello").to_be_bytes()
+        );
+
+        fop.content = Some(Content::Bytes(b"hello".to_vec()));
+        let results = processor
+            .with_source(HashSource::Content)
+            .process_one(fop)
+            .await;
+        assert_eq!(results[0].digests.as_ref().unwrap(), &from_file);
+    }
+
+    #[tokio::test]
+    async fn test_async_hash_file_respects_range() {
+        use crate::content::read::ReadContentProcessor;
+        use crate::fop::ContentRange;
+
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("lines.txt");
+        fs::write(&file_path, "one\nhello\nthree\n").unwrap();
+        // Lines longer than a chunk, and no trailing newline
+        let long_path = dir.path().join("long.txt");
+        let long_line = "x".repeat(CHUNK_SIZE + 10);
+        fs::write(&long_path, format!("{}\nmid\n{}", long_line, long_line)).unwrap();
+
+        let ranges = [
+            ContentRange::Bytes {
+                offset: 4,
+                len: Some(5),
+            },
+            ContentRange::Bytes {
+                offset: 4,
+                len: None,
+            },
+            ContentRange::Lines {
+                start: 2,
+                end: Some(2),
+            },
+            ContentRange::Lines {
+                start: 1,
+                end: Some(2),
+            },
+            ContentRange::Lines {
+                start: 2,
+                end: None,
+            },
+        ];
+        for (path, range) in [&file_path, &long_path]
+            .into_iter()
+            .flat_map(|path| ranges.iter().map(move |range| (path, *range)))
+        {
+            let mut fop = Fop::new("lines.txt");
+            fop.filename = Some(path.clone());
+            fop.range = Some(range);
+
+            // Hashing the file agrees with hashing what would be read
+            let from_file = HashProcessor::new().process_one(fop.clone()).await;
+            let read = ReadContentProcessor::new().process_one(fop).await;
+            let from_content = HashProcessor::new().process_one(read[0].clone()).await;
+            assert_eq!(from_file[0].digests, from_content[0].digests);
+        }
+
+        let mut fop = Fop::new("lines.txt");
+        fop.filename = Some(file_path);
+        fop.range = Some(ContentRange::Bytes {
+            offset: 4,
+            len: Some(5),
+        });
+        let results = HashProcessor::new().process_one(fop).await;
+        assert_eq!(
+            results[0].digests.as_ref().unwrap()[0].to_hex(),
+            HELLO_SHA256
+        );
+    }
+
+    #[tokio::test]
+    async fn test_async_hash_content_missing() {
+        let processor = HashProcessor::new().with_source(HashSource::Content);
+
+        let results = processor.process_one(Fop::new("missing.txt")).await;
+
+        assert!(results[0].digests.is_none());
+        assert_eq!(results[0].err.as_ref().unwrap().processor, "HashProcessor");
+    }
+
+    #[tokio::test]
+    async fn test_async_hash_skips_errors() {
+        let processor = HashProcessor::new();
+        let mut fop = Fop::new("broken.txt");
+        fop.err = Some(ProcessorError::new("SomeProcessor", "test error"));
+
+        let results = processor.process_one(fop).await;
+
+        assert!(results[0].digests.is_none());
+        assert_eq!(results[0].err.as_ref().unwrap().processor, "SomeProcessor");
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_is_order_independent() {
+        let processor = HashProcessor::new().with_algorithms([HashAlgorithm::Blake3]);
+        let mut fops = Vec::new();
+        for (name, text) in [("a.txt", "alpha"), ("b.txt", "beta")] {
+            let mut fop = Fop::new(name);
+            fop.filename = Some(name.into());
+            fop.content = Some(Content::Text(text.to_string()));
+            fops.extend(processor.process_one(fop).await);
+        }
+
+        let forward = HashProcessor::aggregate(&fops, HashAlgorithm::Blake3).unwrap();
+        fops.reverse();
+        let backward = HashProcessor::aggregate(&fops, HashAlgorithm::Blake3).unwrap();
+        assert_eq!(forward, backward);
+
+        // Fops sharing a name are ordered by digest
+        let mut twins = fops.clone();
+        for fop in &mut twins {
+            fop.filename = Some("same.txt".into());
+        }
+        let forward_twins = HashProcessor::aggregate(&twins, HashAlgorithm::Blake3).unwrap();
+        twins.reverse();
+        let backward_twins = HashProcessor::aggregate(&twins, HashAlgorithm::Blake3).unwrap();
+        assert_eq!(forward_twins, backward_twins);
+
+        // Algorithm that wasn't computed
+        assert!(HashProcessor::aggregate(&fops, HashAlgorithm::Sha256).is_none());
+
+        // Changing one input changes the key
+        let mut changed = fops[0].clone();
+        changed.content = Some(Content::Text("changed".to_string()));
+        fops[0] = processor.process_one(changed).await.remove(0);
+        let changed = HashProcessor::aggregate(&fops, HashAlgorithm::Blake3).unwrap();
+        assert_ne!(forward, changed);
+    }
+
+    #[test]
+    fn test_default() {
+        let processor = HashProcessor::default();
+        assert_eq!(processor.name(), "HashProcessor");
+    }
+}
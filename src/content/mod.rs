@@ -2,8 +2,16 @@
 
 pub mod exec;
 pub mod guard;
+pub mod hash;
+pub mod parse;
 pub mod read;
 
-pub use exec::DoExecuteProcessor;
+pub use exec::{
+    CaptureMode, Denial, DoExecuteProcessor, Executable, Execution, Executor, FailChecker,
+    InterpreterRegistry, OutputEvent, OutputMode, OutputStream, Sandbox, StdinSource, TrustPolicy,
+    Verdict, WorkingDir,
+};
 pub use guard::GuardProcessor;
+pub use hash::{Digest, HashAlgorithm, HashProcessor, HashSource};
+pub use parse::{ContentFormat, ParseContentProcessor};
 pub use read::ReadContentProcessor;
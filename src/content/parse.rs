@@ -0,0 +1,358 @@
+//! ParseContentProcessor implementation.
+
+use crate::fop::{Content, ErrorKind, Fop, ProcessorError};
+use crate::processor::AsyncProcessor;
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// Structured formats understood by ParseContentProcessor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentFormat {
+    /// JSON (`.json`)
+    Json,
+    /// TOML (`.toml`)
+    Toml,
+    /// YAML (`.yaml`, `.yml`)
+    Yaml,
+    /// CSV with a header row (`.csv`), parsed into an array of objects
+    Csv,
+}
+
+impl ContentFormat {
+    /// Detect the format from a file extension.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "json" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            "yaml" | "yml" => Some(Self::Yaml),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+
+    /// Parse text in this format into a structured value.
+    pub fn parse(&self, text: &str) -> Result<Value, ParseError> {
+        match self {
+            ContentFormat::Json => serde_json::from_str(text).map_err(|e| ParseError {
+                message: e.to_string(),
+                line: Some(e.line()),
+                column: Some(e.column()),
+            }),
+            ContentFormat::Toml => {
+                let value: toml::Value = toml::from_str(text).map_err(|e| {
+                    let position = e.span().map(|span| line_column(text, span.start));
+                    let message = match position {
+                        Some((line, column)) => {
+                            format!("{} at line {} column {}", e.message(), line, column)
+                        }
+                        None => e.message().to_string(),
+                    };
+                    ParseError {
+                        message,
+                        line: position.map(|(line, _)| line),
+                        column: position.map(|(_, column)| column),
+                    }
+                })?;
+                Ok(toml_to_json(value))
+            }
+            ContentFormat::Yaml => serde_yaml::from_str(text).map_err(|e| {
+                let location = e.location();
+                ParseError {
+                    message: e.to_string(),
+                    line: location.as_ref().map(|l| l.line()),
+                    column: location.as_ref().map(|l| l.column()),
+                }
+            }),
+            ContentFormat::Csv => parse_csv(text),
+        }
+    }
+}
+
+/// A parse failure with its 1-based position, when known.
+///
+/// The message includes the position as reported by the parser.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Parser's description of the failure
+    pub message: String,
+    /// 1-based line of the failure
+    pub line: Option<usize>,
+    /// 1-based column of the failure
+    pub column: Option<usize>,
+}
+
+/// Convert a byte offset into a 1-based (line, column) pair.
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+    (line, column)
+}
+
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(items) => Value::Array(items.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(k, v)| (k, toml_to_json(v)))
+                .collect(),
+        ),
+    }
+}
+
+fn parse_csv(text: &str) -> Result<Value, ParseError> {
+    let csv_error = |e: csv::Error| ParseError {
+        line: e.position().map(|p| p.line() as usize),
+        column: None,
+        message: e.to_string(),
+    };
+
+    let mut reader = csv::Reader::from_reader(text.as_bytes());
+    let headers = reader.headers().map_err(csv_error)?.clone();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(csv_error)?;
+        let row: Map<String, Value> = headers
+            .iter()
+            .zip(record.iter())
+            .map(|(k, v)| (k.to_string(), Value::String(v.to_string())))
+            .collect();
+        rows.push(Value::Object(row));
+    }
+    Ok(Value::Array(rows))
+}
+
+/// Processor for parsing content into a structured value.
+///
+/// Picks a format from the filename's extension, or uses the one set with
+/// `with_format`, parses `content` and stores the result in `fop.parsed`.
+/// Fops with no detectable format, no content, or an existing error pass
+/// through untouched. Parse failures set `err` with `ErrorKind::Parse`.
+pub struct ParseContentProcessor {
+    format: Option<ContentFormat>,
+}
+
+impl ParseContentProcessor {
+    /// Create a new ParseContentProcessor detecting the format by extension.
+    pub fn new() -> Self {
+        Self { format: None }
+    }
+
+    /// Always parse with this format instead of detecting it.
+    pub fn with_format(mut self, format: ContentFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    fn detect_format(&self, fop: &Fop) -> Option<ContentFormat> {
+        self.format.or_else(|| match &fop.filename {
+            Some(filename) => ContentFormat::from_path(filename),
+            None => ContentFormat::from_path(&*fop.file_or_pattern),
+        })
+    }
+}
+
+impl Default for ParseContentProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AsyncProcessor for ParseContentProcessor {
+    fn name(&self) -> &'static str {
+        "ParseContentProcessor"
+    }
+
+    async fn process_one(&self, mut fop: Fop) -> Vec<Fop> {
+        let name = "ParseContentProcessor";
+        if fop.err.is_some() {
+            return vec![fop];
+        }
+        let (Some(format), Some(content)) = (self.detect_format(&fop), &fop.content) else {
+            return vec![fop];
+        };
+
+        let text = match content {
+            Content::Text(text) => Ok(text.as_str()),
+            other => std::str::from_utf8(other.as_bytes()),
+        };
+        let parsed = match text {
+            Ok(text) => format.parse(text),
+            Err(e) => Err(ParseError {
+                message: format!("Content is not valid UTF-8: {}", e),
+                line: None,
+                column: None,
+            }),
+        };
+
+        match parsed {
+            Ok(value) => fop.parsed = Some(value),
+            Err(e) => {
+                fop.err = Some(
+                    ProcessorError::new(
+                        name,
+                        format!(
+                            "Failed to parse {:?} content of {}: {}",
+                            format, fop.file_or_pattern, e.message
+                        ),
+                    )
+                    .with_kind(ErrorKind::Parse {
+                        line: e.line,
+                        column: e.column,
+                    }),
+                );
+            }
+        }
+        vec![fop]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn text_fop(filename: &str, text: &str) -> Fop {
+        let mut fop = Fop::new(filename);
+        fop.filename = Some(filename.into());
+        fop.content = Some(Content::Text(text.to_string()));
+        fop
+    }
+
+    #[test]
+    fn test_parse_content_processor() {
+        let processor = ParseContentProcessor::new();
+        assert_eq!(processor.name(), "ParseContentProcessor");
+        assert!(processor.format.is_none());
+    }
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(ContentFormat::from_path("a.json"), Some(ContentFormat::Json));
+        assert_eq!(ContentFormat::from_path("Cargo.toml"), Some(ContentFormat::Toml));
+        assert_eq!(ContentFormat::from_path("a.yml"), Some(ContentFormat::Yaml));
+        assert_eq!(ContentFormat::from_path("a.YAML"), Some(ContentFormat::Yaml));
+        assert_eq!(ContentFormat::from_path("a.csv"), Some(ContentFormat::Csv));
+        assert_eq!(ContentFormat::from_path("a.txt"), None);
+    }
+
+    #[test]
+    fn test_parse_formats() {
+        assert_eq!(
+            ContentFormat::Json.parse(r#"{"a": [1, 2]}"#).unwrap(),
+            json!({"a": [1, 2]})
+        );
+        assert_eq!(
+            ContentFormat::Toml
+                .parse("[pkg]\nname = \"x\"\nwhen = 1979-05-27\n")
+                .unwrap(),
+            json!({"pkg": {"name": "x", "when": "1979-05-27"}})
+        );
+        assert_eq!(
+            ContentFormat::Yaml.parse("a:\n  - 1\n  - b\n").unwrap(),
+            json!({"a": [1, "b"]})
+        );
+        assert_eq!(
+            ContentFormat::Csv.parse("name,size\nx,1\ny,2\n").unwrap(),
+            json!([{"name": "x", "size": "1"}, {"name": "y", "size": "2"}])
+        );
+    }
+
+    #[test]
+    fn test_parse_error_positions() {
+        let err = ContentFormat::Json.parse("{\n  \"a\": nope\n}").unwrap_err();
+        assert_eq!((err.line, err.column), (Some(2), Some(9)));
+
+        let err = ContentFormat::Toml.parse("a = 1\nb = \n").unwrap_err();
+        assert_eq!(err.line, Some(2));
+        assert!(err.column.is_some());
+
+        let err = ContentFormat::Yaml.parse("a: 1\nb: [\n").unwrap_err();
+        assert!(err.line.is_some());
+
+        let err = ContentFormat::Csv.parse("a,b\n1,2\n3\n").unwrap_err();
+        assert_eq!(err.line, Some(3));
+    }
+
+    #[test]
+    fn test_line_column() {
+        assert_eq!(line_column("abc", 0), (1, 1));
+        assert_eq!(line_column("abc\ndef", 5), (2, 2));
+    }
+
+    #[tokio::test]
+    async fn test_async_parse_by_extension() {
+        let processor = ParseContentProcessor::new();
+
+        let results = processor
+            .process_one(text_fop("config.json", r#"{"debug": true}"#))
+            .await;
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].parsed, Some(json!({"debug": true})));
+    }
+
+    #[tokio::test]
+    async fn test_async_parse_explicit_format() {
+        let processor = ParseContentProcessor::new().with_format(ContentFormat::Yaml);
+
+        let results = processor.process_one(text_fop("config", "debug: true")).await;
+
+        assert_eq!(results[0].parsed, Some(json!({"debug": true})));
+    }
+
+    #[tokio::test]
+    async fn test_async_parse_bytes_content() {
+        let processor = ParseContentProcessor::new();
+        let mut fop = Fop::new("data.json");
+        fop.content = Some(Content::Bytes(b"[1]".to_vec()));
+
+        let results = processor.process_one(fop).await;
+
+        assert_eq!(results[0].parsed, Some(json!([1])));
+    }
+
+    #[tokio::test]
+    async fn test_async_parse_unknown_format_passes_through() {
+        let processor = ParseContentProcessor::new();
+
+        let results = processor.process_one(text_fop("notes.txt", "hello")).await;
+
+        assert!(results[0].parsed.is_none());
+        assert!(results[0].err.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_async_parse_error() {
+        let processor = ParseContentProcessor::new();
+
+        let results = processor
+            .process_one(text_fop("config.json", "{\n  \"a\": nope\n}"))
+            .await;
+
+        assert!(results[0].parsed.is_none());
+        let err = results[0].err.as_ref().unwrap();
+        assert_eq!(err.processor, "ParseContentProcessor");
+        assert_eq!(
+            err.kind,
+            ErrorKind::Parse {
+                line: Some(2),
+                column: Some(9)
+            }
+        );
+        assert!(err.source.contains("line 2 column 9"));
+    }
+
+    #[test]
+    fn test_default() {
+        let processor = ParseContentProcessor::default();
+        assert_eq!(processor.name(), "ParseContentProcessor");
+    }
+}
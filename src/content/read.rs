@@ -1,16 +1,42 @@
 //! ReadContentProcessor implementation.
 
-use crate::fop::{Content, Fop, ProcessorError};
+use crate::archive::ArchiveReader;
+use crate::fop::{Content, ContentRange, Fop, ProcessorError};
 use crate::processor::{AsyncProcessor, Processor};
+use crate::progress::{Progress, ProgressEvent};
+use crate::stamper::{Stamper, StamperOptions};
+use memmap2::Mmap;
 use std::fs;
-use std::io::Read;
+use std::io::{self, Read, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
 
 /// Processor for reading file contents.
 ///
-/// Reads from filename field and adds content to Fop.
+/// Reads from filename field and adds content to Fop. When the Fop carries an
+/// archive `member`, the member is read out of its archive instead; tar.gz
+/// decoders are kept between member reads for as long as the processor lives.
+///
+/// With `with_mmap_threshold`, regular files at least that large are mapped
+/// read-only into `Content::Mapped` rather than copied into memory.
+///
+/// When the Fop has a `range` (see `ParserProcessor::with_ranges`), only that
+/// portion of the file is read.
+///
+/// Fops that already have content, such as the output of an execution, pass
+/// through; the file is only read as a fallback.
+///
+/// With a read stamper, each read is recorded in `fop.stamps` under the read
+/// name, `readStamp` by default.
 pub struct ReadContentProcessor {
     encoding: Option<String>,
     record_encoding: bool,
+    mmap_threshold: Option<u64>,
+    progress: Option<Progress>,
+    read_stamper: Option<Box<dyn Stamper>>,
+    read_name: String,
+    archives: ArchiveReader,
 }
 
 impl ReadContentProcessor {
@@ -19,6 +45,11 @@ impl ReadContentProcessor {
         Self {
             encoding: Some("utf8".to_string()),
             record_encoding: false,
+            mmap_threshold: None,
+            progress: None,
+            read_stamper: None,
+            read_name: "readStamp".to_string(),
+            archives: ArchiveReader::new(),
         }
     }
 
@@ -43,6 +74,197 @@ impl ReadContentProcessor {
         self.record_encoding = record;
         self
     }
+
+    /// Memory-map regular files of at least `bytes` size instead of reading them.
+    ///
+    /// Mapped files become `Content::Mapped` regardless of encoding. Only
+    /// non-empty regular files are mapped: FIFOs, devices, empty files such
+    /// as procfs entries, files the kernel refuses to map such as sysfs
+    /// attributes, and archive members fall back to a normal read.
+    ///
+    /// A mapped file must not be truncated while the mapping is alive: access
+    /// past the new end of file raises SIGBUS. Files whose size changes while
+    /// the mapping is set up are detected and read normally instead.
+    pub fn with_mmap_threshold(mut self, bytes: u64) -> Self {
+        self.mmap_threshold = Some(bytes);
+        self
+    }
+
+    /// Report the bytes of each file read, or mapped.
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Set a stamper for measuring each read. Only the async processor
+    /// stamps.
+    pub fn with_read_stamper(mut self, stamper: impl Stamper + 'static) -> Self {
+        self.read_stamper = Some(Box::new(stamper));
+        self
+    }
+
+    /// Set the field name for the read stamp, `readStamp` by default.
+    pub fn with_read_name(mut self, name: impl Into<String>) -> Self {
+        self.read_name = name.into();
+        self
+    }
+
+    fn report_read(&self, bytes: usize) {
+        if let Some(progress) = &self.progress {
+            progress.report(ProgressEvent::BytesRead(bytes as u64));
+        }
+    }
+
+    /// Map a file read-only if it qualifies, returning None to fall back to a normal read.
+    fn try_map(path: &Path, threshold: u64) -> io::Result<Option<Mmap>> {
+        // Stat before opening: opening a FIFO would block until a writer appears
+        let qualifies =
+            |meta: &fs::Metadata| meta.is_file() && meta.len() > 0 && meta.len() >= threshold;
+        if !qualifies(&fs::metadata(path)?) {
+            return Ok(None);
+        }
+
+        let file = fs::File::open(path)?;
+        let before = file.metadata()?;
+        if !qualifies(&before) {
+            return Ok(None);
+        }
+
+        // SAFETY: the map is read-only, and the size is re-checked below so a
+        // file that shrank while being mapped is never handed out. Truncation
+        // after this point is the caller's documented responsibility.
+        let Ok(map) = (unsafe { Mmap::map(&file) }) else {
+            return Ok(None);
+        };
+
+        let after = file.metadata()?;
+        if after.len() != before.len() || map.len() as u64 != after.len() {
+            return Ok(None);
+        }
+        Ok(Some(map))
+    }
+
+    /// Read only the requested range from a reader.
+    pub(crate) async fn read_range<R>(mut reader: R, range: ContentRange) -> io::Result<Vec<u8>>
+    where
+        R: AsyncRead + AsyncSeek + Unpin,
+    {
+        let mut buffer = Vec::new();
+        match range {
+            ContentRange::Bytes { offset, len } => {
+                reader.seek(SeekFrom::Start(offset)).await?;
+                match len {
+                    Some(len) => reader.take(len).read_to_end(&mut buffer).await?,
+                    None => reader.read_to_end(&mut buffer).await?,
+                };
+            }
+            ContentRange::Lines { start, end } => {
+                let mut reader = tokio::io::BufReader::new(reader);
+                let mut skipped = Vec::new();
+                let mut line = 1;
+                while end.is_none_or(|end| line <= end) {
+                    let read = if line < start {
+                        skipped.clear();
+                        reader.read_until(b'\n', &mut skipped).await?
+                    } else {
+                        reader.read_until(b'\n', &mut buffer).await?
+                    };
+                    if read == 0 {
+                        break;
+                    }
+                    line += 1;
+                }
+            }
+        }
+        Ok(buffer)
+    }
+
+    /// Read the Fop's file, or archive member, into its content.
+    async fn read(&self, mut fop: Fop) -> Fop {
+        let encoding = self.encoding.clone();
+        let record_encoding = self.record_encoding;
+
+        if let Some(filename) = &fop.filename {
+            if let (Some(threshold), None, None) = (self.mmap_threshold, &fop.member, fop.range) {
+                let path = filename.clone();
+                let mapped = tokio::task::spawn_blocking(move || Self::try_map(&path, threshold))
+                    .await
+                    .unwrap_or_else(|e| Err(io::Error::other(e)));
+                match mapped {
+                    Ok(Some(map)) => {
+                        self.report_read(map.len());
+                        fop.content = Some(Content::Mapped(Arc::new(map)));
+                        if record_encoding {
+                            fop.encoding = Some("binary".to_string());
+                        }
+                        return fop;
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        fop.err = Some(ProcessorError::new(
+                            "ReadContentProcessor",
+                            format!("Failed to map file {}: {}", filename.display(), e),
+                        ));
+                        return fop;
+                    }
+                }
+            }
+
+            let read = match (&fop.member, fop.range) {
+                (Some(member), range) => {
+                    let member = member.clone();
+                    let archives = self.archives.clone();
+                    let bytes = tokio::task::spawn_blocking(move || archives.read(&member))
+                        .await
+                        .unwrap_or_else(|e| Err(io::Error::other(e)));
+                    match (bytes, range) {
+                        (Ok(bytes), Some(range)) => {
+                            Self::read_range(io::Cursor::new(bytes), range).await
+                        }
+                        (bytes, _) => bytes,
+                    }
+                }
+                (None, Some(range)) => match tokio::fs::File::open(filename).await {
+                    Ok(file) => Self::read_range(file, range).await,
+                    Err(e) => Err(e),
+                },
+                (None, None) => tokio::fs::read(filename).await,
+            };
+            match read {
+                Ok(bytes) => {
+                    self.report_read(bytes.len());
+                    if let Some(enc) = &encoding {
+                        match String::from_utf8(bytes.clone()) {
+                            Ok(text) => {
+                                fop.content = Some(Content::Text(text));
+                                if record_encoding {
+                                    fop.encoding = Some(enc.clone());
+                                }
+                            }
+                            Err(_) => {
+                                fop.content = Some(Content::Bytes(bytes));
+                                if record_encoding {
+                                    fop.encoding = Some("binary".to_string());
+                                }
+                            }
+                        }
+                    } else {
+                        fop.content = Some(Content::Bytes(bytes));
+                        if record_encoding {
+                            fop.encoding = Some("binary".to_string());
+                        }
+                    }
+                }
+                Err(e) => {
+                    fop.err = Some(ProcessorError::new(
+                        "ReadContentProcessor",
+                        format!("Failed to read file {}: {}", filename.display(), e),
+                    ));
+                }
+            }
+        }
+        fop
+    }
 }
 
 impl Default for ReadContentProcessor {
@@ -60,6 +282,9 @@ impl Processor for ReadContentProcessor {
         let record_encoding = self.record_encoding;
         let name = Processor::name(self).to_string();
         input.map(move |mut fop| {
+            if fop.content.is_some() {
+                return fop;
+            }
             // Only process if filename is set
             if let Some(filename) = &fop.filename {
                 match fs::File::open(filename) {
@@ -124,42 +349,21 @@ impl AsyncProcessor for ReadContentProcessor {
         "ReadContentProcessor"
     }
 
-    async fn process_one(&self, mut fop: Fop) -> Vec<Fop> {
-        let encoding = self.encoding.clone();
-        let record_encoding = self.record_encoding;
+    async fn process_one(&self, fop: Fop) -> Vec<Fop> {
+        if fop.content.is_some() || fop.filename.is_none() {
+            return vec![fop];
+        }
 
-        if let Some(filename) = &fop.filename {
-            match tokio::fs::read(filename).await {
-                Ok(bytes) => {
-                    if let Some(enc) = &encoding {
-                        match String::from_utf8(bytes.clone()) {
-                            Ok(text) => {
-                                fop.content = Some(Content::Text(text));
-                                if record_encoding {
-                                    fop.encoding = Some(enc.clone());
-                                }
-                            }
-                            Err(_) => {
-                                fop.content = Some(Content::Bytes(bytes));
-                                if record_encoding {
-                                    fop.encoding = Some("binary".to_string());
-                                }
-                            }
-                        }
-                    } else {
-                        fop.content = Some(Content::Bytes(bytes));
-                        if record_encoding {
-                            fop.encoding = Some("binary".to_string());
-                        }
-                    }
-                }
-                Err(e) => {
-                    fop.err = Some(ProcessorError::new(
-                        "ReadContentProcessor",
-                        format!("Failed to read file {}: {}", filename.display(), e),
-                    ));
-                }
-            }
+        let options = StamperOptions {
+            processor: Some("ReadContentProcessor"),
+        };
+        let span = self
+            .read_stamper
+            .as_ref()
+            .map(|stamper| stamper.span(&options, "ReadContentProcessor", &fop));
+        let mut fop = self.read(fop).await;
+        if let Some(span) = span {
+            span.finish(&mut fop, &self.read_name);
         }
         vec![fop]
     }
@@ -304,6 +508,48 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_async_read_skips_existing_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("gen.sh");
+        std::fs::write(&file_path, "echo generated").unwrap();
+
+        let processor = ReadContentProcessor::new();
+        let mut fop = Fop::new(file_path.to_str().unwrap());
+        fop.filename = Some(file_path.clone());
+        fop.content = Some(Content::Text("generated\n".to_string()));
+
+        let results = processor.process_one(fop).await;
+
+        assert_eq!(
+            results[0].content.as_ref().unwrap().as_bytes(),
+            b"generated\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_async_read_stamp() {
+        use crate::stamper::TrueStamper;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("test.txt");
+        std::fs::write(&file_path, "stamped").unwrap();
+
+        let processor = ReadContentProcessor::new().with_read_stamper(TrueStamper);
+        let mut fop = Fop::new("test.txt");
+        fop.filename = Some(file_path);
+        let results = processor.process_one(fop).await;
+
+        let stamp = &results[0].stamps["readStamp"];
+        assert!(stamp.start.unwrap() <= stamp.end.unwrap());
+
+        // Fops with content aren't read, so aren't stamped
+        let mut fop = Fop::new("test.txt");
+        fop.content = Some(Content::Text("generated".to_string()));
+        let results = processor.process_one(fop).await;
+        assert!(results[0].stamps.is_empty());
+    }
+
     #[tokio::test]
     async fn test_async_read_binary_content() {
         let dir = tempfile::tempdir().unwrap();
@@ -326,6 +572,179 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_async_read_line_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("lines.txt");
+        std::fs::write(&file_path, "one\ntwo\nthree\nfour\n").unwrap();
+
+        let processor = ReadContentProcessor::new();
+        let mut fop = Fop::new(file_path.to_str().unwrap());
+        fop.filename = Some(file_path.clone());
+        fop.range = Some(ContentRange::Lines {
+            start: 2,
+            end: Some(3),
+        });
+
+        let results = processor.process_one(fop.clone()).await;
+        assert!(matches!(&results[0].content, Some(Content::Text(t)) if t == "two\nthree\n"));
+        assert!(results[0].range.is_some());
+
+        fop.range = Some(ContentRange::Lines {
+            start: 3,
+            end: None,
+        });
+        let results = processor.process_one(fop).await;
+        assert!(matches!(&results[0].content, Some(Content::Text(t)) if t == "three\nfour\n"));
+    }
+
+    #[tokio::test]
+    async fn test_async_read_byte_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("data.bin");
+        std::fs::write(&file_path, b"0123456789").unwrap();
+
+        let processor = ReadContentProcessor::new().as_binary();
+        let mut fop = Fop::new(file_path.to_str().unwrap());
+        fop.filename = Some(file_path.clone());
+        fop.range = Some(ContentRange::Bytes {
+            offset: 2,
+            len: Some(4),
+        });
+
+        let results = processor.process_one(fop.clone()).await;
+        assert!(matches!(&results[0].content, Some(Content::Bytes(b)) if b == b"2345"));
+
+        // Ranges past the end of file are clamped
+        fop.range = Some(ContentRange::Bytes {
+            offset: 8,
+            len: Some(100),
+        });
+        let results = processor.process_one(fop).await;
+        assert!(matches!(&results[0].content, Some(Content::Bytes(b)) if b == b"89"));
+    }
+
+    #[tokio::test]
+    async fn test_async_read_mapped_above_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("large.bin");
+        std::fs::write(&file_path, vec![7u8; 4096]).unwrap();
+
+        let processor = ReadContentProcessor::new()
+            .with_mmap_threshold(1024)
+            .record_encoding(true);
+        let mut fop = Fop::new(file_path.to_str().unwrap());
+        fop.filename = Some(file_path.clone());
+
+        let results = processor.process_one(fop).await;
+
+        assert_eq!(results.len(), 1);
+        match &results[0].content {
+            Some(Content::Mapped(map)) => assert_eq!(&map[..], &[7u8; 4096][..]),
+            other => panic!("Expected Mapped content, got {:?}", other),
+        }
+        assert_eq!(results[0].encoding.as_deref(), Some("binary"));
+    }
+
+    #[tokio::test]
+    async fn test_async_read_below_mmap_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("small.txt");
+        std::fs::write(&file_path, "small").unwrap();
+
+        let processor = ReadContentProcessor::new().with_mmap_threshold(1024);
+        let mut fop = Fop::new(file_path.to_str().unwrap());
+        fop.filename = Some(file_path.clone());
+
+        let results = processor.process_one(fop).await;
+
+        assert!(matches!(&results[0].content, Some(Content::Text(t)) if t == "small"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_async_read_mmap_falls_back_for_procfs() {
+        let processor = ReadContentProcessor::new().with_mmap_threshold(0);
+        let mut fop = Fop::new("/proc/self/status");
+        fop.filename = Some("/proc/self/status".into());
+
+        let results = processor.process_one(fop).await;
+
+        assert!(results[0].err.is_none());
+        assert!(matches!(&results[0].content, Some(Content::Text(t)) if !t.is_empty()));
+
+        // Sysfs attributes claim a page of size but can't be mapped
+        let attribute = Path::new("/sys/kernel/address_bits");
+        if attribute.exists() {
+            let mut fop = Fop::new("address_bits");
+            fop.filename = Some(attribute.into());
+
+            let results = processor.process_one(fop).await;
+
+            assert!(results[0].err.is_none());
+            assert!(matches!(&results[0].content, Some(Content::Text(t)) if !t.is_empty()));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_try_map_skips_fifo() {
+        let dir = tempdir().unwrap();
+        let fifo = dir.path().join("fifo");
+        let status = std::process::Command::new("mkfifo")
+            .arg(&fifo)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        assert!(ReadContentProcessor::try_map(&fifo, 0).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_try_map_skips_empty_file() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("empty");
+        fs::write(&file_path, "").unwrap();
+
+        assert!(ReadContentProcessor::try_map(&file_path, 0)
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_async_read_archive_member() {
+        use crate::archive::{list_members, ArchiveKind};
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let archive_path = dir.path().join("bundle.tar");
+        let mut builder = tar::Builder::new(fs::File::create(&archive_path).unwrap());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(11);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "inner.txt", &b"from member"[..])
+            .unwrap();
+        builder.finish().unwrap();
+
+        let member = list_members(&archive_path, ArchiveKind::Tar)
+            .unwrap()
+            .remove(0);
+        let mut fop = Fop::new("bundle.tar/*.txt");
+        fop.filename = Some(member.virtual_path());
+        fop.member = Some(Arc::new(member));
+
+        let results = ReadContentProcessor::new().process_one(fop).await;
+
+        assert_eq!(results.len(), 1);
+        if let Some(Content::Text(text)) = &results[0].content {
+            assert_eq!(text, "from member");
+        } else {
+            panic!("Expected Text content");
+        }
+    }
+
     #[tokio::test]
     async fn test_async_read_no_filename() {
         let processor = ReadContentProcessor::new();
@@ -0,0 +1,244 @@
+//! Records of how a file was run, carried on a `Fop`.
+//!
+//! These are produced by the processors in `content::exec`, and live here
+//! so that `Fop` can hold them without depending on those processors.
+
+use crate::fop::ProcessorError;
+use futures::stream::{self, BoxStream, StreamExt};
+use std::any::Any;
+use std::process::ExitStatus;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::AbortHandle;
+
+/// How a file was executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Executable {
+    /// Run directly, as permitted by its mode bits
+    Direct,
+    /// Run as an argument to an interpreter, e.g. `python3 gen.py`
+    Interpreter(Interpreter),
+}
+
+/// A command that runs a script given as its last argument.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interpreter {
+    /// Interpreter program, looked up on `PATH` unless it is a path
+    pub program: String,
+    /// Arguments before the script path, e.g. `run` for `deno run`
+    pub args: Vec<String>,
+}
+
+impl Interpreter {
+    /// Parse a whitespace-separated command such as `deno run`.
+    pub fn new(command: &str) -> Self {
+        let mut words = command.split_whitespace().map(str::to_string);
+        Self {
+            program: words.next().unwrap_or_default(),
+            args: words.collect(),
+        }
+    }
+}
+
+impl std::fmt::Display for Interpreter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.program)?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// Record of a finished execution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Execution {
+    /// Exit code, if the process exited normally
+    pub code: Option<i32>,
+    /// Signal that terminated the process, on Unix
+    pub signal: Option<i32>,
+    /// Raw stdout; with `CaptureMode::Combined`, stdout and stderr interleaved
+    pub stdout: Vec<u8>,
+    /// Raw stderr; empty with `CaptureMode::Combined`
+    pub stderr: Vec<u8>,
+    /// Wall-clock time from spawn to exit
+    pub duration: Duration,
+    /// Why feeding stdin failed, if it did
+    ///
+    /// The child closing stdin before reading everything is not an error.
+    pub stdin_error: Option<String>,
+}
+
+impl Execution {
+    /// Build a record from an exit status and captured output.
+    pub fn new(status: ExitStatus, stdout: Vec<u8>, stderr: Vec<u8>, duration: Duration) -> Self {
+        #[cfg(unix)]
+        let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+        #[cfg(not(unix))]
+        let signal = None;
+        Self {
+            code: status.code(),
+            signal,
+            stdout,
+            stderr,
+            duration,
+            stdin_error: None,
+        }
+    }
+
+    /// Whether the process exited with code 0.
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+impl std::fmt::Display for Execution {
+    /// Describes how the process ended, e.g. `exit code 1` or `signal 9`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.code, self.signal) {
+            (Some(code), _) => write!(f, "exit code {}", code),
+            (None, Some(signal)) => write!(f, "signal {}", signal),
+            (None, None) => write!(f, "unknown status"),
+        }
+    }
+}
+
+/// Why a trust policy refused to execute a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Denial {
+    /// The file is not under any trusted root
+    OutsideTrustedRoots,
+    /// The file is owned by a user other than us or root
+    UntrustedOwner { uid: u32 },
+    /// Group or others may write the file
+    Writable { mode: u32 },
+    /// The file's SHA-256 is not on the allowlist
+    NotAllowlisted { sha256: String },
+    /// The file could not be inspected, so it is not trusted
+    Unverifiable(String),
+}
+
+impl std::fmt::Display for Denial {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Denial::OutsideTrustedRoots => write!(f, "not under a trusted directory"),
+            Denial::UntrustedOwner { uid } => write!(f, "owned by untrusted user {}", uid),
+            Denial::Writable { mode } => {
+                write!(f, "writable by group or others (mode {:o})", mode & 0o7777)
+            }
+            Denial::NotAllowlisted { sha256 } => write!(f, "sha256 {} is not allowlisted", sha256),
+            Denial::Unverifiable(reason) => write!(f, "could not be verified: {}", reason),
+        }
+    }
+}
+
+/// How streamed stdout is split into events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Chunks as they are read from the pipe
+    Chunks,
+    /// Whole lines, without their trailing newline
+    Lines,
+}
+
+/// An event from a streaming execution.
+#[derive(Debug, Clone)]
+pub enum OutputEvent {
+    /// A chunk or line of stdout
+    Data(Vec<u8>),
+    /// The process ended; always the last event
+    Exit {
+        /// The execution record, with stdout empty since it was streamed
+        execution: Option<Execution>,
+        /// Set when the run failed or the fail checker rejected it
+        err: Option<ProcessorError>,
+    },
+}
+
+/// Receiving half of a streaming execution, attached to the Fop as `output`.
+///
+/// Clones share one stream, which can be taken once. The execution is
+/// stopped, and its process group killed, once every clone and the taken
+/// stream are dropped.
+///
+/// Wrappers such as bounded processors `hold` their permit in the stream,
+/// so it is kept while the execution runs rather than released as soon as
+/// `process_one` returns.
+#[derive(Clone)]
+pub struct OutputStream {
+    inner: Arc<StreamInner>,
+}
+
+struct StreamInner {
+    mode: OutputMode,
+    rx: Mutex<Option<mpsc::Receiver<OutputEvent>>>,
+    task: AbortHandle,
+    held: Mutex<Vec<Box<dyn Any + Send>>>,
+}
+
+impl StreamInner {
+    fn release(&self) {
+        if let Ok(mut held) = self.held.lock() {
+            held.clear();
+        }
+    }
+}
+
+impl Drop for StreamInner {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl OutputStream {
+    /// Wrap the receiver fed by the execution running as `task`.
+    pub fn new(rx: mpsc::Receiver<OutputEvent>, mode: OutputMode, task: AbortHandle) -> Self {
+        Self {
+            inner: Arc::new(StreamInner {
+                mode,
+                rx: Mutex::new(Some(rx)),
+                task,
+                held: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Keep `guard` alive until the execution's exit event has been read,
+    /// or the stream is dropped.
+    pub fn hold(&self, guard: impl Any + Send) {
+        if let Ok(mut held) = self.inner.held.lock() {
+            held.push(Box::new(guard));
+        }
+    }
+
+    /// How stdout is split into events.
+    pub fn mode(&self) -> OutputMode {
+        self.inner.mode
+    }
+
+    /// Take the event stream; None if it was already taken.
+    pub fn take(&self) -> Option<BoxStream<'static, OutputEvent>> {
+        let rx = self.inner.rx.lock().ok()?.take()?;
+        // The stream holds the inner handle so the execution outlives the Fop
+        let inner = self.inner.clone();
+        Some(
+            stream::unfold((rx, inner), |(mut rx, inner)| async move {
+                let event = rx.recv().await;
+                if matches!(event, None | Some(OutputEvent::Exit { .. })) {
+                    inner.release();
+                }
+                event.map(|event| (event, (rx, inner)))
+            })
+            .boxed(),
+        )
+    }
+}
+
+impl std::fmt::Debug for OutputStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OutputStream")
+            .field("mode", &self.inner.mode)
+            .finish_non_exhaustive()
+    }
+}
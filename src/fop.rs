@@ -1,7 +1,13 @@
 //! Core types for the File Or Pattern library.
 
+use crate::archive::ArchiveMember;
+use crate::basic::fsstream::glob_to_regex;
+use crate::content::hash::Digest;
+use crate::execution::{Denial, Executable, Execution, OutputStream};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
 /// A flyweight object passed through the pipeline, accumulating fields as it's processed.
 ///
@@ -13,16 +19,33 @@ pub struct Fop {
     pub file_or_pattern: Arc<str>,
     /// Concrete existing file path
     pub filename: Option<PathBuf>,
-    /// Whether filename is executable
-    pub executable: Option<bool>,
+    /// How filename was executed: directly, or through an interpreter
+    pub executable: Option<Executable>,
+    /// Archive member backing filename, when filename is a virtual path into an archive
+    pub member: Option<Arc<ArchiveMember>>,
     /// The matcher that detected the match - shared across fan-out
     pub pattern: Option<Arc<Pattern>>,
     /// Resulting content (bytes or string) - NOT cloned in fan-out
     pub content: Option<Content>,
     /// File encoding read from
     pub encoding: Option<String>,
+    /// Portion of the file to read, from a `:lines` or `@bytes` argument suffix
+    pub range: Option<ContentRange>,
+    /// Content digests, one per algorithm computed
+    pub digests: Option<Vec<Digest>>,
+    /// Structured value parsed from content (JSON, TOML, YAML, CSV)
+    pub parsed: Option<serde_json::Value>,
+    /// Record of running the file: exit status, raw output and duration
+    pub execution: Option<Execution>,
+    /// Live stdout of a streaming execution, in place of content
+    pub output: Option<OutputStream>,
     /// Execution duration information
     pub timestamp: Option<TimestampInfo>,
+    /// Attempts made by a `Retry` wrapper, the successful or last one included
+    pub attempts: Option<u32>,
+    /// Named timing stamps, e.g. `waitStamp`, keyed by each processor's
+    /// configured name
+    pub stamps: BTreeMap<String, TimestampInfo>,
     /// Error with processor field
     pub err: Option<ProcessorError>,
 }
@@ -34,22 +57,133 @@ impl Fop {
             file_or_pattern: file_or_pattern.into().into(),
             filename: None,
             executable: None,
+            member: None,
             pattern: None,
             content: None,
             encoding: None,
+            range: None,
+            digests: None,
+            parsed: None,
+            execution: None,
+            output: None,
             timestamp: None,
+            attempts: None,
+            stamps: BTreeMap::new(),
             err: None,
         }
     }
 }
 
-/// Content of a Fop, either as raw bytes or text.
+/// Content of a Fop, either as raw bytes, text, or a read-only memory map.
 #[derive(Debug, Clone)]
 pub enum Content {
     /// Raw bytes content
     Bytes(Vec<u8>),
     /// Text content
     Text(String),
+    /// Read-only memory-mapped file content, shared between clones
+    Mapped(Arc<memmap2::Mmap>),
+}
+
+impl Content {
+    /// View the content as bytes, whichever variant it is.
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Content::Bytes(bytes) => bytes,
+            Content::Text(text) => text.as_bytes(),
+            Content::Mapped(map) => map,
+        }
+    }
+}
+
+/// A portion of a file, requested with an argument suffix.
+///
+/// | Suffix | Range |
+/// |--------|-------|
+/// | `:10` | line 10 |
+/// | `:10-40` | lines 10 through 40 |
+/// | `:10-` | line 10 to end of file |
+/// | `@0+4096` | 4096 bytes from offset 0 |
+/// | `@512-1024` | bytes 512 up to (not including) 1024 |
+/// | `@512` | offset 512 to end of file |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentRange {
+    /// 1-based, inclusive line range; `end` of None reads to end of file
+    Lines { start: usize, end: Option<usize> },
+    /// Byte range from `offset`; `len` of None reads to end of file
+    Bytes { offset: u64, len: Option<u64> },
+}
+
+impl ContentRange {
+    /// Split a trailing range suffix off an argument.
+    ///
+    /// Returns None when the argument has no range-shaped suffix. A suffix
+    /// that is range-shaped but invalid (e.g. `:40-10`) yields an Err.
+    pub fn split_suffix(input: &str) -> Option<(&str, Result<ContentRange, String>)> {
+        let idx = input.rfind([':', '@'])?;
+        let (path, suffix) = (&input[..idx], &input[idx + 1..]);
+        let is_range_shaped = !suffix.is_empty()
+            && suffix.starts_with(|c: char| c.is_ascii_digit())
+            && suffix
+                .chars()
+                .all(|c| c.is_ascii_digit() || c == '-' || c == '+');
+        if path.is_empty() || !is_range_shaped {
+            return None;
+        }
+
+        let range = if input[idx..].starts_with(':') {
+            Self::parse_lines(suffix)
+        } else {
+            Self::parse_bytes(suffix)
+        };
+        Some((path, range))
+    }
+
+    fn parse_lines(suffix: &str) -> Result<ContentRange, String> {
+        let invalid = || format!("Invalid line range: {}", suffix);
+        let (start, end) = match suffix.split_once('-') {
+            None => {
+                let line = suffix.parse().map_err(|_| invalid())?;
+                (line, Some(line))
+            }
+            Some((start, "")) => (start.parse().map_err(|_| invalid())?, None),
+            Some((start, end)) => (
+                start.parse().map_err(|_| invalid())?,
+                Some(end.parse().map_err(|_| invalid())?),
+            ),
+        };
+        if start == 0 || end.is_some_and(|end| end < start) {
+            return Err(invalid());
+        }
+        Ok(ContentRange::Lines { start, end })
+    }
+
+    fn parse_bytes(suffix: &str) -> Result<ContentRange, String> {
+        let invalid = || format!("Invalid byte range: {}", suffix);
+        if let Some((offset, len)) = suffix.split_once('+') {
+            return Ok(ContentRange::Bytes {
+                offset: offset.parse().map_err(|_| invalid())?,
+                len: Some(len.parse().map_err(|_| invalid())?),
+            });
+        }
+        match suffix.split_once('-') {
+            None => Ok(ContentRange::Bytes {
+                offset: suffix.parse().map_err(|_| invalid())?,
+                len: None,
+            }),
+            Some((offset, end)) => {
+                let offset: u64 = offset.parse().map_err(|_| invalid())?;
+                let end: u64 = end.parse().map_err(|_| invalid())?;
+                if end < offset {
+                    return Err(invalid());
+                }
+                Ok(ContentRange::Bytes {
+                    offset,
+                    len: Some(end - offset),
+                })
+            }
+        }
+    }
 }
 
 /// Pattern matcher type that stores the glob pattern.
@@ -57,6 +191,9 @@ pub enum Content {
 pub struct Pattern {
     /// The glob pattern string
     pub pattern: Arc<str>,
+    /// Regex capturing each wildcard, compiled on first use; None if the
+    /// glob doesn't translate
+    captures: OnceLock<Option<regex::Regex>>,
 }
 
 impl Pattern {
@@ -64,21 +201,127 @@ impl Pattern {
     pub fn new(pattern: impl Into<String>) -> Self {
         Self {
             pattern: pattern.into().into(),
+            captures: OnceLock::new(),
         }
     }
+
+    /// Match a path against this pattern, returning what each wildcard matched.
+    ///
+    /// Index 0 is the whole path; 1.. are the `*`, `**`, `?`, `[...]` and
+    /// `{a,b}` parts of the pattern, left to right; alternatives may nest.
+    /// Returns None if the path does not match.
+    ///
+    /// A backslash before a glob metacharacter, as in `\*`, makes it literal;
+    /// any other backslash is a path separator. A leading `./` on either the
+    /// path or the pattern is ignored, as walks report `*.sh` matches as
+    /// `./top.sh`.
+    pub fn captures(&self, path: &str) -> Option<Vec<String>> {
+        let regex = self
+            .captures
+            .get_or_init(|| regex::Regex::new(&glob_to_regex(strip_dot(&self.pattern), true)).ok())
+            .as_ref()?;
+        let path = path.replace('\\', "/");
+        let captures = regex.captures(strip_dot(&path))?;
+        let mut parts: Vec<String> = captures
+            .iter()
+            .map(|m| m.map(|m| m.as_str().to_string()).unwrap_or_default())
+            .collect();
+        parts[0] = path;
+        Some(parts)
+    }
 }
 
-/// Execution duration information.
-#[derive(Debug, Clone)]
+/// Strip leading `./` components from a path or glob.
+fn strip_dot(path: &str) -> &str {
+    let mut path = path;
+    while let Some(rest) = path.strip_prefix("./") {
+        path = rest.trim_start_matches('/');
+    }
+    path
+}
+
+/// Timing of a span of work on a Fop, such as an execution or a wait.
+///
+/// Spans measured by a stamper carry their start and end instants, and the
+/// mark and measure names its namers gave them.
+#[derive(Debug, Clone, Default)]
 pub struct TimestampInfo {
+    /// When the span started, if it was measured
+    pub start: Option<Instant>,
+    /// When the span finished, if it was measured
+    pub end: Option<Instant>,
     /// Duration in milliseconds
     pub duration_ms: u64,
+    /// Name of the span's start mark, e.g. `fop-build.sh`
+    pub mark: Option<String>,
+    /// Name of the span's measure: the mark and end suffix joined by `-`
+    pub measure: Option<String>,
 }
 
 impl TimestampInfo {
     /// Create new TimestampInfo from duration in milliseconds.
     pub fn new(duration_ms: u64) -> Self {
-        Self { duration_ms }
+        Self {
+            duration_ms,
+            ..Self::default()
+        }
+    }
+
+    /// Create TimestampInfo for a span between two instants.
+    pub fn span(start: Instant, end: Instant) -> Self {
+        Self {
+            start: Some(start),
+            end: Some(end),
+            duration_ms: end.saturating_duration_since(start).as_millis() as u64,
+            ..Self::default()
+        }
+    }
+
+    /// The span's duration, precisely when its instants are known.
+    pub fn duration(&self) -> Duration {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => end.saturating_duration_since(start),
+            _ => Duration::from_millis(self.duration_ms),
+        }
+    }
+}
+
+/// Structured classification of a ProcessorError.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Unclassified error, described only by its message
+    #[default]
+    Other,
+    /// Content failed to parse; positions are 1-based when known
+    Parse {
+        line: Option<usize>,
+        column: Option<usize>,
+    },
+    /// An operation ran past its deadline and was stopped
+    Timeout,
+    /// A requested sandbox could not be applied, so nothing was run
+    Sandbox,
+    /// The sandbox killed a run for writing more than its output cap
+    OutputLimit,
+    /// A trust policy refused to run the file
+    Denied(Denial),
+    /// The pipeline was cancelled before the work finished
+    Cancelled,
+}
+
+impl ErrorKind {
+    /// Short name of the kind, without its details, e.g. for grouping.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorKind::Other => "other",
+            ErrorKind::Parse { .. } => "parse",
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::Sandbox => "sandbox",
+            ErrorKind::OutputLimit => "output_limit",
+            ErrorKind::Denied(_) => "denied",
+            ErrorKind::Cancelled => "cancelled",
+        }
     }
 }
 
@@ -89,6 +332,8 @@ pub struct ProcessorError {
     pub processor: String,
     /// Underlying error
     pub source: String,
+    /// Structured error kind
+    pub kind: ErrorKind,
 }
 
 impl std::fmt::Display for ProcessorError {
@@ -115,8 +360,15 @@ impl ProcessorError {
         Self {
             processor: processor.into(),
             source: source.into(),
+            kind: ErrorKind::Other,
         }
     }
+
+    /// Set the structured error kind.
+    pub fn with_kind(mut self, kind: ErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
 }
 
 #[cfg(test)]
@@ -153,10 +405,162 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_content_as_bytes() {
+        assert_eq!(Content::Bytes(vec![1, 2]).as_bytes(), &[1, 2]);
+        assert_eq!(Content::Text("hi".to_string()).as_bytes(), b"hi");
+    }
+
+    #[test]
+    fn test_content_range_lines() {
+        let (path, range) = ContentRange::split_suffix("src/lib.rs:10-40").unwrap();
+        assert_eq!(path, "src/lib.rs");
+        assert_eq!(
+            range.unwrap(),
+            ContentRange::Lines {
+                start: 10,
+                end: Some(40)
+            }
+        );
+
+        let (_, range) = ContentRange::split_suffix("a.rs:7").unwrap();
+        assert_eq!(
+            range.unwrap(),
+            ContentRange::Lines {
+                start: 7,
+                end: Some(7)
+            }
+        );
+
+        let (_, range) = ContentRange::split_suffix("a.rs:7-").unwrap();
+        assert_eq!(
+            range.unwrap(),
+            ContentRange::Lines {
+                start: 7,
+                end: None
+            }
+        );
+
+        let (_, range) = ContentRange::split_suffix("a.rs:40-10").unwrap();
+        assert!(range.is_err());
+        let (_, range) = ContentRange::split_suffix("a.rs:0").unwrap();
+        assert!(range.is_err());
+    }
+
+    #[test]
+    fn test_content_range_bytes() {
+        let (path, range) = ContentRange::split_suffix("path.bin@0+4096").unwrap();
+        assert_eq!(path, "path.bin");
+        assert_eq!(
+            range.unwrap(),
+            ContentRange::Bytes {
+                offset: 0,
+                len: Some(4096)
+            }
+        );
+
+        let (_, range) = ContentRange::split_suffix("path.bin@512-1024").unwrap();
+        assert_eq!(
+            range.unwrap(),
+            ContentRange::Bytes {
+                offset: 512,
+                len: Some(512)
+            }
+        );
+
+        let (_, range) = ContentRange::split_suffix("path.bin@512").unwrap();
+        assert_eq!(
+            range.unwrap(),
+            ContentRange::Bytes {
+                offset: 512,
+                len: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_content_range_no_suffix() {
+        assert!(ContentRange::split_suffix("src/lib.rs").is_none());
+        assert!(ContentRange::split_suffix("C:foo.txt").is_none());
+        assert!(ContentRange::split_suffix("user@host.txt").is_none());
+        assert!(ContentRange::split_suffix(":10").is_none());
+    }
+
+    #[test]
+    fn test_pattern_captures() {
+        let pattern = Pattern::new("src/**/*.rs");
+        assert_eq!(
+            pattern.captures("src/a/b/lib.rs").unwrap(),
+            vec!["src/a/b/lib.rs", "a/b/", "lib"]
+        );
+        assert_eq!(
+            pattern.captures("src/lib.rs").unwrap(),
+            vec!["src/lib.rs", "", "lib"]
+        );
+        assert!(pattern.captures("lib/lib.rs").is_none());
+        // The regex is compiled on first use and kept for later matches
+        assert!(pattern.captures.get().is_some_and(Option::is_some));
+
+        let pattern = Pattern::new("gen/{build,test}-?.[!x]h");
+        assert_eq!(
+            pattern.captures("gen/test-1.sh").unwrap(),
+            vec!["gen/test-1.sh", "test", "1", "s"]
+        );
+        assert!(pattern.captures("gen/test-1.xh").is_none());
+
+        // Regex metacharacters in the glob are literal
+        let pattern = Pattern::new("a+b.(*)");
+        assert_eq!(pattern.captures("a+b.(x)").unwrap(), vec!["a+b.(x)", "x"]);
+
+        // A walk reports bare patterns' matches under `./`
+        let pattern = Pattern::new("*.sh");
+        assert_eq!(
+            pattern.captures("./top.sh").unwrap(),
+            vec!["./top.sh", "top"]
+        );
+        assert_eq!(pattern.captures("top.sh").unwrap()[1], "top");
+        assert!(pattern.captures("./gen/top.sh").is_none());
+        let pattern = Pattern::new("./gen/*.sh");
+        assert_eq!(pattern.captures("gen/a.sh").unwrap()[1], "a");
+    }
+
+    #[test]
+    fn test_pattern_captures_nested_braces() {
+        let pattern = Pattern::new("{a,{b,c}}/*.rs");
+        assert_eq!(
+            pattern.captures("c/lib.rs").unwrap(),
+            vec!["c/lib.rs", "c", "lib"]
+        );
+        assert_eq!(pattern.captures("a/x.rs").unwrap()[1], "a");
+        assert!(pattern.captures("{b/x.rs").is_none());
+
+        let pattern = Pattern::new("{x,y\\,z}.txt");
+        assert_eq!(pattern.captures("y,z.txt").unwrap()[1], "y,z");
+    }
+
+    #[test]
+    fn test_pattern_captures_escapes() {
+        // Escaped metacharacters are literal, other backslashes separate
+        let pattern = Pattern::new("logs/\\*.txt");
+        assert!(pattern.captures("logs/*.txt").is_some());
+        assert!(pattern.captures("logs/a.txt").is_none());
+
+        let pattern = Pattern::new("src\\lib\\m?in.rs");
+        assert_eq!(pattern.captures("src\\lib\\main.rs").unwrap()[1], "a");
+        assert!(pattern.captures("src/lib/main.rs").is_some());
+    }
+
     #[test]
     fn test_timestamp_info() {
         let info = TimestampInfo::new(100);
         assert_eq!(info.duration_ms, 100);
+        assert_eq!(info.duration(), Duration::from_millis(100));
+        assert!(info.start.is_none());
+
+        let start = Instant::now();
+        let info = TimestampInfo::span(start, start + Duration::from_micros(2500));
+        assert_eq!(info.duration_ms, 2);
+        assert_eq!(info.duration(), Duration::from_micros(2500));
     }
 
     #[test]
@@ -164,5 +568,16 @@ mod tests {
         let err = ProcessorError::new("TestProcessor", "Something went wrong");
         assert_eq!(err.processor, "TestProcessor");
         assert_eq!(err.source, "Something went wrong");
+        assert_eq!(err.kind, ErrorKind::Other);
+    }
+
+    #[test]
+    fn test_processor_error_with_kind() {
+        let kind = ErrorKind::Parse {
+            line: Some(3),
+            column: Some(7),
+        };
+        let err = ProcessorError::new("TestProcessor", "bad input").with_kind(kind.clone());
+        assert_eq!(err.kind, kind);
     }
 }
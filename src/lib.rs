@@ -1,20 +1,44 @@
 //! File Or Pattern - A library for handling file or pattern arguments in CLI programs.
 
+pub mod archive;
 pub mod basic;
+pub mod bound;
 pub mod builder;
 pub mod content;
+pub mod execution;
 pub mod fop;
 pub mod pipelines;
 pub mod processor;
+pub mod progress;
+pub mod resilience;
 pub mod stamper;
+pub mod stats;
 pub mod stream;
 
+pub use archive::{ArchiveKind, ArchiveMember, ArchiveReader};
 pub use basic::{CheckExistProcessor, FsstreamProcessor, ParserProcessor, TinyGlobbyProcessor};
-pub use content::{DoExecuteProcessor, GuardProcessor, ReadContentProcessor};
-pub use fop::{Content, Fop, Pattern, ProcessorError, TimestampInfo};
-pub use processor::{AsyncProcessor, BoundedProcessor, Processor, SemaphoreBoundedProcessor};
+pub use bound::{
+    AdaptiveLimiter, Aimd, Cost, FileSizeCost, FixedCost, Gradient, LimitAlgorithm, TokenBucket,
+};
+pub use content::{
+    CaptureMode, ContentFormat, Denial, Digest, DoExecuteProcessor, Executable, Execution,
+    Executor, FailChecker, GuardProcessor, HashAlgorithm, HashProcessor, HashSource,
+    InterpreterRegistry, OutputMode, OutputStream, ParseContentProcessor, ReadContentProcessor,
+    Sandbox, StdinSource, TrustPolicy, Verdict, WorkingDir,
+};
+pub use fop::{Content, ContentRange, ErrorKind, Fop, Pattern, ProcessorError, TimestampInfo};
+pub use processor::{
+    AdaptiveBoundedProcessor, AsyncProcessor, BoundedProcessor, Processor, RateLimitedProcessor,
+    SemaphoreBoundedProcessor, StreamingProcessor,
+};
+pub use progress::{
+    Progress, ProgressEvent, ProgressReporter, ProgressSnapshot, ReportFormat, ReporterHandle,
+};
+pub use resilience::{Cancellable, Retry, Timeout};
 pub use stamper::{
     DefaultEndSuffixNamer, DefaultStartNamer, EndSuffixNamer, LiteralSuffixNamer,
-    PerformanceMeasureStamper, Stamper, StamperHandle, StamperOptions, StartNamer, TrueStamper,
+    PerformanceMeasureStamper, Span, Stamper, StamperHandle, StamperOptions, StartNamer,
+    TrueStamper,
 };
+pub use stats::{Instrumented, ProcessorSummary, RunStats, RunSummary};
 pub use stream::{apply_bounded, apply_processor, FopStream, FopStreamStatic};
@@ -1,7 +1,10 @@
 //! Processor trait and related types.
 
-use crate::fop::Fop;
-use crate::stamper::Stamper;
+use crate::bound::{self, AdaptiveLimiter, Cost, TokenBucket};
+use crate::fop::{ErrorKind, Fop, ProcessorError};
+use crate::progress::Progress;
+use crate::stamper::{Span, Stamper, StamperOptions};
+use futures::stream::BoxStream;
 use std::future::Future;
 use std::sync::Arc;
 use tokio::sync::Semaphore;
@@ -43,7 +46,11 @@ pub trait Processor: Send + Sync {
 /// This enables asynchronous, non-blocking processing with proper tokio integration.
 pub trait AsyncProcessor: Send + Sync {
     /// Processor name for debugging and error attribution.
-    fn name(&self) -> &'static str;
+    ///
+    /// Wrappers may build theirs from the inner processor's, e.g.
+    /// `Bounded(DoExecuteProcessor)`, and use the same name for their own
+    /// errors.
+    fn name(&self) -> &str;
 
     /// Process a single Fop, potentially producing multiple outputs.
     ///
@@ -54,11 +61,25 @@ pub trait AsyncProcessor: Send + Sync {
     fn process_one(&self, fop: Fop) -> impl Future<Output = Vec<Fop>> + Send;
 }
 
+/// Async trait for processors that fan a Fop out into a stream of results.
+///
+/// Where `process_one` returns every result at once, `process_stream`
+/// yields each as soon as it is produced, so the first match of a large
+/// glob can move down the pipeline while the walk is still running. The
+/// returned stream owns what it needs and may outlive the processor borrow.
+/// See `stream::apply_streaming` for flattening it into a pipeline.
+pub trait StreamingProcessor: AsyncProcessor {
+    /// Process a single Fop, yielding 0..N Fops as they are produced.
+    ///
+    /// Return semantics match `AsyncProcessor::process_one`.
+    fn process_stream(&self, fop: Fop) -> BoxStream<'static, Fop>;
+}
+
 /// Trait for processors that can process items concurrently.
 ///
 /// This trait is implemented by processors that support bounded
 /// execution modes using resource pools.
-pub trait BoundedProcessor: Processor {
+pub trait BoundedProcessor {
     /// Get the bound limit for this processor.
     ///
     /// Returns None if unbounded.
@@ -67,42 +88,91 @@ pub trait BoundedProcessor: Processor {
 
 /// A bounded processor that limits concurrent execution using a semaphore.
 ///
-/// Wraps an inner processor and limits to number of concurrent
-/// executions to a configured pool size using a semaphore.
+/// Wraps an inner processor and limits the number of concurrent
+/// executions to a configured pool size using a semaphore. The semaphore
+/// can be shared between processors, so that several stages draw on one
+/// pool, e.g. reads and executions in an EREb pipeline.
+///
+/// Each Fop takes one permit unless a cost is set, see `with_cost`. With a
+/// wait stamper, the time each Fop waited for its permits is recorded in
+/// `fop.stamps` under the wait name, `waitStamp` by default. A Fop with a
+/// streaming execution's `output` keeps its permits until the execution
+/// ends.
 pub struct SemaphoreBoundedProcessor<P> {
     /// Inner processor being wrapped
     inner: P,
     /// Semaphore for limiting concurrent executions
     semaphore: Arc<Semaphore>,
+    /// Number of permits the semaphore was created with
+    pool_size: usize,
+    /// Optional strategy weighing each Fop in permits
+    cost: Option<Arc<dyn Cost>>,
     /// Optional stamper for measuring wait time
     wait_stamper: Option<Box<dyn Stamper>>,
     /// Field name for wait timestamp
     wait_name: String,
+    /// Optional sink for waits on the bound
+    progress: Option<Progress>,
     /// Name of this processor
     name: String,
 }
 
 impl<P> SemaphoreBoundedProcessor<P>
 where
-    P: Processor + Clone + 'static,
+    P: AsyncProcessor,
 {
     /// Create a new bounded processor wrapping the given inner processor.
     ///
     /// # Arguments
     ///
     /// * `inner` - The inner processor to wrap
-    /// * `pool_size` - Maximum number of concurrent executions allowed
+    /// * `pool_size` - Maximum number of concurrent executions allowed. A
+    ///   `pool_size` of 0 is raised to 1, as no Fop could run otherwise.
     pub fn new(inner: P, pool_size: usize) -> Self {
+        let pool_size = pool_size.max(1);
+        Self::with_pool(inner, Arc::new(Semaphore::new(pool_size)), pool_size)
+    }
+
+    /// Create a bounded processor drawing on an existing semaphore.
+    ///
+    /// `pool_size` is the number of permits the semaphore was created with,
+    /// reported by `bound_limit` and capping costs. It is lowered to the
+    /// permits available when this is called, so that a Fop can never be
+    /// weighed at more permits than the semaphore holds and wait forever.
+    pub fn from_semaphore(inner: P, semaphore: Arc<Semaphore>, pool_size: usize) -> Self {
+        let pool_size = pool_size.min(semaphore.available_permits());
+        Self::with_pool(inner, semaphore, pool_size)
+    }
+
+    /// Create a bounded processor sharing the pool of `other`.
+    pub fn sharing<Q>(inner: P, other: &SemaphoreBoundedProcessor<Q>) -> Self {
+        Self::with_pool(inner, other.semaphore.clone(), other.pool_size)
+    }
+
+    fn with_pool(inner: P, semaphore: Arc<Semaphore>, pool_size: usize) -> Self {
         let name = format!("Bounded({})", inner.name());
         Self {
-            inner: inner.clone(),
-            semaphore: Arc::new(Semaphore::new(pool_size)),
+            inner,
+            semaphore,
+            pool_size,
+            cost: None,
             wait_stamper: None,
             wait_name: "waitStamp".to_string(),
+            progress: None,
             name,
         }
     }
 
+    /// Weigh each Fop in permits, e.g. by file size, instead of one each.
+    ///
+    /// Costs are capped at the pool size, and are at least 1. They are
+    /// computed on the blocking thread pool. A processor sharing this pool
+    /// keeps its own cost, so reads and executions can be weighed apart.
+    pub fn with_cost(mut self, cost: impl Cost + 'static) -> Self {
+        self.cost = Some(Arc::new(cost));
+        self
+    }
+
     /// Set a stamper for measuring wait time.
     pub fn with_wait_stamper(mut self, stamper: impl Stamper + 'static) -> Self {
         self.wait_stamper = Some(Box::new(stamper));
@@ -114,41 +184,288 @@ where
         self.wait_name = name.into();
         self
     }
+
+    /// Report Fops as they start and stop waiting on the bound.
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+}
+
+impl<P> SemaphoreBoundedProcessor<P> {
+    /// The semaphore this processor acquires from, for sharing.
+    pub fn semaphore(&self) -> Arc<Semaphore> {
+        self.semaphore.clone()
+    }
+}
+
+impl<P> AsyncProcessor for SemaphoreBoundedProcessor<P>
+where
+    P: AsyncProcessor,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Wait for a permit, then run the inner processor while holding it.
+    async fn process_one(&self, fop: Fop) -> Vec<Fop> {
+        let waiting = wait_span(self.wait_stamper.as_deref(), &self.name, &fop);
+
+        let limit = u32::try_from(self.pool_size).unwrap_or(u32::MAX);
+        let (permits, mut fop) = match &self.cost {
+            Some(cost) => bound::weigh(cost.clone(), fop, limit).await,
+            None => (1, fop),
+        };
+        let wait = self.progress.as_ref().map(Progress::waiting);
+        let Ok(permit) = self.semaphore.clone().acquire_many_owned(permits).await else {
+            fop.err = Some(ProcessorError::new(
+                self.name.as_str(),
+                "Bound was closed before a permit was granted",
+            ));
+            return vec![fop];
+        };
+        drop(wait);
+
+        if let Some(span) = waiting {
+            span.finish(&mut fop, &self.wait_name);
+        }
+        let results = self.inner.process_one(fop).await;
+        hold_for_output(&results, permit);
+        results
+    }
 }
 
 impl<P> Processor for SemaphoreBoundedProcessor<P>
 where
-    P: Processor + Clone + 'static,
+    P: Processor,
 {
-    /// Process items with semaphore-based bounding.
+    /// Process items through the inner processor.
     ///
-    /// Note: Full implementation is TODO pending resolution of
-    /// async/sync complexity with iterator lifetimes.
+    /// Sync processing is sequential, so the bound is never reached; only
+    /// the async processor waits on the semaphore.
     fn process<'a, I>(&self, input: I) -> impl Iterator<Item = Fop> + 'a
     where
         I: Iterator<Item = Fop> + 'a,
     {
-        input
+        self.inner.process(input)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<P> BoundedProcessor for SemaphoreBoundedProcessor<P> {
+    fn bound_limit(&self) -> Option<usize> {
+        Some(self.pool_size)
+    }
+}
+
+/// A processor that limits throughput using a token bucket.
+///
+/// Wraps an inner processor and lets Fops through at the bucket's rate,
+/// however many are in flight. Buckets are shared by cloning, so several
+/// stages can draw on one budget. With a wait stamper, the time each Fop
+/// waited for its token is recorded in `fop.stamps` under the wait name,
+/// `waitStamp` by default.
+pub struct RateLimitedProcessor<P> {
+    /// Inner processor being wrapped
+    inner: P,
+    /// Bucket tokens are taken from
+    bucket: TokenBucket,
+    /// Optional stamper for measuring wait time
+    wait_stamper: Option<Box<dyn Stamper>>,
+    /// Field name for wait timestamp
+    wait_name: String,
+    /// Optional sink for waits on the bound
+    progress: Option<Progress>,
+    /// Name of this processor
+    name: String,
+}
+
+impl<P> RateLimitedProcessor<P>
+where
+    P: AsyncProcessor,
+{
+    /// Create a rate-limited processor wrapping the given inner processor.
+    pub fn new(inner: P, bucket: TokenBucket) -> Self {
+        let name = format!("RateLimited({})", inner.name());
+        Self {
+            inner,
+            bucket,
+            wait_stamper: None,
+            wait_name: "waitStamp".to_string(),
+            progress: None,
+            name,
+        }
+    }
+
+    /// Set a stamper for measuring wait time.
+    pub fn with_wait_stamper(mut self, stamper: impl Stamper + 'static) -> Self {
+        self.wait_stamper = Some(Box::new(stamper));
+        self
+    }
+
+    /// Set the field name for wait timestamp.
+    pub fn with_wait_name(mut self, name: impl Into<String>) -> Self {
+        self.wait_name = name.into();
+        self
+    }
+
+    /// Report Fops as they start and stop waiting on the bound.
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = Some(progress);
+        self
     }
+}
 
+impl<P> AsyncProcessor for RateLimitedProcessor<P>
+where
+    P: AsyncProcessor,
+{
     fn name(&self) -> &str {
         &self.name
     }
+
+    /// Wait for a token, then run the inner processor.
+    async fn process_one(&self, mut fop: Fop) -> Vec<Fop> {
+        let waiting = wait_span(self.wait_stamper.as_deref(), &self.name, &fop);
+        let wait = self.progress.as_ref().map(Progress::waiting);
+        self.bucket.acquire().await;
+        drop(wait);
+        if let Some(span) = waiting {
+            span.finish(&mut fop, &self.wait_name);
+        }
+        self.inner.process_one(fop).await
+    }
 }
 
-impl<P> BoundedProcessor for SemaphoreBoundedProcessor<P>
+/// A bounded processor whose limit adapts to observed latency.
+///
+/// Wraps an inner processor and bounds it with an `AdaptiveLimiter`, which
+/// sees how long each Fop took. Fops coming back with a `Timeout` error
+/// count as dropped, a sign of overload. The limiter can be shared, and
+/// `bound_limit` reports its current limit. With a wait stamper, the time
+/// each Fop waited is recorded in `fop.stamps` under the wait name,
+/// `waitStamp` by default. A Fop with a streaming execution's `output` keeps
+/// its permit until the execution ends, and its latency runs until then.
+pub struct AdaptiveBoundedProcessor<P> {
+    /// Inner processor being wrapped
+    inner: P,
+    /// Limiter permits are taken from
+    limiter: AdaptiveLimiter,
+    /// Optional stamper for measuring wait time
+    wait_stamper: Option<Box<dyn Stamper>>,
+    /// Field name for wait timestamp
+    wait_name: String,
+    /// Optional sink for waits on the bound
+    progress: Option<Progress>,
+    /// Name of this processor
+    name: String,
+}
+
+impl<P> AdaptiveBoundedProcessor<P>
 where
-    P: Processor + Clone + 'static,
+    P: AsyncProcessor,
 {
+    /// Create an adaptively bounded processor wrapping the given inner processor.
+    pub fn new(inner: P, limiter: AdaptiveLimiter) -> Self {
+        let name = format!("AdaptiveBounded({})", inner.name());
+        Self {
+            inner,
+            limiter,
+            wait_stamper: None,
+            wait_name: "waitStamp".to_string(),
+            progress: None,
+            name,
+        }
+    }
+
+    /// Set a stamper for measuring wait time.
+    pub fn with_wait_stamper(mut self, stamper: impl Stamper + 'static) -> Self {
+        self.wait_stamper = Some(Box::new(stamper));
+        self
+    }
+
+    /// Set the field name for wait timestamp.
+    pub fn with_wait_name(mut self, name: impl Into<String>) -> Self {
+        self.wait_name = name.into();
+        self
+    }
+
+    /// Report Fops as they start and stop waiting on the bound.
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+}
+
+impl<P> AdaptiveBoundedProcessor<P> {
+    /// The limiter this processor acquires from, for sharing and diagnostics.
+    pub fn limiter(&self) -> &AdaptiveLimiter {
+        &self.limiter
+    }
+}
+
+impl<P> AsyncProcessor for AdaptiveBoundedProcessor<P>
+where
+    P: AsyncProcessor,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Wait for a permit, run the inner processor, and report its latency.
+    async fn process_one(&self, mut fop: Fop) -> Vec<Fop> {
+        let waiting = wait_span(self.wait_stamper.as_deref(), &self.name, &fop);
+        let wait = self.progress.as_ref().map(Progress::waiting);
+        let permit = self.limiter.acquire().await;
+        drop(wait);
+        if let Some(span) = waiting {
+            span.finish(&mut fop, &self.wait_name);
+        }
+
+        let results = self.inner.process_one(fop).await;
+        let timed_out = results
+            .iter()
+            .any(|f| f.err.as_ref().is_some_and(|e| e.kind == ErrorKind::Timeout));
+        if timed_out {
+            permit.dropped();
+        } else {
+            hold_for_output(&results, permit);
+        }
+        results
+    }
+}
+
+impl<P> BoundedProcessor for AdaptiveBoundedProcessor<P> {
     fn bound_limit(&self) -> Option<usize> {
-        Some(self.semaphore.available_permits())
+        Some(self.limiter.limit())
+    }
+}
+
+/// Keep a bound's permit until a streaming execution among `results` has
+/// finished, rather than releasing it once the inner processor returned.
+fn hold_for_output(results: &[Fop], permit: impl std::any::Any + Send) {
+    if let Some(output) = results.iter().find_map(|fop| fop.output.as_ref()) {
+        output.hold(permit);
     }
 }
 
+/// Start a span over a Fop's wait on a bound, when a wait stamper is set.
+fn wait_span(stamper: Option<&dyn Stamper>, processor: &str, fop: &Fop) -> Option<Span> {
+    let options = StamperOptions {
+        processor: Some(processor),
+    };
+    stamper.map(|stamper| stamper.span(&options, processor, fop))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::stamper::TrueStamper;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
 
     #[derive(Clone)]
     struct TestProcessor;
@@ -220,28 +537,254 @@ mod tests {
         assert_eq!(unbounded.bound_limit(), None);
     }
 
+    #[derive(Clone)]
+    struct SlowProcessor {
+        running: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    impl SlowProcessor {
+        fn new() -> Self {
+            Self {
+                running: Arc::new(AtomicUsize::new(0)),
+                peak: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+    }
+
+    impl AsyncProcessor for SlowProcessor {
+        fn name(&self) -> &'static str {
+            "SlowProcessor"
+        }
+
+        async fn process_one(&self, fop: Fop) -> Vec<Fop> {
+            let now = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            vec![fop]
+        }
+    }
+
+    async fn run_all<P: AsyncProcessor>(processor: &P, count: usize) -> Vec<Fop> {
+        let fops = (0..count).map(|i| processor.process_one(Fop::new(format!("f{}", i))));
+        futures::future::join_all(fops)
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
     #[test]
     fn test_semaphore_bounded_processor_creation() {
-        let inner = TestProcessor;
-        let bounded = SemaphoreBoundedProcessor::new(inner, 3);
+        let bounded = SemaphoreBoundedProcessor::new(SlowProcessor::new(), 3);
 
-        assert_eq!(bounded.name(), "Bounded(TestProcessor)");
+        assert_eq!(AsyncProcessor::name(&bounded), "Bounded(SlowProcessor)");
         assert_eq!(bounded.bound_limit(), Some(3));
+        assert_eq!(bounded.semaphore().available_permits(), 3);
+
+        // An empty pool would never run anything
+        let bounded = SemaphoreBoundedProcessor::new(SlowProcessor::new(), 0);
+        assert_eq!(bounded.bound_limit(), Some(1));
     }
 
-    #[test]
-    fn test_semaphore_bounded_processor_with_wait_stamper() {
-        let inner = TestProcessor;
-        let bounded = SemaphoreBoundedProcessor::new(inner, 3).with_wait_stamper(TrueStamper);
+    #[tokio::test]
+    async fn test_semaphore_bounded_processor_oversized_pool() {
+        let inner = SlowProcessor::new();
+        let semaphore = Arc::new(Semaphore::new(2));
+        let bounded = SemaphoreBoundedProcessor::from_semaphore(inner.clone(), semaphore, 10)
+            .with_cost(|_: &Fop| 10);
+
+        // Costs are capped at the permits that exist, not the claimed size
+        assert_eq!(bounded.bound_limit(), Some(2));
+        let results = tokio::time::timeout(Duration::from_secs(5), run_all(&bounded, 3))
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(inner.peak.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_semaphore_bounded_processor_bounds_concurrency() {
+        let inner = SlowProcessor::new();
+        let bounded = SemaphoreBoundedProcessor::new(inner.clone(), 2);
 
-        assert_eq!(bounded.name(), "Bounded(TestProcessor)");
+        let results = run_all(&bounded, 6).await;
+
+        assert_eq!(results.len(), 6);
+        assert_eq!(inner.peak.load(Ordering::SeqCst), 2);
+        // The configured size is reported even while permits are held
+        let _held = bounded.semaphore().acquire_owned().await.unwrap();
+        assert_eq!(bounded.bound_limit(), Some(2));
     }
 
-    #[test]
-    fn test_semaphore_bounded_processor_with_wait_name() {
-        let inner = TestProcessor;
-        let bounded = SemaphoreBoundedProcessor::new(inner, 3).with_wait_name("customWait");
+    #[tokio::test]
+    async fn test_semaphore_bounded_processor_sharing() {
+        let inner = SlowProcessor::new();
+        let reads = SemaphoreBoundedProcessor::new(inner.clone(), 2);
+        let execs = SemaphoreBoundedProcessor::sharing(inner.clone(), &reads);
+
+        assert_eq!(execs.bound_limit(), Some(2));
+        tokio::join!(run_all(&reads, 4), run_all(&execs, 4));
+        assert_eq!(inner.peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_semaphore_bounded_processor_with_cost() {
+        let inner = SlowProcessor::new();
+        let heavy = |fop: &Fop| {
+            if fop.file_or_pattern.ends_with("0") {
+                100
+            } else {
+                2
+            }
+        };
+        let bounded = SemaphoreBoundedProcessor::new(inner.clone(), 4).with_cost(heavy);
+
+        let results = run_all(&bounded, 6).await;
+
+        // Light items take half the pool each; the heavy one is capped
+        // at the pool size and runs alone
+        assert_eq!(results.len(), 6);
+        assert_eq!(inner.peak.load(Ordering::SeqCst), 2);
+        assert_eq!(bounded.semaphore().available_permits(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_semaphore_bounded_processor_with_wait_stamper() {
+        let semaphore = Arc::new(Semaphore::new(1));
+        let bounded =
+            SemaphoreBoundedProcessor::from_semaphore(SlowProcessor::new(), semaphore.clone(), 1)
+                .with_wait_stamper(TrueStamper);
+
+        let held = semaphore.clone().acquire_owned().await.unwrap();
+        let release = async {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            drop(held);
+        };
+        let (results, _) = tokio::join!(bounded.process_one(Fop::new("f")), release);
+
+        let waited = &results[0].stamps["waitStamp"];
+        assert!(waited.duration_ms >= 25);
+        assert!(waited.end.unwrap() - waited.start.unwrap() >= Duration::from_millis(25));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_semaphore_bounded_processor_holds_permit_for_streaming() {
+        use crate::content::exec::{DoExecuteProcessor, MockExecutor, OutputMode};
+        use futures::StreamExt;
+
+        let exec = DoExecuteProcessor::new()
+            .with_executor(MockExecutor::with_stdout("a\nb\n"))
+            .with_streaming(OutputMode::Lines);
+        let bounded = SemaphoreBoundedProcessor::new(exec, 1);
+        let mut fop = Fop::new("sh");
+        fop.filename = Some("/bin/sh".into());
+
+        let results = bounded.process_one(fop).await;
+
+        // Held while the output is unread, released at its exit event
+        assert_eq!(bounded.semaphore().available_permits(), 0);
+        let events: Vec<_> = results[0]
+            .output
+            .as_ref()
+            .unwrap()
+            .take()
+            .unwrap()
+            .collect()
+            .await;
+        assert_eq!(events.len(), 3);
+        assert_eq!(bounded.semaphore().available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_semaphore_bounded_processor_with_wait_name() {
+        let bounded = SemaphoreBoundedProcessor::new(SlowProcessor::new(), 3)
+            .with_wait_stamper(TrueStamper)
+            .with_wait_name("customWait");
+
+        let results = bounded.process_one(Fop::new("f")).await;
+
+        assert!(results[0].stamps.contains_key("customWait"));
+        assert!(!results[0].stamps.contains_key("waitStamp"));
+
+        // Without a stamper nothing is recorded
+        let bounded = SemaphoreBoundedProcessor::new(SlowProcessor::new(), 3);
+        let results = bounded.process_one(Fop::new("f")).await;
+        assert!(results[0].stamps.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_processor() {
+        let bucket = TokenBucket::new(20, Duration::from_secs(1)).with_burst(2);
+        let limited =
+            RateLimitedProcessor::new(SlowProcessor::new(), bucket).with_wait_stamper(TrueStamper);
+
+        let started = Instant::now();
+        assert_eq!(limited.name(), "RateLimited(SlowProcessor)");
+        let results = run_all(&limited, 4).await;
+
+        // Two pass at once, the other two wait 50ms and 100ms for tokens
+        assert_eq!(results.len(), 4);
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        let mut waits: Vec<u64> = results
+            .iter()
+            .map(|f| f.stamps["waitStamp"].duration_ms)
+            .collect();
+        waits.sort();
+        assert!(waits[0] < 25 && waits[1] < 25, "{:?}", waits);
+        assert!(waits[3] >= 90, "{:?}", waits);
+    }
+
+    #[derive(Clone)]
+    struct TimingOutProcessor;
+
+    impl AsyncProcessor for TimingOutProcessor {
+        fn name(&self) -> &'static str {
+            "TimingOutProcessor"
+        }
+
+        async fn process_one(&self, mut fop: Fop) -> Vec<Fop> {
+            let err =
+                ProcessorError::new("TimingOutProcessor", "slow").with_kind(ErrorKind::Timeout);
+            fop.err = Some(err);
+            vec![fop]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_bounded_processor() {
+        let inner = SlowProcessor::new();
+        let limiter = AdaptiveLimiter::new(2).with_bounds(1, 4);
+        let bounded = AdaptiveBoundedProcessor::new(inner.clone(), limiter.clone());
+        assert_eq!(bounded.name(), "AdaptiveBounded(SlowProcessor)");
+
+        let results = run_all(&bounded, 8).await;
+
+        // Successes under a full limit raise it to the upper bound
+        assert_eq!(results.len(), 8);
+        assert!(inner.peak.load(Ordering::SeqCst) <= 4);
+        assert_eq!(bounded.bound_limit(), Some(4));
+
+        // Timeouts back it off, and a shared limiter sees that too
+        let timing_out = AdaptiveBoundedProcessor::new(TimingOutProcessor, limiter.clone());
+        for _ in 0..10 {
+            timing_out.process_one(Fop::new("f")).await;
+        }
+        assert_eq!(bounded.bound_limit(), Some(1));
+        assert_eq!(timing_out.limiter().limit(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_semaphore_bounded_processor_closed() {
+        let bounded = SemaphoreBoundedProcessor::new(SlowProcessor::new(), 1);
+        bounded.semaphore().close();
+
+        let results = bounded.process_one(Fop::new("f")).await;
 
-        assert_eq!(bounded.name(), "Bounded(TestProcessor)");
+        let err = results[0].err.as_ref().unwrap();
+        assert_eq!(err.processor, "Bounded(SlowProcessor)");
+        assert_eq!(err.processor, AsyncProcessor::name(&bounded));
     }
 }
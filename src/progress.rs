@@ -0,0 +1,486 @@
+//! Progress events from a running pipeline, and a reporter rendering them.
+
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+/// Events buffered per subscriber before it starts missing them.
+const EVENT_BUFFER: usize = 1024;
+
+/// Default time between reporter status updates.
+const DEFAULT_REPORT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Width of the reporter's progress bar, in characters.
+const BAR_WIDTH: usize = 20;
+
+/// Something that happened in a pipeline, as reported to `Progress`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProgressEvent {
+    /// A pattern started expanding
+    PatternStarted { pattern: Arc<str> },
+    /// A pattern finished expanding, after matching `matched` files
+    PatternFinished { pattern: Arc<str>, matched: u64 },
+    /// A pattern matched a file
+    FileMatched { pattern: Arc<str> },
+    /// Content was read from a file
+    BytesRead(u64),
+    /// An execution started
+    ExecutionStarted,
+    /// An execution finished, failed or was killed
+    ExecutionFinished,
+    /// A Fop started waiting on a bound
+    WaitStarted,
+    /// A Fop stopped waiting on a bound
+    WaitFinished,
+}
+
+/// Totals of the events reported so far, see `Progress::snapshot`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProgressSnapshot {
+    pub patterns_started: u64,
+    pub patterns_finished: u64,
+    pub files_matched: u64,
+    pub bytes_read: u64,
+    /// Executions running now
+    pub executions_running: u64,
+    pub executions_finished: u64,
+    /// Fops waiting on a bound now
+    pub waiting: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    patterns_started: AtomicU64,
+    patterns_finished: AtomicU64,
+    files_matched: AtomicU64,
+    bytes_read: AtomicU64,
+    executions_running: AtomicU64,
+    executions_finished: AtomicU64,
+    waiting: AtomicU64,
+}
+
+/// Shared sink for a pipeline's progress events.
+///
+/// Clone it into each processor with their `with_progress` builder. Each
+/// event bumps atomic counters, read with `snapshot`, and is only sent on
+/// when someone has subscribed, so progress is cheap enough to leave on.
+/// Subscribers that fall behind miss events, but never counts.
+#[derive(Debug, Clone)]
+pub struct Progress {
+    counters: Arc<Counters>,
+    events: broadcast::Sender<ProgressEvent>,
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Self {
+            counters: Arc::default(),
+            events: broadcast::channel(EVENT_BUFFER).0,
+        }
+    }
+
+    /// Receive events reported from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<ProgressEvent> {
+        self.events.subscribe()
+    }
+
+    /// Totals of the events reported so far.
+    pub fn snapshot(&self) -> ProgressSnapshot {
+        let c = &*self.counters;
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        ProgressSnapshot {
+            patterns_started: load(&c.patterns_started),
+            patterns_finished: load(&c.patterns_finished),
+            files_matched: load(&c.files_matched),
+            bytes_read: load(&c.bytes_read),
+            executions_running: load(&c.executions_running),
+            executions_finished: load(&c.executions_finished),
+            waiting: load(&c.waiting),
+        }
+    }
+
+    /// Report an event, e.g. from a custom processor.
+    pub fn report(&self, event: ProgressEvent) {
+        let c = &*self.counters;
+        let add = |counter: &AtomicU64, n: u64| counter.fetch_add(n, Ordering::Relaxed);
+        // Saturating, so an unmatched finish cannot wrap the gauge
+        let sub = |counter: &AtomicU64| {
+            counter
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                .unwrap_or(0)
+        };
+        match &event {
+            ProgressEvent::PatternStarted { .. } => add(&c.patterns_started, 1),
+            ProgressEvent::PatternFinished { .. } => add(&c.patterns_finished, 1),
+            ProgressEvent::FileMatched { .. } => add(&c.files_matched, 1),
+            ProgressEvent::BytesRead(n) => add(&c.bytes_read, *n),
+            ProgressEvent::ExecutionStarted => add(&c.executions_running, 1),
+            ProgressEvent::ExecutionFinished => {
+                add(&c.executions_finished, 1);
+                sub(&c.executions_running)
+            }
+            ProgressEvent::WaitStarted => add(&c.waiting, 1),
+            ProgressEvent::WaitFinished => sub(&c.waiting),
+        };
+        if self.events.receiver_count() > 0 {
+            let _ = self.events.send(event);
+        }
+    }
+
+    /// Report a pattern starting; it finishes when the scope is dropped.
+    pub(crate) fn pattern(&self, pattern: Arc<str>) -> PatternScope {
+        self.report(ProgressEvent::PatternStarted {
+            pattern: pattern.clone(),
+        });
+        PatternScope {
+            progress: self.clone(),
+            pattern,
+            matched: 0,
+        }
+    }
+
+    /// Report an execution starting; it finishes when the scope is dropped.
+    pub(crate) fn execution(&self) -> Scope {
+        self.report(ProgressEvent::ExecutionStarted);
+        Scope {
+            progress: self.clone(),
+            finished: ProgressEvent::ExecutionFinished,
+        }
+    }
+
+    /// Report a wait on a bound starting; it finishes when the scope is dropped.
+    pub(crate) fn waiting(&self) -> Scope {
+        self.report(ProgressEvent::WaitStarted);
+        Scope {
+            progress: self.clone(),
+            finished: ProgressEvent::WaitFinished,
+        }
+    }
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reports its finishing event when dropped, so cancelled work is counted.
+pub(crate) struct Scope {
+    progress: Progress,
+    finished: ProgressEvent,
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        self.progress.report(self.finished.clone());
+    }
+}
+
+/// A pattern being expanded; reports its matches, and finishes when dropped.
+pub(crate) struct PatternScope {
+    progress: Progress,
+    pattern: Arc<str>,
+    matched: u64,
+}
+
+impl PatternScope {
+    pub(crate) fn matched(&mut self) {
+        self.matched += 1;
+        self.progress.report(ProgressEvent::FileMatched {
+            pattern: self.pattern.clone(),
+        });
+    }
+}
+
+impl Drop for PatternScope {
+    fn drop(&mut self) {
+        self.progress.report(ProgressEvent::PatternFinished {
+            pattern: self.pattern.clone(),
+            matched: self.matched,
+        });
+    }
+}
+
+/// How a `ProgressReporter` renders status.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// A progress bar redrawn in place, for terminals
+    #[default]
+    Bar,
+    /// One JSON object per line, for machines
+    JsonLines,
+}
+
+/// Renders a pipeline's progress to stderr until finished.
+///
+/// # Example
+///
+/// ```ignore
+/// let progress = Progress::new();
+/// let reporter = ProgressReporter::new(progress.clone()).spawn();
+/// // ... run a pipeline whose processors report to `progress` ...
+/// reporter.finish().await;
+/// ```
+pub struct ProgressReporter {
+    progress: Progress,
+    format: ReportFormat,
+    interval: Duration,
+    writer: Option<Arc<Mutex<dyn Write + Send>>>,
+}
+
+impl ProgressReporter {
+    /// Report as a progress bar every 250ms.
+    pub fn new(progress: Progress) -> Self {
+        Self {
+            progress,
+            format: ReportFormat::default(),
+            interval: DEFAULT_REPORT_INTERVAL,
+            writer: None,
+        }
+    }
+
+    pub fn with_format(mut self, format: ReportFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set the time between status updates.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Write to a shared writer instead of stderr.
+    pub fn with_writer(mut self, writer: Arc<Mutex<dyn Write + Send>>) -> Self {
+        self.writer = Some(writer);
+        self
+    }
+
+    /// Start reporting in the background.
+    pub fn spawn(self) -> ReporterHandle {
+        let stop = CancellationToken::new();
+        let task = tokio::spawn(self.run(stop.clone()));
+        ReporterHandle { stop, task }
+    }
+
+    async fn run(self, stop: CancellationToken) {
+        let started = Instant::now();
+        let mut ticker = tokio::time::interval(self.interval);
+        loop {
+            let done = tokio::select! {
+                _ = ticker.tick() => false,
+                _ = stop.cancelled() => true,
+            };
+            let status = self.render(&self.progress.snapshot(), started.elapsed(), done);
+            match &self.writer {
+                Some(writer) => {
+                    let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+                    let _ = writer.write_all(status.as_bytes());
+                    let _ = writer.flush();
+                }
+                None => {
+                    let _ = std::io::stderr().lock().write_all(status.as_bytes());
+                }
+            }
+            if done {
+                break;
+            }
+        }
+    }
+
+    fn render(&self, s: &ProgressSnapshot, elapsed: Duration, done: bool) -> String {
+        match self.format {
+            ReportFormat::Bar => {
+                // Counters are read one by one, so finished may pass started
+                let filled = if s.patterns_started == 0 {
+                    0
+                } else {
+                    (BAR_WIDTH * s.patterns_finished as usize / s.patterns_started as usize)
+                        .min(BAR_WIDTH)
+                };
+                format!(
+                    "\r\x1b[K[{}{}] {}/{} patterns, {} files, {} read, {} running, {} waiting{}",
+                    "=".repeat(filled),
+                    " ".repeat(BAR_WIDTH - filled),
+                    s.patterns_finished,
+                    s.patterns_started,
+                    s.files_matched,
+                    human_bytes(s.bytes_read),
+                    s.executions_running,
+                    s.waiting,
+                    if done { "\n" } else { "" }
+                )
+            }
+            ReportFormat::JsonLines => {
+                let status = serde_json::json!({
+                    "elapsed_ms": elapsed.as_millis() as u64,
+                    "patterns_started": s.patterns_started,
+                    "patterns_finished": s.patterns_finished,
+                    "files_matched": s.files_matched,
+                    "bytes_read": s.bytes_read,
+                    "executions_running": s.executions_running,
+                    "executions_finished": s.executions_finished,
+                    "waiting": s.waiting,
+                    "done": done,
+                });
+                format!("{}\n", status)
+            }
+        }
+    }
+}
+
+/// A running `ProgressReporter`; stops reporting when finished or dropped.
+pub struct ReporterHandle {
+    stop: CancellationToken,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl ReporterHandle {
+    /// Write a final status and stop reporting.
+    pub async fn finish(mut self) {
+        self.stop.cancel();
+        let _ = (&mut self.task).await;
+    }
+}
+
+impl Drop for ReporterHandle {
+    fn drop(&mut self) {
+        self.stop.cancel();
+    }
+}
+
+fn human_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_progress_counts_and_events() {
+        let progress = Progress::new();
+        // Reporting without subscribers only counts
+        progress.report(ProgressEvent::BytesRead(10));
+
+        let mut events = progress.subscribe();
+        {
+            let mut pattern = progress.pattern(Arc::from("*.rs"));
+            pattern.matched();
+            pattern.matched();
+            let _running = progress.execution();
+            let _waiting = progress.waiting();
+            let snapshot = progress.snapshot();
+            assert_eq!(snapshot.executions_running, 1);
+            assert_eq!(snapshot.waiting, 1);
+        }
+
+        assert_eq!(
+            progress.snapshot(),
+            ProgressSnapshot {
+                patterns_started: 1,
+                patterns_finished: 1,
+                files_matched: 2,
+                bytes_read: 10,
+                executions_running: 0,
+                executions_finished: 1,
+                waiting: 0,
+            }
+        );
+        // Unmatched finishes do not wrap the gauges
+        progress.report(ProgressEvent::ExecutionFinished);
+        progress.report(ProgressEvent::WaitFinished);
+        assert_eq!(progress.snapshot().executions_running, 0);
+        assert_eq!(progress.snapshot().waiting, 0);
+
+        let pattern: Arc<str> = Arc::from("*.rs");
+        assert_eq!(
+            events.recv().await.unwrap(),
+            ProgressEvent::PatternStarted {
+                pattern: pattern.clone()
+            }
+        );
+        let mut last = None;
+        while let Ok(event) = events.try_recv() {
+            if matches!(event, ProgressEvent::PatternFinished { .. }) {
+                last = Some(event);
+            }
+        }
+        assert_eq!(
+            last,
+            Some(ProgressEvent::PatternFinished {
+                pattern,
+                matched: 2
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reporter_json_lines() {
+        let progress = Progress::new();
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let reporter = ProgressReporter::new(progress.clone())
+            .with_format(ReportFormat::JsonLines)
+            .with_interval(Duration::from_secs(60))
+            .with_writer(output.clone())
+            .spawn();
+        progress.report(ProgressEvent::BytesRead(2048));
+        reporter.finish().await;
+
+        let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+        let last: serde_json::Value =
+            serde_json::from_str(output.lines().last().unwrap()).unwrap();
+        assert_eq!(last["bytes_read"], 2048);
+        assert_eq!(last["done"], true);
+    }
+
+    #[test]
+    fn test_reporter_bar() {
+        let reporter = ProgressReporter::new(Progress::new());
+        let snapshot = ProgressSnapshot {
+            patterns_started: 4,
+            patterns_finished: 1,
+            files_matched: 12,
+            bytes_read: 3 << 20,
+            executions_running: 2,
+            ..Default::default()
+        };
+
+        let line = reporter.render(&snapshot, Duration::ZERO, false);
+
+        assert_eq!(
+            line,
+            "\r\x1b[K[=====               ] 1/4 patterns, 12 files, 3.0 MiB read, 2 running, 0 waiting"
+        );
+        assert!(reporter.render(&snapshot, Duration::ZERO, true).ends_with('\n'));
+        assert_eq!(human_bytes(512), "512 B");
+
+        // More finished than started stays a full bar
+        let overrun = ProgressSnapshot {
+            patterns_started: 1,
+            patterns_finished: 2,
+            ..Default::default()
+        };
+        let line = reporter.render(&overrun, Duration::ZERO, false);
+        assert!(
+            line.starts_with("\r\x1b[K[====================] 2/1"),
+            "{:?}",
+            line
+        );
+    }
+}
@@ -0,0 +1,377 @@
+//! Wrappers making any processor resilient to slow, flaky or cancelled work.
+//!
+//! Each wrapper takes the name of the processor it wraps, so its errors and
+//! run statistics are attributed to the work itself.
+
+use crate::fop::{ErrorKind, Fop, ProcessorError};
+use crate::processor::AsyncProcessor;
+use std::hash::BuildHasher;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Bounds the time an inner processor may spend on each Fop.
+///
+/// When the deadline passes, the inner work is dropped, which also kills
+/// any process it was running, and the input Fop comes back with a
+/// `Timeout` error. That Fop is the input as it arrived, every field kept,
+/// with only the error attached.
+///
+/// A streaming execution returns as soon as it starts, so only its start is
+/// bounded; use `DoExecuteProcessor::with_timeout` to bound the run itself.
+pub struct Timeout<P> {
+    inner: P,
+    timeout: Duration,
+}
+
+impl<P> Timeout<P>
+where
+    P: AsyncProcessor,
+{
+    /// Give `inner` at most `timeout` for each Fop.
+    pub fn new(inner: P, timeout: Duration) -> Self {
+        Self { inner, timeout }
+    }
+}
+
+impl<P> AsyncProcessor for Timeout<P>
+where
+    P: AsyncProcessor,
+{
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn process_one(&self, fop: Fop) -> Vec<Fop> {
+        // Kept to report the timeout on, since the inner work owns `fop`
+        let mut original = fop.clone();
+        match tokio::time::timeout(self.timeout, self.inner.process_one(fop)).await {
+            Ok(results) => results,
+            Err(_) => {
+                let err = ProcessorError::new(
+                    self.inner.name(),
+                    format!("Timed out after {:?}", self.timeout),
+                )
+                .with_kind(ErrorKind::Timeout);
+                original.err = Some(err);
+                vec![original]
+            }
+        }
+    }
+}
+
+/// Abandons an inner processor's work when a pipeline-wide token is
+/// cancelled, e.g. on Ctrl-C.
+///
+/// In-flight work is dropped, which kills any process it was running and
+/// releases any bound it holds or waits on, and the input Fop comes back
+/// with a `Cancelled` error, as for `Timeout`. Fops arriving after
+/// cancellation are marked without being processed.
+pub struct Cancellable<P> {
+    inner: P,
+    token: CancellationToken,
+}
+
+impl<P> Cancellable<P>
+where
+    P: AsyncProcessor,
+{
+    /// Abandon `inner`'s work once `token` is cancelled.
+    pub fn new(inner: P, token: CancellationToken) -> Self {
+        Self { inner, token }
+    }
+
+    fn cancelled(&self, mut fop: Fop) -> Vec<Fop> {
+        let err =
+            ProcessorError::new(self.inner.name(), "Cancelled").with_kind(ErrorKind::Cancelled);
+        fop.err = Some(err);
+        vec![fop]
+    }
+}
+
+impl<P> AsyncProcessor for Cancellable<P>
+where
+    P: AsyncProcessor,
+{
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn process_one(&self, fop: Fop) -> Vec<Fop> {
+        if self.token.is_cancelled() {
+            return self.cancelled(fop);
+        }
+        let original = fop.clone();
+        tokio::select! {
+            biased;
+            _ = self.token.cancelled() => self.cancelled(original),
+            results = self.inner.process_one(fop) => results,
+        }
+    }
+}
+
+type RetryIf = dyn Fn(&ErrorKind) -> bool + Send + Sync;
+
+/// Retries an inner processor on transient errors, with exponential
+/// backoff and jitter.
+///
+/// An attempt is retried when any Fop it produced has an error the
+/// predicate accepts; by default only timeouts are retried. All output of a
+/// retried attempt is discarded, and each retry starts from the input Fop.
+/// Fops that already carry an error are passed to the inner processor once.
+/// The number of attempts made is recorded in `fop.attempts`.
+pub struct Retry<P> {
+    inner: P,
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    retry_if: Arc<RetryIf>,
+}
+
+impl<P> Retry<P>
+where
+    P: AsyncProcessor,
+{
+    /// Retry up to 3 attempts in all, starting with a 100ms backoff.
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            retry_if: Arc::new(|kind| *kind == ErrorKind::Timeout),
+        }
+    }
+
+    /// Make at most `attempts` attempts, the first included.
+    pub fn with_max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Wait `initial` before the first retry, doubling for each further
+    /// retry up to `max`. Each wait is jittered down by up to half.
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max.max(initial);
+        self
+    }
+
+    /// Retry errors whose kind satisfies `predicate`, instead of timeouts.
+    pub fn with_retry_if(
+        mut self,
+        predicate: impl Fn(&ErrorKind) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.retry_if = Arc::new(predicate);
+        self
+    }
+
+    /// Wait before retry number `retry`, counting from 1.
+    fn backoff(&self, retry: u32) -> Duration {
+        let base = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry - 1))
+            .min(self.max_backoff);
+        // Random in [0.5, 1), so concurrent retries spread out
+        let random = std::collections::hash_map::RandomState::new().hash_one(retry);
+        let jitter = 0.5 + (random >> 11) as f64 / (1u64 << 53) as f64 / 2.0;
+        base.mul_f64(jitter)
+    }
+
+    fn retryable(&self, results: &[Fop]) -> bool {
+        results
+            .iter()
+            .filter_map(|fop| fop.err.as_ref())
+            .any(|err| (self.retry_if)(&err.kind))
+    }
+}
+
+impl<P> AsyncProcessor for Retry<P>
+where
+    P: AsyncProcessor,
+{
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn process_one(&self, fop: Fop) -> Vec<Fop> {
+        let max_attempts = if fop.err.is_some() { 1 } else { self.max_attempts };
+        let mut attempt = 1;
+        let mut results = loop {
+            let results = self.inner.process_one(fop.clone()).await;
+            if attempt >= max_attempts || !self.retryable(&results) {
+                break results;
+            }
+            tokio::time::sleep(self.backoff(attempt)).await;
+            attempt += 1;
+        };
+        for result in &mut results {
+            result.attempts = Some(attempt);
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Fails with the given kind until it has been called `failures` times.
+    struct Flaky {
+        calls: AtomicU32,
+        failures: u32,
+        kind: ErrorKind,
+    }
+
+    impl Flaky {
+        fn new(failures: u32, kind: ErrorKind) -> Self {
+            Self {
+                calls: AtomicU32::new(0),
+                failures,
+                kind,
+            }
+        }
+    }
+
+    impl AsyncProcessor for Flaky {
+        fn name(&self) -> &'static str {
+            "Flaky"
+        }
+
+        async fn process_one(&self, mut fop: Fop) -> Vec<Fop> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                let err = ProcessorError::new("Flaky", "try again").with_kind(self.kind.clone());
+                fop.err = Some(err);
+            }
+            vec![fop]
+        }
+    }
+
+    struct Sleepy(Duration);
+
+    impl AsyncProcessor for Sleepy {
+        fn name(&self) -> &'static str {
+            "Sleepy"
+        }
+
+        async fn process_one(&self, fop: Fop) -> Vec<Fop> {
+            tokio::time::sleep(self.0).await;
+            vec![fop]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancellable() {
+        let token = CancellationToken::new();
+        let bounded =
+            crate::processor::SemaphoreBoundedProcessor::new(Sleepy(Duration::from_secs(5)), 1);
+        let semaphore = bounded.semaphore();
+        let processor = Arc::new(Cancellable::new(bounded, token.clone()));
+
+        // One Fop holds the permit, the other waits for it
+        let tasks: Vec<_> = ["running", "waiting"]
+            .into_iter()
+            .map(|name| {
+                let processor = processor.clone();
+                tokio::spawn(async move { processor.process_one(Fop::new(name)).await })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        token.cancel();
+
+        for task in tasks {
+            let results = tokio::time::timeout(Duration::from_secs(1), task)
+                .await
+                .unwrap()
+                .unwrap();
+            let err = results[0].err.as_ref().unwrap();
+            assert_eq!(err.kind, ErrorKind::Cancelled);
+            assert_eq!(err.processor, "Bounded(Sleepy)");
+        }
+        assert_eq!(semaphore.available_permits(), 1);
+
+        let results = processor.process_one(Fop::new("late")).await;
+        assert_eq!(results[0].err.as_ref().unwrap().kind, ErrorKind::Cancelled);
+    }
+
+    fn quick<P: AsyncProcessor>(inner: P) -> Retry<P> {
+        Retry::new(inner).with_backoff(Duration::from_millis(1), Duration::from_millis(5))
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let slow = Timeout::new(Sleepy(Duration::from_secs(5)), Duration::from_millis(20));
+        assert_eq!(slow.name(), "Sleepy");
+        let mut fop = Fop::new("slow");
+        fop.filename = Some("slow.txt".into());
+        fop.content = Some(crate::fop::Content::Text("large".to_string()));
+        let results = slow.process_one(fop).await;
+
+        let err = results[0].err.as_ref().unwrap();
+        assert_eq!(err.kind, ErrorKind::Timeout);
+        assert_eq!(err.processor, "Sleepy");
+        assert_eq!(&*results[0].file_or_pattern, "slow");
+        assert_eq!(results[0].filename, Some("slow.txt".into()));
+        // The rest of the input is kept alongside the error
+        assert_eq!(results[0].content.as_ref().unwrap().as_bytes(), b"large");
+
+        let fast = Timeout::new(Sleepy(Duration::ZERO), Duration::from_secs(5));
+        assert!(fast.process_one(Fop::new("fast")).await[0].err.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_retry_until_success() {
+        let retry = quick(Flaky::new(2, ErrorKind::Timeout));
+
+        let results = retry.process_one(Fop::new("f")).await;
+
+        assert!(results[0].err.is_none());
+        assert_eq!(results[0].attempts, Some(3));
+        assert_eq!(retry.inner.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_gives_up() {
+        let retry = quick(Flaky::new(10, ErrorKind::Timeout)).with_max_attempts(4);
+
+        let results = retry.process_one(Fop::new("f")).await;
+
+        assert_eq!(results[0].err.as_ref().unwrap().kind, ErrorKind::Timeout);
+        assert_eq!(results[0].attempts, Some(4));
+    }
+
+    #[tokio::test]
+    async fn test_retry_predicate() {
+        // Other errors are not retried by default
+        let retry = quick(Flaky::new(1, ErrorKind::Other));
+        assert_eq!(retry.process_one(Fop::new("f")).await[0].attempts, Some(1));
+
+        let retry = quick(Flaky::new(1, ErrorKind::Other)).with_retry_if(|_| true);
+        let results = retry.process_one(Fop::new("f")).await;
+        assert!(results[0].err.is_none());
+        assert_eq!(results[0].attempts, Some(2));
+
+        // Errors from earlier stages are not retried
+        let retry = quick(Flaky::new(0, ErrorKind::Timeout));
+        let mut fop = Fop::new("f");
+        fop.err = Some(ProcessorError::new("Earlier", "failed").with_kind(ErrorKind::Timeout));
+        assert_eq!(retry.process_one(fop).await[0].attempts, Some(1));
+    }
+
+    #[test]
+    fn test_retry_backoff() {
+        let retry = Retry::new(Sleepy(Duration::ZERO))
+            .with_backoff(Duration::from_millis(100), Duration::from_millis(300));
+
+        for _ in 0..20 {
+            let first = retry.backoff(1);
+            assert!(first >= Duration::from_millis(50) && first < Duration::from_millis(100));
+            let second = retry.backoff(2);
+            assert!(second >= Duration::from_millis(100) && second < Duration::from_millis(200));
+            // Capped at the maximum
+            let tenth = retry.backoff(10);
+            assert!(tenth >= Duration::from_millis(150) && tenth < Duration::from_millis(300));
+        }
+    }
+}
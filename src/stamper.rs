@@ -1,8 +1,14 @@
 //! Stamper trait and implementations.
 //!
 //! Stampers generate supplemental execution information about pipeline processing.
+//!
+//! Processors stamp their work as a [`Span`]: started before the work, and
+//! finished after it into a named entry of `fop.stamps`, such as
+//! `executionStamp`, `waitStamp` or `readStamp`.
 
 use crate::fop::{Fop, TimestampInfo};
+use std::any::Any;
+use std::time::Instant;
 
 /// Options passed to stamper start method.
 #[derive(Debug, Clone, Default)]
@@ -65,13 +71,15 @@ impl<T> Default for StamperHandle<T> {
 /// Trait for generating supplemental execution information.
 ///
 /// Stampers are called to measure operations and generate metadata
-/// about pipeline processing. They support deferred completion through
-/// StamperHandle promise-like interface.
+/// about pipeline processing. Processors time their work with a [`Span`],
+/// whose StamperHandle promise resolves when the span finishes.
 pub trait Stamper: Send + Sync {
-    /// Start a stamper operation.
+    /// Start a stamper operation, e.g. place a start mark.
     ///
-    /// Returns a StamperHandle with a promise that can be resolved
-    /// or rejected when the operation completes.
+    /// The returned handle holds its own resolver, so awaiting its promise
+    /// only completes once something resolves it. Stampers resolve it
+    /// before returning; deferred completion is what [`Stamper::span`] is
+    /// for, resolving its handle when the span finishes.
     ///
     /// # Arguments
     ///
@@ -81,13 +89,70 @@ pub trait Stamper: Send + Sync {
     ///
     /// # Returns
     ///
-    /// A StamperHandle with a promise-like interface for deferred completion.
+    /// A StamperHandle whose promise is ready to await.
     fn start(
         &self,
         options: &StamperOptions,
         processor_name: &str,
         fop: &Fop,
     ) -> StamperHandle<Box<dyn std::any::Any + Send + Sync>>;
+
+    /// Start a span around a processor's work on a Fop.
+    ///
+    /// Takes the same arguments as [`Stamper::start`]. The span must be
+    /// finished with [`Span::finish`] once the work is done.
+    fn span(&self, options: &StamperOptions, processor_name: &str, fop: &Fop) -> Span {
+        Span::new(self.start(options, processor_name, fop))
+    }
+}
+
+/// A started stamp, timing a processor's work on a Fop until finished.
+#[derive(Debug)]
+pub struct Span {
+    handle: StamperHandle<Box<dyn Any + Send + Sync>>,
+    start: Instant,
+    precise: minstant::Instant,
+    mark: Option<String>,
+    measure: Option<String>,
+}
+
+impl Span {
+    /// Start a span now, resolving `handle` when it finishes.
+    pub fn new(handle: StamperHandle<Box<dyn Any + Send + Sync>>) -> Self {
+        Self {
+            handle,
+            start: Instant::now(),
+            precise: minstant::Instant::now(),
+            mark: None,
+            measure: None,
+        }
+    }
+
+    /// Name the span's start mark and measure.
+    pub fn with_names(mut self, mark: impl Into<String>, measure: impl Into<String>) -> Self {
+        self.mark = Some(mark.into());
+        self.measure = Some(measure.into());
+        self
+    }
+
+    /// Take the handle's promise, to await the span's resolution elsewhere.
+    pub fn take_promise(&mut self) -> tokio::sync::oneshot::Receiver<Box<dyn Any + Send + Sync>> {
+        std::mem::replace(&mut self.handle.promise, tokio::sync::oneshot::channel().1)
+    }
+
+    /// Finish the span, recording it in `fop.stamps` under `name`.
+    ///
+    /// The handle is resolved with the recorded TimestampInfo, unless the
+    /// stamper already resolved it.
+    pub fn finish(mut self, fop: &mut Fop, name: &str) -> TimestampInfo {
+        let duration = self.precise.elapsed();
+        let mut info = TimestampInfo::span(self.start, self.start + duration);
+        info.mark = self.mark;
+        info.measure = self.measure;
+        let _ = self.handle.resolve(Box::new(info.clone()));
+        fop.stamps.insert(name.to_string(), info.clone());
+        info
+    }
 }
 
 /// Strategy for generating start mark names.
@@ -163,7 +228,10 @@ impl Stamper for TrueStamper {
 
 /// Performance measure stamper using minstant for high-precision timing.
 ///
-/// Records start time and calculates duration when resolved.
+/// Its spans are named by the start namer, e.g. `fop-build.sh`, with the
+/// end suffix joined by `-` for the measure, e.g. `fop-build.sh-end`. A
+/// span's handle resolves to its TimestampInfo when it finishes; a handle
+/// from `start` alone resolves at once to the start mark.
 #[derive(Debug)]
 pub struct PerformanceMeasureStamper {
     start_namer: Box<dyn StartNamer>,
@@ -202,24 +270,20 @@ impl Stamper for PerformanceMeasureStamper {
         _processor_name: &str,
         fop: &Fop,
     ) -> StamperHandle<Box<dyn std::any::Any + Send + Sync>> {
-        let start_time = minstant::Instant::now();
-        let _start_name = self.start_namer.name(fop);
-        let _end_suffix = self.end_suffix_namer.suffix(fop);
-
-        let (resolve_tx, promise) = tokio::sync::oneshot::channel();
-
-        let _ = tokio::task::spawn_blocking(move || {
-            let end_time = minstant::Instant::now();
-            let duration_ms = end_time.duration_since(start_time).as_millis() as u64;
-            resolve_tx
-                .send(Box::new(TimestampInfo::new(duration_ms))
-                    as Box<dyn std::any::Any + Send + Sync>)
-        });
+        let info = TimestampInfo {
+            start: Some(Instant::now()),
+            mark: Some(self.start_namer.name(fop)),
+            ..TimestampInfo::default()
+        };
+        let mut handle = StamperHandle::new();
+        let _ = handle.resolve(Box::new(info) as Box<dyn std::any::Any + Send + Sync>);
+        handle
+    }
 
-        StamperHandle {
-            promise,
-            resolve_tx: None,
-        }
+    fn span(&self, _options: &StamperOptions, _processor_name: &str, fop: &Fop) -> Span {
+        let mark = self.start_namer.name(fop);
+        let measure = format!("{}-{}", mark, self.end_suffix_namer.suffix(fop));
+        Span::new(StamperHandle::new()).with_names(mark, measure)
     }
 }
 
@@ -362,18 +426,42 @@ mod tests {
         assert_eq!(namer.suffix(&fop), "custom-suffix");
     }
 
-    #[tokio::test]
-    async fn test_performance_measure_stamper_default() {
+    fn finish(stamper: &dyn Stamper, fop: &mut Fop) -> TimestampInfo {
+        let span = stamper.span(&StamperOptions::default(), "TestProcessor", fop);
+        span.finish(fop, "executionStamp")
+    }
+
+    #[test]
+    fn test_true_stamper_span() {
+        let mut fop = Fop::new("test.txt");
+        let info = finish(&TrueStamper, &mut fop);
+
+        assert!(info.start.unwrap() <= info.end.unwrap());
+        assert!(info.mark.is_none());
+        assert!(fop.stamps.contains_key("executionStamp"));
+    }
+
+    #[test]
+    fn test_performance_measure_stamper_default() {
         let stamper = PerformanceMeasureStamper::new();
         let fop = Fop::new("test.txt");
-        let options = StamperOptions::default();
 
-        let handle = stamper.start(&options, "TestProcessor", &fop);
-        let _ = handle.promise.await;
+        // A span's handle is unresolved until the span finishes
+        let mut span = stamper.span(&StamperOptions::default(), "TestProcessor", &fop);
+        assert!(span.take_promise().try_recv().is_err());
+
+        let mut fop = fop;
+        let info = finish(&stamper, &mut fop);
+        assert_eq!(info.mark.as_deref(), Some("fop-test.txt"));
+        assert_eq!(info.measure.as_deref(), Some("fop-test.txt-end"));
+        assert_eq!(
+            fop.stamps["executionStamp"].measure.as_deref(),
+            Some("fop-test.txt-end")
+        );
     }
 
-    #[tokio::test]
-    async fn test_performance_measure_stamper_with_custom_namers() {
+    #[test]
+    fn test_performance_measure_stamper_with_custom_namers() {
         #[derive(Debug)]
         struct CustomStartNamer;
 
@@ -396,43 +484,55 @@ mod tests {
             .with_start_namer(CustomStartNamer)
             .with_end_suffix_namer(CustomEndNamer);
 
-        let fop = Fop::new("test.txt");
-        let options = StamperOptions::default();
-
-        let handle = stamper.start(&options, "TestProcessor", &fop);
-        let _ = handle.promise.await;
+        let mut fop = Fop::new("test.txt");
+        let info = finish(&stamper, &mut fop);
+        assert_eq!(info.mark.as_deref(), Some("custom-test.txt"));
+        assert_eq!(info.measure.as_deref(), Some("custom-test.txt-custom-end"));
     }
 
     #[tokio::test]
     async fn test_performance_measure_stamper_timing() {
         let stamper = PerformanceMeasureStamper::new();
-        let fop = Fop::new("test.txt");
-        let options = StamperOptions::default();
-
-        let handle = stamper.start(&options, "TestProcessor", &fop);
+        let mut fop = Fop::new("test.txt");
 
+        let mut span = stamper.span(&StamperOptions::default(), "TestProcessor", &fop);
+        let promise = span.take_promise();
         tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        let info = span.finish(&mut fop, "readStamp");
 
-        let result = handle.promise.await;
-        assert!(result.is_ok());
+        assert!(info.duration_ms >= 10);
+        assert!(info.duration() >= std::time::Duration::from_millis(10));
+        assert!(fop.stamps.contains_key("readStamp"));
 
-        let value = result.unwrap();
-        if let Some(timestamp) = value.downcast_ref::<TimestampInfo>() {
-            assert!(timestamp.duration_ms >= 0);
-        } else {
-            panic!("Expected TimestampInfo");
-        }
+        let value = promise.await.unwrap();
+        let resolved = value.downcast_ref::<TimestampInfo>().unwrap();
+        assert_eq!(resolved.end, info.end);
+        assert_eq!(resolved.mark.as_deref(), Some("fop-test.txt"));
     }
 
     #[tokio::test]
-    async fn test_performance_measure_stamper_literal_suffix() {
+    async fn test_performance_measure_stamper_start() {
+        let stamper = PerformanceMeasureStamper::new();
+        let fop = Fop::new("test.txt");
+
+        // Without a span there is nothing to wait for
+        let handle = stamper.start(&StamperOptions::default(), "TestProcessor", &fop);
+        let value = tokio::time::timeout(std::time::Duration::from_secs(1), handle.promise)
+            .await
+            .expect("start should resolve its handle")
+            .unwrap();
+        let mark = value.downcast_ref::<TimestampInfo>().unwrap();
+        assert_eq!(mark.mark.as_deref(), Some("fop-test.txt"));
+        assert!(mark.start.is_some() && mark.end.is_none());
+    }
+
+    #[test]
+    fn test_performance_measure_stamper_literal_suffix() {
         let stamper = PerformanceMeasureStamper::new()
             .with_end_suffix_namer(LiteralSuffixNamer::new("execution"));
 
-        let fop = Fop::new("test.txt");
-        let options = StamperOptions::default();
-
-        let handle = stamper.start(&options, "TestProcessor", &fop);
-        let _ = handle.promise.await;
+        let mut fop = Fop::new("test.txt");
+        let info = finish(&stamper, &mut fop);
+        assert_eq!(info.measure.as_deref(), Some("fop-test.txt-execution"));
     }
 }
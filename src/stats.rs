@@ -0,0 +1,460 @@
+//! Run statistics: what each processor did, and how long it took.
+
+use crate::fop::Fop;
+use crate::processor::{AsyncProcessor, StreamingProcessor};
+use crate::stream::{FopStream, FopStreamStatic};
+use futures::stream::{BoxStream, StreamExt};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Collects statistics over a pipeline run, for one summary at the end.
+///
+/// Wrap processors with `instrument` to count their items, errors,
+/// durations and waits on bounds, and pass the pipeline's output through
+/// `observe` to count matches per pattern and bytes read. Clones share
+/// the same statistics.
+///
+/// # Example
+///
+/// ```ignore
+/// let stats = RunStats::new();
+/// let glob = Arc::new(stats.instrument(FsstreamProcessor::new()));
+/// let read = Arc::new(stats.instrument(
+///     SemaphoreBoundedProcessor::new(ReadContentProcessor::new(), 16)
+///         .with_wait_stamper(TrueStamper::new()),
+/// ));
+/// let output = stats.observe(apply_processor(apply_streaming(input, glob), read));
+/// let results: Vec<Fop> = output.collect().await;
+/// eprintln!("{}", stats.summary());
+/// ```
+#[derive(Clone, Default)]
+pub struct RunStats {
+    shared: Arc<Shared>,
+}
+
+#[derive(Default)]
+struct Shared {
+    started: Mutex<Option<Instant>>,
+    processors: Mutex<Vec<Arc<Mutex<Record>>>>,
+    matches: Mutex<BTreeMap<String, u64>>,
+    bytes_read: Mutex<u64>,
+    /// Files already observed, with the pattern that matched them
+    seen: Mutex<HashSet<(Option<String>, PathBuf)>>,
+}
+
+/// Raw statistics of one instrumented processor.
+#[derive(Default)]
+struct Record {
+    name: String,
+    items_in: u64,
+    items_out: u64,
+    errors: BTreeMap<&'static str, u64>,
+    durations: Vec<Duration>,
+    waited: Duration,
+}
+
+impl RunStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wrap `processor` so its work is counted, under its own name.
+    ///
+    /// Wrappers such as `Timeout`, `Retry` and `Cancellable` take the name of
+    /// the processor they wrap, and bounded processors name theirs, e.g.
+    /// `Bounded(ReadContentProcessor)`, so rows stay distinguishable.
+    pub fn instrument<P: AsyncProcessor>(&self, processor: P) -> Instrumented<P> {
+        let name = processor.name().to_string();
+        self.instrument_as(name, processor)
+    }
+
+    /// Wrap `processor` so its work is counted under `name`, e.g. to tell
+    /// two stages of the same processor apart.
+    pub fn instrument_as<P: AsyncProcessor>(
+        &self,
+        name: impl Into<String>,
+        processor: P,
+    ) -> Instrumented<P> {
+        let record = Arc::new(Mutex::new(Record {
+            name: name.into(),
+            ..Record::default()
+        }));
+        lock(&self.shared.processors).push(record.clone());
+        Instrumented {
+            inner: processor,
+            record,
+            stats: self.clone(),
+            wait_name: "waitStamp".to_string(),
+        }
+    }
+
+    /// Count matches per pattern and bytes read in a pipeline's output.
+    ///
+    /// Bytes are those of content read from files, not execution output.
+    /// Each file is counted once per pattern, so a fan-out stage such as
+    /// `split_outputs` does not inflate the totals.
+    pub fn observe(&self, input: FopStream<'static>) -> FopStreamStatic {
+        let stats = self.clone();
+        input.inspect(move |fop| stats.observe_one(fop)).boxed()
+    }
+
+    fn observe_one(&self, fop: &Fop) {
+        self.start();
+        if fop.err.is_some() {
+            return;
+        }
+        let pattern = fop.pattern.as_ref().map(|p| p.pattern.to_string());
+        if let Some(filename) = &fop.filename {
+            let key = (pattern.clone(), filename.clone());
+            if !lock(&self.shared.seen).insert(key) {
+                return;
+            }
+        }
+        if let Some(pattern) = pattern {
+            *lock(&self.shared.matches).entry(pattern).or_default() += 1;
+        }
+        // Executed Fops carry output, streamed ones before `execution` too
+        if let (Some(content), None, None) = (&fop.content, &fop.execution, &fop.executable) {
+            *lock(&self.shared.bytes_read) += content.as_bytes().len() as u64;
+        }
+    }
+
+    /// Note the run started, if this is the first thing seen.
+    fn start(&self) {
+        lock(&self.shared.started).get_or_insert_with(Instant::now);
+    }
+
+    /// Summarize the run so far.
+    pub fn summary(&self) -> RunSummary {
+        let processors = lock(&self.shared.processors)
+            .iter()
+            .map(|record| lock(record).summary())
+            .collect();
+        RunSummary {
+            elapsed: lock(&self.shared.started).map_or(Duration::ZERO, |s| s.elapsed()),
+            processors,
+            matches: lock(&self.shared.matches).clone(),
+            bytes_read: *lock(&self.shared.bytes_read),
+        }
+    }
+}
+
+impl Record {
+    fn summary(&self) -> ProcessorSummary {
+        let mut durations = self.durations.clone();
+        durations.sort_unstable();
+        ProcessorSummary {
+            name: self.name.clone(),
+            items_in: self.items_in,
+            items_out: self.items_out,
+            errors: self
+                .errors
+                .iter()
+                .map(|(kind, count)| (kind.to_string(), *count))
+                .collect(),
+            total: durations.iter().sum(),
+            p50: percentile(&durations, 50),
+            p99: percentile(&durations, 99),
+            waited: self.waited,
+        }
+    }
+}
+
+/// Nearest-rank percentile of sorted `durations`.
+fn percentile(durations: &[Duration], p: usize) -> Duration {
+    if durations.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (p * durations.len()).div_ceil(100).max(1);
+    durations[rank - 1]
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A processor whose work is counted in a `RunStats`.
+///
+/// Each input Fop counts as an item in and each result as an item out.
+/// Errors are counted by kind, once for each result carrying one its input
+/// did not. Durations are per input Fop, from the start of processing to
+/// its last result, including any wait on a bound; the waits themselves are
+/// taken from the wait stamps a bounded processor records, see
+/// `with_wait_name`. A streaming execution is timed only until it starts,
+/// when its output stream is returned.
+pub struct Instrumented<P> {
+    inner: P,
+    record: Arc<Mutex<Record>>,
+    stats: RunStats,
+    wait_name: String,
+}
+
+impl<P> Instrumented<P> {
+    /// Set the stamp name waits on bounds are read from, `waitStamp` by
+    /// default. Only bounded processors with a wait stamper record waits.
+    pub fn with_wait_name(mut self, name: impl Into<String>) -> Self {
+        self.wait_name = name.into();
+        self
+    }
+
+    fn track(&self, fop: &Fop) -> Tracker {
+        self.stats.start();
+        Tracker {
+            record: self.record.clone(),
+            wait_name: self.wait_name.clone(),
+            had_err: fop.err.is_some(),
+            had_wait: fop.stamps.contains_key(&self.wait_name),
+            waited: None,
+            items_out: 0,
+            errors: Vec::new(),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl<P> AsyncProcessor for Instrumented<P>
+where
+    P: AsyncProcessor,
+{
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn process_one(&self, fop: Fop) -> Vec<Fop> {
+        let mut tracker = self.track(&fop);
+        let results = self.inner.process_one(fop).await;
+        results.iter().for_each(|result| tracker.output(result));
+        results
+    }
+}
+
+impl<P> StreamingProcessor for Instrumented<P>
+where
+    P: StreamingProcessor,
+{
+    fn process_stream(&self, fop: Fop) -> BoxStream<'static, Fop> {
+        let mut tracker = self.track(&fop);
+        self.inner
+            .process_stream(fop)
+            .inspect(move |result| tracker.output(result))
+            .boxed()
+    }
+}
+
+/// Statistics of one input Fop, recorded when dropped.
+struct Tracker {
+    record: Arc<Mutex<Record>>,
+    wait_name: String,
+    had_err: bool,
+    had_wait: bool,
+    waited: Option<Duration>,
+    items_out: u64,
+    errors: Vec<&'static str>,
+    started: Instant,
+}
+
+impl Tracker {
+    fn output(&mut self, fop: &Fop) {
+        self.items_out += 1;
+        if let (false, Some(err)) = (self.had_err, &fop.err) {
+            self.errors.push(err.kind.name());
+        }
+        // Fan-out copies the wait to every result, so take it once
+        if !self.had_wait && self.waited.is_none() {
+            if let Some(stamp) = fop.stamps.get(&self.wait_name) {
+                self.waited = Some(Duration::from_millis(stamp.duration_ms));
+            }
+        }
+    }
+}
+
+impl Drop for Tracker {
+    fn drop(&mut self) {
+        let mut record = lock(&self.record);
+        record.items_in += 1;
+        record.items_out += self.items_out;
+        for kind in &self.errors {
+            *record.errors.entry(kind).or_default() += 1;
+        }
+        record.durations.push(self.started.elapsed());
+        record.waited += self.waited.unwrap_or_default();
+    }
+}
+
+/// Statistics of one instrumented processor over a run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProcessorSummary {
+    pub name: String,
+    pub items_in: u64,
+    pub items_out: u64,
+    /// Errors this processor produced, by `ErrorKind::name`
+    pub errors: BTreeMap<String, u64>,
+    /// Total time spent on items, concurrent items counted separately
+    pub total: Duration,
+    pub p50: Duration,
+    pub p99: Duration,
+    /// Total time items waited on bounds
+    pub waited: Duration,
+}
+
+/// Summary of a pipeline run, see `RunStats::summary`.
+///
+/// Displays as a table, one row per instrumented processor.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunSummary {
+    /// Time since the first item was seen
+    pub elapsed: Duration,
+    /// Instrumented processors, in the order they were instrumented
+    pub processors: Vec<ProcessorSummary>,
+    /// Files matched per pattern
+    pub matches: BTreeMap<String, u64>,
+    pub bytes_read: u64,
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<28} {:>8} {:>8} {:>10} {:>10} {:>10} {:>10}  errors",
+            "processor", "in", "out", "total", "p50", "p99", "waited"
+        )?;
+        for p in &self.processors {
+            let errors = p
+                .errors
+                .iter()
+                .map(|(kind, count)| format!("{}={}", kind, count))
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(
+                f,
+                "{:<28} {:>8} {:>8} {:>10} {:>10} {:>10} {:>10}  {}",
+                p.name,
+                p.items_in,
+                p.items_out,
+                format!("{:.1?}", p.total),
+                format!("{:.1?}", p.p50),
+                format!("{:.1?}", p.p99),
+                format!("{:.1?}", p.waited),
+                errors
+            )?;
+        }
+        for (pattern, count) in &self.matches {
+            writeln!(f, "{} matched {} files", pattern, count)?;
+        }
+        write!(
+            f,
+            "{} bytes read in {:.1?}",
+            self.bytes_read, self.elapsed
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fop::{Content, ErrorKind, Pattern, ProcessorError, TimestampInfo};
+
+    /// Sleeps `ms` of the name's length, fails names starting with `bad`,
+    /// and records a wait of 7ms.
+    struct TestProcessor;
+
+    impl AsyncProcessor for TestProcessor {
+        fn name(&self) -> &'static str {
+            "TestProcessor"
+        }
+
+        async fn process_one(&self, mut fop: Fop) -> Vec<Fop> {
+            tokio::time::sleep(Duration::from_millis(fop.file_or_pattern.len() as u64)).await;
+            if fop.file_or_pattern.starts_with("bad") {
+                fop.err = Some(ProcessorError::new("TestProcessor", "bad").with_kind(ErrorKind::Timeout));
+            }
+            fop.stamps.insert("waitStamp".to_string(), TimestampInfo::new(7));
+            vec![fop.clone(), fop]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_instrumented() {
+        let stats = RunStats::new();
+        let processor = stats.instrument(TestProcessor);
+
+        for name in ["a", "bb", "bad", "dddddddddddddddddddd"] {
+            processor.process_one(Fop::new(name)).await;
+        }
+        // Errors from earlier stages are not this processor's
+        let mut failed = Fop::new("e");
+        failed.err = Some(ProcessorError::new("Earlier", "failed"));
+        processor.process_one(failed).await;
+
+        let summary = stats.summary();
+        let p = &summary.processors[0];
+        assert_eq!(p.name, "TestProcessor");
+        assert_eq!(processor.name(), "TestProcessor");
+        assert_eq!(p.items_in, 5);
+        assert_eq!(p.items_out, 10);
+        // Both results of the failed item count
+        assert_eq!(p.errors, BTreeMap::from([("timeout".to_string(), 2)]));
+        assert_eq!(p.waited, Duration::from_millis(35));
+        assert!(p.p50 >= Duration::from_millis(2) && p.p50 < Duration::from_millis(20));
+        assert!(p.p99 >= Duration::from_millis(20));
+        assert!(p.total >= Duration::from_millis(27));
+    }
+
+    #[tokio::test]
+    async fn test_observe() {
+        let stats = RunStats::new();
+        let pattern = Arc::new(Pattern::new("*.txt"));
+        let fops: Vec<Fop> = (0..3)
+            .map(|i| {
+                let mut fop = Fop::new("*.txt");
+                fop.pattern = Some(pattern.clone());
+                fop.content = Some(Content::Text("x".repeat(i + 1)));
+                fop
+            })
+            .collect();
+
+        let output: Vec<Fop> = stats
+            .observe(futures::stream::iter(fops).boxed())
+            .collect()
+            .await;
+
+        assert_eq!(output.len(), 3);
+        let summary = stats.summary();
+        assert_eq!(summary.matches, BTreeMap::from([("*.txt".to_string(), 3)]));
+        assert_eq!(summary.bytes_read, 6);
+        assert!(summary.to_string().contains("*.txt matched 3 files"));
+
+        // Lines split from one streamed execution are one match, not reads
+        let fragments: Vec<Fop> = ["a\n", "b\n"]
+            .into_iter()
+            .map(|line| {
+                let mut fop = Fop::new("gen/*.sh");
+                fop.pattern = Some(Arc::new(Pattern::new("gen/*.sh")));
+                fop.filename = Some("gen/run.sh".into());
+                fop.executable = Some(crate::content::exec::Executable::Direct);
+                fop.content = Some(Content::Text(line.to_string()));
+                fop
+            })
+            .collect();
+        let _: Vec<Fop> = stats
+            .observe(futures::stream::iter(fragments).boxed())
+            .collect()
+            .await;
+
+        let summary = stats.summary();
+        assert_eq!(summary.matches["gen/*.sh"], 1);
+        assert_eq!(summary.bytes_read, 6);
+    }
+
+    #[test]
+    fn test_percentile() {
+        let durations: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&durations, 50), Duration::from_millis(50));
+        assert_eq!(percentile(&durations, 99), Duration::from_millis(99));
+        assert_eq!(percentile(&durations[..1], 99), Duration::from_millis(1));
+        assert_eq!(percentile(&[], 50), Duration::ZERO);
+    }
+}
@@ -1,10 +1,13 @@
 //! Stream combinators for async pipeline processing.
 
-use crate::fop::Fop;
-use crate::processor::AsyncProcessor;
+use crate::bound::{self, AdaptiveLimiter, Cost, TokenBucket};
+use crate::fop::{ErrorKind, Fop, ProcessorError};
+use crate::processor::{AsyncProcessor, StreamingProcessor};
 use futures::stream::{BoxStream, StreamExt};
+use std::future::Future;
 use std::sync::Arc;
 use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
 
 /// Type alias for a boxed stream of Fops.
 pub type FopStream<'a> = BoxStream<'a, Fop>;
@@ -52,12 +55,69 @@ where
         .boxed()
 }
 
+/// Apply a streaming processor, flattening each Fop's results into the
+/// output as they are produced.
+///
+/// Input Fops are expanded one at a time, in order, but each result moves
+/// on as soon as it is yielded: the first match of a `**/*` reaches the
+/// next stage while the walk is still running.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use file_or_pattern::basic::FsstreamProcessor;
+/// use file_or_pattern::content::ReadContentProcessor;
+/// use file_or_pattern::fop::Fop;
+/// use file_or_pattern::stream::{apply_bounded, apply_streaming, FopStreamStatic};
+/// use futures::stream;
+/// use futures::StreamExt;
+/// use std::sync::Arc;
+///
+/// # async fn example() {
+/// let input: FopStreamStatic = stream::iter(vec![Fop::new("**/*")]).boxed();
+/// let files = apply_streaming(input, Arc::new(FsstreamProcessor::new()));
+/// let output = apply_bounded(files, Arc::new(ReadContentProcessor::new()), 16);
+/// let results: Vec<Fop> = output.collect().await;
+/// # }
+/// ```
+pub fn apply_streaming<P>(input: FopStream<'static>, processor: Arc<P>) -> FopStreamStatic
+where
+    P: StreamingProcessor + 'static,
+{
+    input
+        .flat_map(move |fop| processor.process_stream(fop))
+        .boxed()
+}
+
+/// Apply a streaming processor to up to `max_concurrency` Fops at a time,
+/// interleaving their results as they are produced.
+///
+/// Like `apply_streaming`, but several patterns are expanded at once, so a
+/// slow walk does not hold up the others.
+pub fn apply_streaming_bounded<P>(
+    input: FopStream<'static>,
+    processor: Arc<P>,
+    max_concurrency: usize,
+) -> FopStreamStatic
+where
+    P: StreamingProcessor + 'static,
+{
+    input
+        .map(move |fop| processor.process_stream(fop))
+        .flatten_unordered(max_concurrency)
+        .boxed()
+}
+
 /// Apply a processor with bounded concurrency using a semaphore.
 ///
 /// Limits concurrent processing to `max_concurrency` operations at a time.
 /// Each processing operation acquires a permit from the semaphore before
 /// executing and releases it automatically on completion.
 ///
+/// Every Fop is admitted at once and queues on the semaphore, so stopping
+/// the input with `until_cancelled` does not stop the queue; use
+/// `apply_bounded_cancellable` to release it too.
+///
 /// # Example
 ///
 /// ```rust,no_run
@@ -81,6 +141,33 @@ pub fn apply_bounded<P>(
     processor: Arc<P>,
     max_concurrency: usize,
 ) -> FopStreamStatic
+where
+    P: AsyncProcessor + 'static,
+{
+    bounded(input, processor, max_concurrency, None)
+}
+
+/// Like `apply_bounded`, but Fops still waiting for a permit once `cancel`
+/// is cancelled come back with a `Cancelled` error instead of being
+/// processed.
+pub fn apply_bounded_cancellable<P>(
+    input: FopStream<'static>,
+    processor: Arc<P>,
+    max_concurrency: usize,
+    cancel: CancellationToken,
+) -> FopStreamStatic
+where
+    P: AsyncProcessor + 'static,
+{
+    bounded(input, processor, max_concurrency, Some(cancel))
+}
+
+fn bounded<P>(
+    input: FopStream<'static>,
+    processor: Arc<P>,
+    max_concurrency: usize,
+    cancel: Option<CancellationToken>,
+) -> FopStreamStatic
 where
     P: AsyncProcessor + 'static,
 {
@@ -90,11 +177,129 @@ where
         .map(move |fop| {
             let proc = processor.clone();
             let sem = semaphore.clone();
+            let cancel = cancel.clone();
+            async move {
+                let Some(permit) = admit(sem.acquire(), cancel.as_ref()).await else {
+                    return cancelled(fop, proc.name());
+                };
+                let _permit = permit.expect("semaphore should not be closed");
+                proc.process_one(fop).await
+            }
+        })
+        .buffer_unordered(usize::MAX)
+        .flat_map(futures::stream::iter)
+        .boxed()
+}
+
+/// Apply a processor with a weighted bound.
+///
+/// Like `apply_bounded`, but each Fop takes as many of the `capacity`
+/// permits as `cost` says, so the bound reflects resource use such as
+/// memory or I/O bandwidth. Costs above `capacity` are capped at it, and
+/// every Fop takes at least one permit. `cancel` releases waiting Fops as
+/// for `apply_bounded_cancellable`.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use file_or_pattern::bound::FileSizeCost;
+/// use file_or_pattern::fop::Fop;
+/// use file_or_pattern::stream::{apply_weighted, FopStreamStatic};
+/// use file_or_pattern::content::ReadContentProcessor;
+/// use futures::stream;
+/// use futures::StreamExt;
+/// use std::sync::Arc;
+///
+/// # async fn example() {
+/// let processor = Arc::new(ReadContentProcessor::new());
+/// let input: FopStreamStatic = stream::iter(vec![Fop::new("test")]).boxed();
+/// // At most 256 MiB of files read at once, in 1 MiB permits
+/// let output = apply_weighted(input, processor, 256, FileSizeCost::new(1 << 20), None);
+/// let results: Vec<Fop> = output.collect().await;
+/// # }
+/// ```
+pub fn apply_weighted<P, C>(
+    input: FopStream<'static>,
+    processor: Arc<P>,
+    capacity: u32,
+    cost: C,
+    cancel: Option<CancellationToken>,
+) -> FopStreamStatic
+where
+    P: AsyncProcessor + 'static,
+    C: Cost + 'static,
+{
+    let capacity = capacity.max(1);
+    let semaphore = Arc::new(Semaphore::new(capacity as usize));
+    let cost: Arc<dyn Cost> = Arc::new(cost);
+
+    input
+        .map(move |fop| {
+            let proc = processor.clone();
+            let sem = semaphore.clone();
+            let cost = cost.clone();
+            let cancel = cancel.clone();
+            async move {
+                let (permits, fop) = bound::weigh(cost, fop, capacity).await;
+                let Some(permit) = admit(sem.acquire_many(permits), cancel.as_ref()).await else {
+                    return cancelled(fop, proc.name());
+                };
+                let _permit = permit.expect("semaphore should not be closed");
+                proc.process_one(fop).await
+            }
+        })
+        .buffer_unordered(usize::MAX)
+        .flat_map(futures::stream::iter)
+        .boxed()
+}
+
+/// Apply a processor with a throughput limit.
+///
+/// Each Fop takes a token from `bucket` before it is processed, so at most
+/// the bucket's rate of Fops start per interval, after an initial burst.
+/// Processing itself is unbounded; combine with a bounded processor to also
+/// cap concurrency. `cancel` releases Fops waiting for a token as for
+/// `apply_bounded_cancellable`, and their tokens go back to the bucket.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use file_or_pattern::bound::TokenBucket;
+/// use file_or_pattern::fop::Fop;
+/// use file_or_pattern::stream::{apply_rate_limited, FopStreamStatic};
+/// use file_or_pattern::content::DoExecuteProcessor;
+/// use futures::stream;
+/// use futures::StreamExt;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// # async fn example() {
+/// let processor = Arc::new(DoExecuteProcessor::new());
+/// let input: FopStreamStatic = stream::iter(vec![Fop::new("test")]).boxed();
+/// // Five executions a second, bursting to ten
+/// let bucket = TokenBucket::new(5, Duration::from_secs(1)).with_burst(10);
+/// let output = apply_rate_limited(input, processor, bucket, None);
+/// let results: Vec<Fop> = output.collect().await;
+/// # }
+/// ```
+pub fn apply_rate_limited<P>(
+    input: FopStream<'static>,
+    processor: Arc<P>,
+    bucket: TokenBucket,
+    cancel: Option<CancellationToken>,
+) -> FopStreamStatic
+where
+    P: AsyncProcessor + 'static,
+{
+    input
+        .map(move |fop| {
+            let proc = processor.clone();
+            let bucket = bucket.clone();
+            let cancel = cancel.clone();
             async move {
-                let _permit = sem
-                    .acquire()
-                    .await
-                    .expect("semaphore should not be closed");
+                if admit(bucket.acquire(), cancel.as_ref()).await.is_none() {
+                    return cancelled(fop, proc.name());
+                }
                 proc.process_one(fop).await
             }
         })
@@ -103,12 +308,146 @@ where
         .boxed()
 }
 
+/// Apply a processor with a concurrency limit that adapts to latency.
+///
+/// Instead of guessing `max_concurrency` for `apply_bounded`, the limiter
+/// raises or lowers the limit from how long each Fop takes, so the same
+/// pipeline suits NVMe, spinning disks and NFS. Keep a clone of the limiter
+/// to watch its current limit. `cancel` releases waiting Fops as for
+/// `apply_bounded_cancellable`.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use file_or_pattern::bound::{AdaptiveLimiter, Gradient};
+/// use file_or_pattern::fop::Fop;
+/// use file_or_pattern::stream::{apply_adaptive, FopStreamStatic};
+/// use file_or_pattern::content::ReadContentProcessor;
+/// use futures::stream;
+/// use futures::StreamExt;
+/// use std::sync::Arc;
+///
+/// # async fn example() {
+/// let processor = Arc::new(ReadContentProcessor::new());
+/// let input: FopStreamStatic = stream::iter(vec![Fop::new("test")]).boxed();
+/// let limiter = AdaptiveLimiter::new(8).with_algorithm(Gradient::new());
+/// let output = apply_adaptive(input, processor, limiter.clone(), None);
+/// let results: Vec<Fop> = output.collect().await;
+/// println!("settled on {} concurrent reads", limiter.limit());
+/// # }
+/// ```
+pub fn apply_adaptive<P>(
+    input: FopStream<'static>,
+    processor: Arc<P>,
+    limiter: AdaptiveLimiter,
+    cancel: Option<CancellationToken>,
+) -> FopStreamStatic
+where
+    P: AsyncProcessor + 'static,
+{
+    input
+        .map(move |fop| {
+            let proc = processor.clone();
+            let limiter = limiter.clone();
+            let cancel = cancel.clone();
+            async move {
+                let Some(permit) = admit(limiter.acquire(), cancel.as_ref()).await else {
+                    return cancelled(fop, proc.name());
+                };
+                let results = proc.process_one(fop).await;
+                // Timeouts are a sign of overload
+                let timed_out = results
+                    .iter()
+                    .any(|f| f.err.as_ref().is_some_and(|e| e.kind == ErrorKind::Timeout));
+                if timed_out {
+                    permit.dropped();
+                }
+                results
+            }
+        })
+        .buffer_unordered(usize::MAX)
+        .flat_map(futures::stream::iter)
+        .boxed()
+}
+
+/// Wait for `permit` unless `cancel` is cancelled first.
+async fn admit<T>(
+    permit: impl Future<Output = T>,
+    cancel: Option<&CancellationToken>,
+) -> Option<T> {
+    let Some(token) = cancel else {
+        return Some(permit.await);
+    };
+    tokio::select! {
+        biased;
+        _ = token.cancelled() => None,
+        permit = permit => Some(permit),
+    }
+}
+
+/// Mark a Fop that was cancelled before `processor` got to it.
+fn cancelled(mut fop: Fop, processor: &str) -> Vec<Fop> {
+    fop.err = Some(ProcessorError::new(processor, "Cancelled").with_kind(ErrorKind::Cancelled));
+    vec![fop]
+}
+
+/// Stop pulling from `input` once `token` is cancelled.
+///
+/// Put this at the head of a pipeline, and wrap its processors in
+/// `Cancellable` (or give them the token with `with_cancellation`), and pass
+/// it to bounded combinators such as `apply_bounded_cancellable`, so a
+/// cancelled pipeline takes no new work and its in-flight Fops come back
+/// with a `Cancelled` error. Bounds applied by the `apply_*` combinators are
+/// waited on before the processor runs; use the bounded processors inside
+/// `Cancellable` for waits that end on cancellation too.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use file_or_pattern::basic::FsstreamProcessor;
+/// use file_or_pattern::fop::Fop;
+/// use file_or_pattern::resilience::Cancellable;
+/// use file_or_pattern::stream::{apply_bounded_cancellable, until_cancelled, FopStreamStatic};
+/// use futures::stream;
+/// use futures::StreamExt;
+/// use std::sync::Arc;
+/// use tokio_util::sync::CancellationToken;
+///
+/// # async fn example() {
+/// let token = CancellationToken::new();
+/// let ctrl_c = token.clone();
+/// tokio::spawn(async move {
+///     tokio::signal::ctrl_c().await.ok();
+///     ctrl_c.cancel();
+/// });
+///
+/// let input: FopStreamStatic = stream::iter(vec![Fop::new("**/*.rs")]).boxed();
+/// let glob = FsstreamProcessor::new().with_cancellation(token.clone());
+/// let input = until_cancelled(input, token.clone());
+/// let output = apply_bounded_cancellable(input, Arc::new(glob), 4, token);
+/// let results: Vec<Fop> = output.collect().await;
+/// # }
+/// ```
+pub fn until_cancelled(input: FopStream<'static>, token: CancellationToken) -> FopStreamStatic {
+    input.take_until(token.cancelled_owned()).boxed()
+}
+
+/// Fan streaming executions out into one Fop per line or chunk of output.
+///
+/// Fops carrying an `output` stream from a `DoExecuteProcessor` configured
+/// `with_streaming` are replaced by their output as it arrives; see
+/// `split_output`. Other Fops pass through.
+pub fn split_outputs(input: FopStream<'static>) -> FopStreamStatic {
+    input.flat_map(crate::content::exec::split_output).boxed()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::processor::AsyncProcessor;
     use crate::fop::Fop;
     use futures::StreamExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     #[derive(Clone)]
     struct TestAsyncProcessor;
@@ -123,6 +462,35 @@ mod tests {
         }
     }
 
+    /// Yields `<name>-first` at once, and `<name>-last` once the gate opens.
+    struct GatedProcessor {
+        gate: CancellationToken,
+    }
+
+    impl AsyncProcessor for GatedProcessor {
+        fn name(&self) -> &'static str {
+            "GatedProcessor"
+        }
+
+        async fn process_one(&self, fop: Fop) -> Vec<Fop> {
+            self.process_stream(fop).collect().await
+        }
+    }
+
+    impl StreamingProcessor for GatedProcessor {
+        fn process_stream(&self, fop: Fop) -> FopStreamStatic {
+            let gate = self.gate.clone();
+            let first = Fop::new(format!("{}-first", fop.file_or_pattern));
+            let last = Fop::new(format!("{}-last", fop.file_or_pattern));
+            futures::stream::iter([first])
+                .chain(futures::stream::once(async move {
+                    gate.cancelled().await;
+                    last
+                }))
+                .boxed()
+        }
+    }
+
     #[tokio::test]
     async fn test_apply_processor() {
         let processor = Arc::new(TestAsyncProcessor);
@@ -157,4 +525,156 @@ mod tests {
 
         assert_eq!(results.len(), 5);
     }
+
+    #[tokio::test]
+    async fn test_apply_bounded_cancel() {
+        let processor = Arc::new(PeakProcessor::default());
+        let inputs: Vec<_> = (0..4).map(|i| Fop::new(format!("test{}", i))).collect();
+        let stream: FopStream<'static> = futures::stream::iter(inputs).boxed();
+        let token = CancellationToken::new();
+        let output = apply_bounded_cancellable(stream, processor.clone(), 1, token.clone());
+
+        let canceller = tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            token.cancel();
+        });
+        let results: Vec<Fop> = output.collect().await;
+        canceller.await.unwrap();
+
+        // The running Fop finishes, the queued ones are released unprocessed
+        assert_eq!(results.len(), 4);
+        let cancelled: Vec<_> = results.iter().filter_map(|f| f.err.as_ref()).collect();
+        assert_eq!(cancelled.len(), 3);
+        assert!(cancelled.iter().all(|e| e.kind == ErrorKind::Cancelled));
+        assert_eq!(cancelled[0].processor, "PeakProcessor");
+    }
+
+    /// Sleeps briefly, recording the most Fops it ever processed at once.
+    #[derive(Default)]
+    struct PeakProcessor {
+        running: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    impl AsyncProcessor for PeakProcessor {
+        fn name(&self) -> &'static str {
+            "PeakProcessor"
+        }
+
+        async fn process_one(&self, fop: Fop) -> Vec<Fop> {
+            let now = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            vec![fop]
+        }
+    }
+
+    async fn peak_weighted(capacity: u32, cost: impl Cost + 'static) -> usize {
+        let processor = Arc::new(PeakProcessor::default());
+        let inputs: Vec<_> = (0..6).map(|i| Fop::new(format!("test{}", i))).collect();
+        let stream: FopStream<'static> = futures::stream::iter(inputs).boxed();
+        let output = apply_weighted(stream, processor.clone(), capacity, cost, None);
+        let results: Vec<Fop> = output.collect().await;
+
+        assert_eq!(results.len(), 6);
+        processor.peak.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn test_apply_weighted() {
+        // Each takes half the capacity
+        assert_eq!(peak_weighted(4, |_: &Fop| 2).await, 2);
+        // Oversized items are capped at the capacity and run alone
+        assert_eq!(peak_weighted(4, |_: &Fop| 10).await, 1);
+        // Free items still take a permit, and a zero capacity still admits one
+        assert_eq!(peak_weighted(2, |_: &Fop| 0).await, 2);
+        assert_eq!(peak_weighted(0, |_: &Fop| 1).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_apply_rate_limited() {
+        let processor = Arc::new(TestAsyncProcessor);
+        let inputs: Vec<_> = (0..3).map(|i| Fop::new(format!("test{}", i))).collect();
+        let stream: FopStream<'static> = futures::stream::iter(inputs).boxed();
+        let bucket = TokenBucket::new(50, std::time::Duration::from_secs(1)).with_burst(1);
+
+        let started = std::time::Instant::now();
+        let results: Vec<Fop> = apply_rate_limited(stream, processor, bucket, None)
+            .collect()
+            .await;
+
+        assert_eq!(results.len(), 3);
+        assert!(started.elapsed() >= std::time::Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn test_apply_adaptive() {
+        let processor = Arc::new(TestAsyncProcessor);
+        let inputs: Vec<_> = (0..5).map(|i| Fop::new(format!("test{}", i))).collect();
+        let stream: FopStream<'static> = futures::stream::iter(inputs).boxed();
+        let limiter = AdaptiveLimiter::new(1).with_bounds(1, 8);
+
+        let results: Vec<Fop> = apply_adaptive(stream, processor, limiter.clone(), None)
+            .collect()
+            .await;
+
+        assert_eq!(results.len(), 5);
+        assert!(limiter.limit() > 1);
+        assert_eq!(limiter.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_until_cancelled() {
+        let token = CancellationToken::new();
+        let (tx, rx) = futures::channel::mpsc::unbounded();
+        let mut output = until_cancelled(rx.boxed(), token.clone());
+
+        tx.unbounded_send(Fop::new("first")).unwrap();
+        assert_eq!(&*output.next().await.unwrap().file_or_pattern, "first");
+
+        token.cancel();
+        tx.unbounded_send(Fop::new("second")).unwrap();
+        assert!(output.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_apply_streaming() {
+        let gate = CancellationToken::new();
+        let processor = Arc::new(GatedProcessor { gate: gate.clone() });
+        let input: FopStream<'static> = futures::stream::iter(vec![Fop::new("a")]).boxed();
+        let mut output = apply_processor(
+            apply_streaming(input, processor),
+            Arc::new(TestAsyncProcessor),
+        );
+
+        // The first result reaches the next stage before the expansion ends
+        let first = output.next().await.unwrap();
+        assert_eq!(&*first.file_or_pattern, "a-first");
+
+        gate.cancel();
+        let rest: Vec<Fop> = output.collect().await;
+        assert_eq!(rest.len(), 1);
+        assert_eq!(&*rest[0].file_or_pattern, "a-last");
+    }
+
+    #[tokio::test]
+    async fn test_apply_streaming_bounded() {
+        let gate = CancellationToken::new();
+        let processor = Arc::new(GatedProcessor { gate: gate.clone() });
+        let inputs = vec![Fop::new("a"), Fop::new("b")];
+        let input: FopStream<'static> = futures::stream::iter(inputs).boxed();
+        let mut output = apply_streaming_bounded(input, processor, 2);
+
+        // Both expansions run at once, so both first results arrive
+        let mut firsts = vec![
+            output.next().await.unwrap().file_or_pattern.to_string(),
+            output.next().await.unwrap().file_or_pattern.to_string(),
+        ];
+        firsts.sort();
+        assert_eq!(firsts, ["a-first", "b-first"]);
+
+        gate.cancel();
+        assert_eq!(output.count().await, 2);
+    }
 }